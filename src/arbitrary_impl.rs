@@ -0,0 +1,185 @@
+use std::hash::{BuildHasher, Hash};
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::HashMap;
+
+impl<'a, K, V, S> Arbitrary<'a> for HashMap<K, V, S>
+where
+    K: Arbitrary<'a> + Hash + Eq,
+    V: Arbitrary<'a>,
+    S: BuildHasher + Default,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.arbitrary_iter()?.collect()
+    }
+
+    fn arbitrary_take_rest(u: Unstructured<'a>) -> Result<Self> {
+        u.arbitrary_take_rest_iter()?.collect()
+    }
+}
+
+/// The operation-sequence harness behind `fuzz/fuzz_targets/map_ops.rs`.
+///
+/// Every operation is applied both to a [`HashMap`] and to
+/// `std::collections::HashMap`, and any difference in what they return
+/// panics. Keys are `u8` so that a short input already produces collisions,
+/// overwrites and several resizes.
+#[doc(hidden)]
+pub mod fuzz {
+    use std::collections::HashMap as StdMap;
+    use std::mem;
+
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use crate::{DefaultState, Entry, HashMap, Table};
+
+    #[derive(Arbitrary, Debug)]
+    pub enum Op {
+        Insert(u8, u16),
+        Remove(u8),
+        RemoveEntry(u8),
+        Get(u8),
+        GetMut(u8, u16),
+        ContainsKey(u8),
+        OrInsert(u8, u16),
+        AndModify(u8, u16),
+        RemoveOccupied(u8),
+        Retain(u8),
+        Iterate,
+        Clear,
+        ShrinkToFit,
+        Reserve(u8),
+    }
+
+    /// Decodes `data` as a starting map plus a list of operations and runs
+    /// them. Inputs that do not decode are ignored. With the `swiss`
+    /// feature the same operations also run on a copy of the map on a
+    /// [`SwissTable`](crate::SwissTable), and the two must end up equal.
+    pub fn run(data: &[u8]) {
+        let mut u = Unstructured::new(data);
+        let Ok(map) = HashMap::<u8, u16>::arbitrary(&mut u) else {
+            return;
+        };
+        let Ok(ops) = Vec::<Op>::arbitrary_take_rest(u) else {
+            return;
+        };
+        #[cfg(not(feature = "swiss"))]
+        check_ops(map, &ops);
+        #[cfg(feature = "swiss")]
+        {
+            let swiss: crate::SwissMap<u8, u16> = map.iter().map(|(&k, &v)| (k, v)).collect();
+            let swiss = check_ops(swiss, &ops);
+            assert!(check_ops(map, &ops) == swiss);
+        }
+    }
+
+    /// Applies `ops` to `map` and to a std copy of it, asserting after every
+    /// step that both agree, and hands the map back.
+    pub fn check_ops<T: Table>(
+        mut map: HashMap<u8, u16, DefaultState, T>,
+        ops: &[Op],
+    ) -> HashMap<u8, u16, DefaultState, T> {
+        let mut model: StdMap<u8, u16> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(map.len(), model.len());
+
+        for op in ops {
+            match *op {
+                Op::Insert(k, v) => assert_eq!(map.insert(k, v), model.insert(k, v)),
+                Op::Remove(k) => assert_eq!(map.remove(&k), model.remove(&k)),
+                Op::RemoveEntry(k) => assert_eq!(map.remove_entry(&k), model.remove_entry(&k)),
+                Op::Get(k) => assert_eq!(map.get(&k), model.get(&k)),
+                Op::GetMut(k, v) => {
+                    let ours = map.get_mut(&k).map(|slot| mem::replace(slot, v));
+                    let theirs = model.get_mut(&k).map(|slot| mem::replace(slot, v));
+                    assert_eq!(ours, theirs);
+                }
+                Op::ContainsKey(k) => assert_eq!(map.contains_key(&k), model.contains_key(&k)),
+                Op::OrInsert(k, v) => {
+                    assert_eq!(*map.entry(k).or_insert(v), *model.entry(k).or_insert(v));
+                }
+                Op::AndModify(k, v) => {
+                    let ours = *map.entry(k).and_modify(|x| *x ^= v).or_insert(v);
+                    let theirs = *model.entry(k).and_modify(|x| *x ^= v).or_insert(v);
+                    assert_eq!(ours, theirs);
+                }
+                Op::RemoveOccupied(k) => {
+                    let ours = match map.entry(k) {
+                        Entry::Occupied(e) => Some(e.remove_entry()),
+                        Entry::Vacant(_) => None,
+                    };
+                    let theirs = match model.entry(k) {
+                        std::collections::hash_map::Entry::Occupied(e) => Some(e.remove_entry()),
+                        std::collections::hash_map::Entry::Vacant(_) => None,
+                    };
+                    assert_eq!(ours, theirs);
+                }
+                Op::Retain(m) => {
+                    let m = m.max(1);
+                    map.retain(|&k, _| k % m != 0);
+                    model.retain(|&k, _| k % m != 0);
+                }
+                Op::Iterate => {
+                    assert_eq!(map.iter().len(), model.len());
+                    let mut ours: Vec<_> = map.iter().map(|(&k, &v)| (k, v)).collect();
+                    let mut theirs: Vec<_> = model.iter().map(|(&k, &v)| (k, v)).collect();
+                    ours.sort_unstable();
+                    theirs.sort_unstable();
+                    assert_eq!(ours, theirs);
+                }
+                Op::Clear => {
+                    map.clear();
+                    model.clear();
+                }
+                Op::ShrinkToFit => map.shrink_to_fit(),
+                Op::Reserve(n) => map.reserve(n as usize),
+            }
+            assert_eq!(map.len(), model.len());
+            assert!(map.capacity() >= map.len());
+        }
+        assert_eq!(map, model);
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A tiny xorshift so the test is reproducible without extra dev-deps.
+    fn random_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn arbitrary_map() {
+        let data = random_bytes(1, 256);
+        let map = HashMap::<u8, u16>::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        let rest = HashMap::<u8, u16>::arbitrary_take_rest(Unstructured::new(&data)).unwrap();
+        assert!(!rest.is_empty());
+
+        let pairs: Vec<(u8, u16)> = Unstructured::new(&data)
+            .arbitrary_take_rest_iter()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(rest, pairs.into_iter().collect::<HashMap<_, _>>());
+        assert!(map.len() <= rest.len());
+    }
+
+    #[test]
+    fn fuzz_harness_bounded() {
+        for seed in 0..256 {
+            let len = 64 + (seed as usize * 37) % 2048;
+            fuzz::run(&random_bytes(seed, len));
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use alloc::vec::Vec;
+
+use crate::TryReserveError;
+
+/// A Bloom filter over the hashes of a map's keys, for
+/// [`HashMap::with_miss_filter`](crate::HashMap::with_miss_filter).
+///
+/// Every live entry's hash has its bits set, so a hash with any bit clear
+/// is definitely absent and the lookup never needs to touch the buckets.
+/// Bits can't be cleared for one key without risking another's, so
+/// removals only count how stale the filter is getting, and the map
+/// rebuilds it from the arena once dead keys outnumber live ones.
+#[derive(Clone, Debug)]
+pub(crate) struct MissFilter {
+    words: Vec<u64>,
+    bits_per_key: usize,
+    probes: u32,
+    /// Entries removed since the last rebuild, whose bits are still set.
+    stale: usize,
+}
+
+impl MissFilter {
+    /// An empty filter; it gets its size from the first `rebuild`.
+    pub(crate) fn new(bits_per_key: usize) -> Self {
+        assert!(bits_per_key > 0, "a miss filter needs at least one bit per key");
+        MissFilter {
+            words: Vec::new(),
+            bits_per_key,
+            // k = bits per key * ln 2 minimises false positives
+            probes: ((bits_per_key * 69 + 50) / 100).clamp(1, 16) as u32,
+            stale: 0,
+        }
+    }
+
+    pub(crate) fn bits_per_key(&self) -> usize {
+        self.bits_per_key
+    }
+
+    /// Bit positions for `hash`, by double hashing. The filter's own mixing
+    /// keeps them independent of which bucket the hash picks.
+    fn positions(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let mask = (self.words.len() * 64 - 1) as u64;
+        let first = (hash ^ (hash >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+        let step = (first ^ (first >> 33)).wrapping_mul(0xc4ce_b9fe_1a85_ec53) | 1;
+        (0..u64::from(self.probes)).map(move |i| (first.wrapping_add(i.wrapping_mul(step)) & mask) as usize)
+    }
+
+    pub(crate) fn insert(&mut self, hash: u64) {
+        for bit in self.positions(hash) {
+            self.words[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// False only if no key with this hash was inserted since the last
+    /// rebuild.
+    pub(crate) fn may_contain(&self, hash: u64) -> bool {
+        self.positions(hash).all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Heap bytes in use by the bit array, and allocated beyond that.
+    pub(crate) fn heap_bytes(&self) -> (usize, usize) {
+        let word = core::mem::size_of::<u64>();
+        (self.words.len() * word, (self.words.capacity() - self.words.len()) * word)
+    }
+
+    /// Records a removal, returning whether the filter is now stale enough
+    /// next to the `len` remaining entries to be worth rebuilding.
+    pub(crate) fn note_removal(&mut self, len: usize) -> bool {
+        self.stale += 1;
+        self.stale > len
+    }
+
+    /// Words in a filter sized for `capacity` keys, or `None` if the bit
+    /// count doesn't fit in a `usize`.
+    fn words_for(&self, capacity: usize) -> Option<usize> {
+        let nbits = capacity
+            .saturating_mul(self.bits_per_key)
+            .max(64)
+            .checked_next_power_of_two()?;
+        Some(nbits / 64)
+    }
+
+    /// Allocates room for a filter sized for `capacity` keys without
+    /// touching its bits, so the next `rebuild` to that size can't fail.
+    pub(crate) fn try_reserve(&mut self, capacity: usize) -> Result<(), TryReserveError> {
+        let words = self.words_for(capacity).ok_or(TryReserveError::CapacityOverflow)?;
+        self.words
+            .try_reserve_exact(words.saturating_sub(self.words.len()))
+            .map_err(|_| TryReserveError::AllocError)
+    }
+
+    /// Clears the filter, sized for `capacity` keys, and inserts `hashes`.
+    pub(crate) fn rebuild(&mut self, capacity: usize, hashes: impl Iterator<Item = u64>) {
+        let words = self.words_for(capacity).expect("capacity overflow");
+        self.words.clear();
+        self.words.resize(words, 0);
+        self.stale = 0;
+        for hash in hashes {
+            self.insert(hash);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::hash::BuildHasher;
+
+    use super::*;
+    use crate::DeterministicState;
+
+    #[test]
+    fn no_false_negatives_and_few_false_positives() {
+        let state = DeterministicState::new(3);
+        let mut filter = MissFilter::new(10);
+        assert_eq!(filter.probes, 7);
+        filter.rebuild(10_000, (0..10_000u32).map(|i| state.hash_one(i)));
+        assert!((0..10_000u32).all(|i| filter.may_contain(state.hash_one(i))));
+        let false_positives = (10_000..110_000u32)
+            .filter(|&i| filter.may_contain(state.hash_one(i)))
+            .count();
+        // about 1% at ten bits a key; the table rounds its size up
+        assert!(false_positives < 2_000, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn rebuild_forgets_and_resizes() {
+        let mut filter = MissFilter::new(1);
+        filter.rebuild(0, core::iter::empty());
+        assert_eq!(filter.words.len(), 1);
+        filter.insert(42);
+        assert!(filter.may_contain(42));
+        assert!(!filter.note_removal(1));
+        assert!(filter.note_removal(1));
+        filter.rebuild(1000, core::iter::empty());
+        assert_eq!(filter.words.len(), 1024 / 64);
+        assert!(!filter.may_contain(42));
+        assert_eq!(filter.stale, 0);
+    }
+}
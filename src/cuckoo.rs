@@ -0,0 +1,759 @@
+//! A cuckoo hashing map: every key has exactly two places it can live.
+//!
+//! [`CuckooMap`] keeps its entries in a dense arena like the crate's other
+//! maps, and finds them through two tables of arena indices. A key belongs
+//! either in the first table, at the slot picked by its ordinary hash, or
+//! in the second, at the slot picked by a second hash mixed from the first
+//! and a per-map seed. A lookup checks those two slots, and a small stash.
+//!
+//! Inserting into an occupied slot evicts its entry to that entry's other
+//! slot, which may evict another, and so on. A chain of evictions that runs
+//! too long is taken to be a cycle, and the entry left over waits in the
+//! stash. Once that fills up, the map picks a new seed, which moves every
+//! key's second slot, and lays everything out again.
+//!
+//! Keys the hasher gives equal hashes share both slots under every seed,
+//! so no layout separates more than two of them. When no seed empties the
+//! stash the map keeps the crowded layout and only tries again once the
+//! stash has doubled: such keys cost a linear scan, like a long chain
+//! would in [`HashMap`], but inserting them never fails.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::{
+    bucket_index, make_hash, DefaultState, Drain, Equivalent, HashMap, IntoIter, IntoKeys,
+    IntoValues, Iter, IterMut, Keys, Values, ValuesMut, NONE,
+};
+
+/// Slots in each table of the smallest allocated map.
+const MIN_SLOTS: usize = 4;
+
+/// Evictions one insertion may cause before it is treated as a cycle.
+/// Paths this long are vanishingly rare below the load the tables are kept
+/// at, unless the keys really can't all be placed.
+const MAX_KICKS: usize = 128;
+
+/// Entries the stash holds before an insertion reseeds the map.
+const STASH_LEN: usize = 4;
+
+/// Reseeded layouts tried before settling for a crowded stash.
+const MAX_REHASHES: usize = 16;
+
+/// The second hash of an entry whose first hash is `hash`. Mixing it from
+/// the first means the map's hasher only ever sees each key once, and a new
+/// seed moves the second slots whatever that hasher does with its input.
+fn alt_hash(hash: u64, seed: u64) -> u64 {
+    let mut x = hash ^ seed;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Slots per table for `capacity` entries, keeping the tables at most half
+/// full overall, or `None` if that does not fit in a `usize`.
+fn slots_for(capacity: usize) -> Option<usize> {
+    match capacity {
+        0 => Some(0),
+        n => Some(n.checked_next_power_of_two()?.max(MIN_SLOTS)),
+    }
+}
+
+/// A hash map with worst-case constant-time lookups, by cuckoo hashing.
+///
+/// It offers the core of [`HashMap`]'s API, with the
+/// crate's iterators and an entry API of its own in this module:
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::CuckooMap;
+///
+/// let mut map = CuckooMap::new();
+/// map.insert("a", 1);
+/// *map.entry("b").or_insert(0) += 2;
+/// assert_eq!(map.get("a"), Some(&1));
+/// assert_eq!(map.remove("b"), Some(2));
+/// assert_eq!(map.len(), 1);
+/// ```
+///
+/// Lookups stay constant-time as long as the hasher tells keys apart; keys
+/// it gives equal hashes end up in the stash, which is scanned.
+pub struct CuckooMap<K, V, S = DefaultState> {
+    // each entry carries its first hash, and its second follows from that
+    // and `seed`; every arena index is in one slot or in `stash`
+    entries: Vec<(u64, K, V)>,
+    tables: [Vec<usize>; 2],
+    stash: Vec<usize>,
+    // more than this many stashed entries makes an insertion reseed
+    stash_limit: usize,
+    seed: u64,
+    hash_builder: S,
+}
+
+#[cfg(feature = "std")]
+impl<K, V> CuckooMap<K, V, DefaultState> {
+    pub fn new() -> Self {
+        CuckooMap::with_hasher(DefaultState::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        CuckooMap::with_capacity_and_hasher(capacity, DefaultState::default())
+    }
+}
+
+impl<K, V, S> CuckooMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        CuckooMap::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let nslots = slots_for(capacity).expect("capacity overflow");
+        CuckooMap {
+            entries: Vec::with_capacity(capacity),
+            tables: [alloc::vec![NONE; nslots], alloc::vec![NONE; nslots]],
+            stash: Vec::new(),
+            stash_limit: STASH_LEN,
+            seed: 0,
+            hash_builder,
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of entries the map can hold before its tables grow: as many
+    /// as one table has slots.
+    pub fn capacity(&self) -> usize {
+        self.tables[0].len()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.unlink_all();
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut index = 0;
+        while let Some((_, ekey, evalue)) = self.entries.get_mut(index) {
+            if f(ekey, evalue) {
+                index += 1;
+            } else {
+                // the last entry moves into `index`, so don't advance
+                self.remove_at(index);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { inner: self.entries.iter() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut { inner: self.entries.iter_mut() }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { inner: self.iter_mut() }
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys { inner: self.into_iter() }
+    }
+
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues { inner: self.into_iter() }
+    }
+
+    /// Removes every entry, handing them out by value. The tables keep
+    /// their size, as with [`clear`](CuckooMap::clear).
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        self.unlink_all();
+        Drain {
+            inner: self.entries.drain(..),
+            marker: PhantomData,
+        }
+    }
+
+    fn unlink_all(&mut self) {
+        for table in &mut self.tables {
+            table.fill(NONE);
+        }
+        self.stash.clear();
+        self.stash_limit = STASH_LEN;
+    }
+
+    /// Slot for a first hash in `table`. The tables must be allocated.
+    fn slot(&self, table: usize, hash: u64) -> usize {
+        let hash = match table {
+            0 => hash,
+            _ => alt_hash(hash, self.seed),
+        };
+        bucket_index(hash, self.tables[0].len())
+    }
+
+    /// Table and slot that point at `entries[index]`, or `None` if it is
+    /// in the stash.
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        let hash = self.entries[index].0;
+        (0..2)
+            .map(|table| (table, self.slot(table, hash)))
+            .find(|&(table, slot)| self.tables[table][slot] == index)
+    }
+
+    /// Points whatever refers to `entries[index]` at `to` instead, or drops
+    /// the reference if `to` is `NONE`.
+    fn repoint(&mut self, index: usize, to: usize) {
+        if let Some((table, slot)) = self.locate(index) {
+            self.tables[table][slot] = to;
+            return;
+        }
+        let at = self.stash.iter().position(|&i| i == index).expect("entry has no slot");
+        match to {
+            NONE => {
+                self.stash.swap_remove(at);
+            }
+            _ => self.stash[at] = to,
+        }
+    }
+
+    /// Finds `entries[index]` a slot, evicting whatever is in the way to
+    /// that entry's other slot, and so on. Returns the entry left without a
+    /// slot if that goes on for `MAX_KICKS` evictions.
+    fn place(&mut self, mut index: usize) -> Result<(), usize> {
+        for table in 0..2 {
+            let slot = self.slot(table, self.entries[index].0);
+            if self.tables[table][slot] == NONE {
+                self.tables[table][slot] = index;
+                return Ok(());
+            }
+        }
+
+        let mut table = 0;
+        for _ in 0..MAX_KICKS {
+            let slot = self.slot(table, self.entries[index].0);
+            index = mem::replace(&mut self.tables[table][slot], index);
+            if index == NONE {
+                return Ok(());
+            }
+            // the evicted entry sat in its slot of `table`; its other one
+            // is in the other table
+            table = 1 - table;
+        }
+        Err(index)
+    }
+
+    /// Adds `entries[index]` to the tables, or failing that the stash,
+    /// reseeding if the stash grows past its limit.
+    fn link(&mut self, index: usize) {
+        if let Err(homeless) = self.place(index) {
+            self.stash.push(homeless);
+            if self.stash.len() > self.stash_limit {
+                self.rehash();
+            }
+        }
+    }
+
+    /// Lays every entry out over fresh tables of `nslots` slots each under
+    /// the current seed, stashing those left without a slot. Returns
+    /// whether the stash stayed within its limit.
+    fn rebuild(&mut self, nslots: usize) -> bool {
+        for table in &mut self.tables {
+            table.clear();
+            table.resize(nslots, NONE);
+        }
+        self.stash.clear();
+        for index in 0..self.entries.len() {
+            if let Err(homeless) = self.place(index) {
+                self.stash.push(homeless);
+            }
+        }
+        self.stash.len() <= self.stash_limit
+    }
+
+    /// Picks fresh seeds until a layout leaves at most `STASH_LEN` entries
+    /// stashed. If none does, the keys left over share their hashes and
+    /// the last layout is kept, with room for the stash to double before
+    /// the next try.
+    fn rehash(&mut self) {
+        let nslots = self.tables[0].len();
+        self.stash_limit = STASH_LEN;
+        for _ in 0..MAX_REHASHES {
+            self.seed = self.seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            if self.rebuild(nslots) {
+                return;
+            }
+        }
+        self.stash_limit = self.stash.len() * 2;
+    }
+
+    /// Resizes the tables to `nslots`, rehashing if the current seed
+    /// doesn't fit everything at the new size.
+    fn resize(&mut self, nslots: usize) {
+        if !self.rebuild(nslots) {
+            self.rehash();
+        }
+    }
+
+    /// Removes `entries[index]`. The last entry is swapped into its place
+    /// in the arena, and its slot updated.
+    fn remove_at(&mut self, index: usize) -> (u64, K, V) {
+        self.repoint(index, NONE);
+        let last = self.entries.len() - 1;
+        if index != last {
+            self.repoint(last, index);
+        }
+        self.entries.swap_remove(index)
+    }
+}
+
+impl<K, V, S> CuckooMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Arena index of the entry for `key`, found in one of its two slots
+    /// or in the stash.
+    fn lookup<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let hash = make_hash(&self.hash_builder, key);
+        let is_match = |&index: &usize| {
+            index != NONE && self.entries[index].0 == hash && key.equivalent(&self.entries[index].1)
+        };
+        (0..2)
+            .map(|table| self.tables[table][self.slot(table, hash)])
+            .chain(self.stash.iter().copied())
+            .find(is_match)
+    }
+
+    /// Adds an entry to the arena and gives it a slot. Returns its arena
+    /// index.
+    fn push_entry(&mut self, hash: u64, key: K, value: V) -> usize {
+        let index = self.entries.len();
+        self.entries.push((hash, key, value));
+        self.link(index);
+        index
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        if self.len() >= self.capacity() {
+            self.resize((self.tables[0].len() * 2).max(MIN_SLOTS));
+        }
+        match self.lookup(&key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => {
+                let hash = make_hash(&self.hash_builder, &key);
+                Entry::Vacant(VacantEntry { key, hash, map: self })
+            }
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => Some(e.insert(value)),
+            Entry::Vacant(e) => {
+                e.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let (_, ekey, evalue) = &self.entries[self.lookup(key)?];
+        Some((ekey, evalue))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.entries[index].2)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        let (_, ekey, evalue) = self.remove_at(index);
+        Some((ekey, evalue))
+    }
+
+    /// Makes room for at least `additional` more entries.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len().checked_add(additional).expect("capacity overflow");
+        self.entries.reserve(additional);
+        if needed > self.capacity() {
+            self.resize(slots_for(needed).expect("capacity overflow"));
+        }
+    }
+
+    /// Shrinks the tables as far as the current entries allow.
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        self.resize(slots_for(self.len()).expect("capacity overflow"));
+        for table in &mut self.tables {
+            table.shrink_to_fit();
+        }
+    }
+}
+
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    map: &'a mut CuckooMap<K, V, S>,
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    key: K,
+    hash: u64,
+    map: &'a mut CuckooMap<K, V, S>,
+}
+
+/// A view into a single entry of a [`CuckooMap`], from
+/// [`CuckooMap::entry`].
+pub enum Entry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+impl<'a, K: 'a, V: 'a, S> OccupiedEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.map.entries[self.index].1
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.entries[self.index].2
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entries[self.index].2
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entries[self.index].2
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        let (_, ekey, evalue) = self.map.remove_at(self.index);
+        (ekey, evalue)
+    }
+}
+
+impl<'a, K, V, S> VacantEntry<'a, K, V, S>
+where
+    K: Hash + Eq + 'a,
+    V: 'a,
+    S: BuildHasher,
+{
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S> {
+        let index = self.map.push_entry(self.hash, self.key, value);
+        OccupiedEntry { map: self.map, index }
+    }
+}
+
+entry_impls!(where K: Hash + Eq, S: BuildHasher);
+
+impl<K: Clone, V: Clone, S: Clone> Clone for CuckooMap<K, V, S> {
+    fn clone(&self) -> Self {
+        CuckooMap {
+            entries: self.entries.clone(),
+            tables: self.tables.clone(),
+            stash: self.stash.clone(),
+            stash_limit: self.stash_limit,
+            seed: self.seed,
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+map_impls!(CuckooMap);
+
+impl<K, V, S> IntoIterator for CuckooMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.entries.into_iter(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::hash::{BuildHasherDefault, Hasher};
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::test_util::{differential, Colliding};
+
+    // 64-bit FNV-1a, deterministic so tests can pick colliding keys.
+    struct Fnv(u64);
+
+    impl Default for Fnv {
+        fn default() -> Self {
+            Fnv(0xcbf2_9ce4_8422_2325)
+        }
+    }
+
+    impl Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    // FNV cut down to two bits: there are only four hashes to go round,
+    // however big the tables grow.
+    #[derive(Default)]
+    struct TwoBitHasher(Fnv);
+
+    impl Hasher for TwoBitHasher {
+        fn finish(&self) -> u64 {
+            self.0.finish() & 3
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0.write(bytes);
+        }
+    }
+
+    /// Every entry sits in one of its two slots or else in the stash, with
+    /// the right hash, and nothing else is in the tables or the stash.
+    fn check_tables<K: Hash + Eq, V, S: BuildHasher>(map: &CuckooMap<K, V, S>) {
+        for (index, (hash, key, _)) in map.entries.iter().enumerate() {
+            assert_eq!(*hash, map.hash_builder.hash_one(key));
+            let stashed = map.stash.iter().filter(|&&i| i == index).count();
+            assert_eq!(stashed, usize::from(map.locate(index).is_none()), "entry {}", index);
+        }
+        let used = map.tables.iter().flatten().filter(|&&index| index != NONE).count();
+        assert_eq!(used + map.stash.len(), map.len());
+    }
+
+    #[test]
+    fn differential_against_chaining() {
+        for seed in 0..8 {
+            // few keys, so inserts and removals keep hitting the same ones
+            differential(seed, 64, CuckooMap::new(), check_tables);
+            differential(seed, 4096, CuckooMap::new(), check_tables);
+        }
+        for seed in 0..2 {
+            differential(seed, 200, CuckooMap::with_hasher(Colliding::default()), check_tables);
+        }
+    }
+
+    #[test]
+    fn overflowing_stash_forces_reseed() {
+        let build = BuildHasherDefault::<Fnv>::default();
+        let map: CuckooMap<u32, u32, _> = CuckooMap::with_capacity_and_hasher(64, build.clone());
+        let slots = |key: u32| {
+            let hash = build.hash_one(key);
+            (map.slot(0, hash), map.slot(1, hash))
+        };
+        // of the keys sharing both their slots, two are placed and the rest
+        // stashed, until one too many makes the map pick another seed
+        let target = slots(0);
+        let keys: Vec<u32> = (0..).filter(|&k| slots(k) == target).take(STASH_LEN + 3).collect();
+
+        let mut map = map;
+        let nslots = map.tables[0].len();
+        for (placed, &key) in keys.iter().enumerate() {
+            map.insert(key, key);
+            check_tables(&map);
+            if placed < STASH_LEN + 2 {
+                assert_eq!(map.seed, 0);
+            }
+        }
+        assert_ne!(map.seed, 0);
+        assert!(map.stash.len() <= STASH_LEN);
+        assert_eq!(map.tables[0].len(), nslots);
+        assert!(keys.iter().all(|key| map[key] == *key));
+    }
+
+    #[test]
+    fn shared_hashes_wait_in_the_stash() {
+        // four hashes give at most eight keys a slot under any seed
+        let mut map: CuckooMap<u32, u32, BuildHasherDefault<TwoBitHasher>> = CuckooMap::default();
+        for key in 0..100 {
+            assert_eq!(map.insert(key, key), None);
+            check_tables(&map);
+        }
+        assert!(map.stash.len() >= 92);
+        assert!((0..100).all(|key| map[&key] == key));
+        assert_eq!(map.get(&100), None);
+        for key in (0..100).step_by(2) {
+            assert_eq!(map.remove(&key), Some(key));
+            check_tables(&map);
+        }
+        assert!((0..100).all(|key| map.get(&key) == (key % 2 == 1).then_some(&key)));
+        map.shrink_to_fit();
+        check_tables(&map);
+        assert_eq!(map.len(), 50);
+    }
+
+    #[test]
+    fn degenerate_hashers_still_insert() {
+        // one hash for every key: one key in each table, the rest stashed
+        let mut map = CuckooMap::with_hasher(Colliding::default());
+        for key in 0..300 {
+            map.insert(key, key);
+        }
+        check_tables(&map);
+        assert_eq!(map.stash.len(), 298);
+        assert!((0..300).all(|key| map[&key] == key));
+
+        // a hasher that only takes a single integer is only given one
+        let mut ints: CuckooMap<u64, u64, crate::BuildNoHashHasher<u64>> = CuckooMap::default();
+        for key in 0..10_000 {
+            ints.insert(key << 20, key);
+        }
+        check_tables(&ints);
+        assert!(ints.stash.len() <= STASH_LEN);
+        assert!((0..10_000).all(|key| ints[&(key << 20)] == key));
+        ints.reserve(100_000);
+        check_tables(&ints);
+    }
+
+    struct Counted(u32);
+
+    impl Hash for Counted {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+        }
+    }
+
+    thread_local! {
+        static COMPARED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    impl PartialEq for Counted {
+        fn eq(&self, other: &Self) -> bool {
+            COMPARED.with(|n| n.set(n.get() + 1));
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for Counted {}
+
+    #[test]
+    fn lookups_check_two_slots() {
+        let mut map = CuckooMap::with_hasher(BuildHasherDefault::<Fnv>::default());
+        for i in 0..10_000 {
+            map.insert(Counted(i), i);
+        }
+        check_tables(&map);
+        for i in 0..20_000 {
+            COMPARED.with(|n| n.set(0));
+            assert_eq!(map.get(&Counted(i)).is_some(), i < 10_000);
+            // the cached hashes filter out the slot of the wrong key
+            assert!(COMPARED.with(Cell::get) <= 1);
+        }
+    }
+
+    #[test]
+    fn removal_and_swap_fix_up() {
+        let mut map = CuckooMap::new();
+        for i in 0..500 {
+            map.insert(i, i * 2);
+        }
+        for i in (0..500).step_by(3) {
+            assert_eq!(map.remove(&i), Some(i * 2));
+            assert_eq!(map.remove(&i), None);
+        }
+        check_tables(&map);
+        assert!((0..500).all(|i| map.get(&i) == (i % 3 != 0).then_some(&(i * 2))));
+        map.retain(|&k, v| {
+            *v += 1;
+            k % 2 == 0
+        });
+        check_tables(&map);
+        assert!(map.iter().all(|(&k, &v)| k % 2 == 0 && v == k * 2 + 1));
+        map.shrink_to_fit();
+        check_tables(&map);
+        map.reserve(1000);
+        assert!(map.capacity() >= map.len() + 1000);
+        check_tables(&map);
+        let len = map.len();
+        assert_eq!(map.drain().count(), len);
+        check_tables(&map);
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut map = CuckooMap::new();
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(map["the"], 3);
+        match map.entry("fox") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.insert(10), 1);
+                assert_eq!(e.remove_entry(), ("fox", 10));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry("fox") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(e) => assert_eq!(e.insert_entry(5).get(), &5),
+        }
+        map.entry("end").and_modify(|v| *v += 1).or_insert(0);
+        assert_eq!(map["end"], 2);
+        assert_eq!(format!("{:?}", CuckooMap::from([(1, 2)])), "{1: 2}");
+        check_tables(&map);
+    }
+}
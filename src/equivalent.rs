@@ -0,0 +1,175 @@
+use core::borrow::Borrow;
+use core::hash::{Hash, Hasher};
+
+/// Key equivalence for lookups, a more flexible [`Borrow`].
+///
+/// The query type `Q` in methods like [`HashMap::get`](crate::HashMap::get)
+/// only has to be `Equivalent` to the key type, so it needn't be something
+/// the key can lend out as a reference. Every `Q` that `K` borrows as is
+/// covered by the blanket impl; implement it for your own query types when
+/// that isn't enough.
+///
+/// An implementation must agree with `Hash`: if `q.equivalent(k)` then `q`
+/// and `k` must hash the same, or lookups will miss.
+pub trait Equivalent<K: ?Sized> {
+    fn equivalent(&self, key: &K) -> bool;
+}
+
+impl<Q, K> Equivalent<K> for Q
+where
+    Q: Eq + ?Sized,
+    K: Borrow<Q> + ?Sized,
+{
+    fn equivalent(&self, key: &K) -> bool {
+        *self == *key.borrow()
+    }
+}
+
+/// Looks up a 2-tuple key by borrowed parts: with `K = (String, String)`,
+/// `map.get(&Pair("host", "path"))` finds the entry without allocating.
+///
+/// The hash matches the owned tuple's, since a tuple hashes its fields in
+/// order and each part hashes the same as the field it stands in for.
+#[derive(Debug)]
+pub struct Pair<'a, A: ?Sized, B: ?Sized>(pub &'a A, pub &'a B);
+
+impl<A: ?Sized, B: ?Sized> Clone for Pair<'_, A, B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A: ?Sized, B: ?Sized> Copy for Pair<'_, A, B> {}
+
+impl<A: Hash + ?Sized, B: Hash + ?Sized> Hash for Pair<'_, A, B> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+        self.1.hash(state);
+    }
+}
+
+impl<A, B, KA, KB> Equivalent<(KA, KB)> for Pair<'_, A, B>
+where
+    A: Equivalent<KA> + ?Sized,
+    B: Equivalent<KB> + ?Sized,
+{
+    fn equivalent(&self, key: &(KA, KB)) -> bool {
+        self.0.equivalent(&key.0) && self.1.equivalent(&key.1)
+    }
+}
+
+/// Like [`Pair`], for 3-tuple keys.
+#[derive(Debug)]
+pub struct Triple<'a, A: ?Sized, B: ?Sized, C: ?Sized>(pub &'a A, pub &'a B, pub &'a C);
+
+impl<A: ?Sized, B: ?Sized, C: ?Sized> Clone for Triple<'_, A, B, C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<A: ?Sized, B: ?Sized, C: ?Sized> Copy for Triple<'_, A, B, C> {}
+
+impl<A: Hash + ?Sized, B: Hash + ?Sized, C: Hash + ?Sized> Hash for Triple<'_, A, B, C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+        self.1.hash(state);
+        self.2.hash(state);
+    }
+}
+
+impl<A, B, C, KA, KB, KC> Equivalent<(KA, KB, KC)> for Triple<'_, A, B, C>
+where
+    A: Equivalent<KA> + ?Sized,
+    B: Equivalent<KB> + ?Sized,
+    C: Equivalent<KC> + ?Sized,
+{
+    fn equivalent(&self, key: &(KA, KB, KC)) -> bool {
+        self.0.equivalent(&key.0) && self.1.equivalent(&key.1) && self.2.equivalent(&key.2)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::hash::BuildHasher;
+
+    use super::*;
+    use crate::HashMap;
+
+    // Looks up `(String, u32)` keys without building a `String`. Hashes
+    // exactly like the tuple: the `str` as a `String` would, then the `u32`.
+    struct Query<'a>(&'a str, u32);
+
+    impl Hash for Query<'_> {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+            self.1.hash(state);
+        }
+    }
+
+    impl Equivalent<(String, u32)> for Query<'_> {
+        fn equivalent(&self, key: &(String, u32)) -> bool {
+            self.0 == key.0 && self.1 == key.1
+        }
+    }
+
+    #[test]
+    fn custom_query_type() {
+        let mut map: HashMap<(String, u32), u32> = HashMap::new();
+        for i in 0..100 {
+            map.insert((format!("k{}", i % 10), i), i * 2);
+        }
+
+        assert_eq!(map.get(&Query("k3", 13)), Some(&26));
+        assert_eq!(map.get(&Query("k3", 14)), None);
+        assert!(map.contains_key(&Query("k9", 99)));
+        assert_eq!(map[&Query("k0", 0)], 0);
+        *map.get_mut(&Query("k1", 1)).unwrap() += 1;
+        assert_eq!(map.get_key_value(&Query("k1", 1)), Some((&("k1".to_string(), 1), &3)));
+        assert_eq!(map.remove(&Query("k5", 55)), Some(110));
+        assert_eq!(map.remove(&Query("k5", 55)), None);
+        assert_eq!(map.len(), 99);
+    }
+
+    #[test]
+    fn borrow_lookups_still_work() {
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), 1);
+        assert_eq!(map.get("foo"), Some(&1));
+        assert_eq!(map.get(&"foo".to_string()), Some(&1));
+        assert!(map.contains_key("foo"));
+        assert_eq!(map.remove("foo"), Some(1));
+        assert!("x".equivalent(&"x".to_string()));
+    }
+
+    #[test]
+    fn pair_lookup() {
+        let mut map: HashMap<(String, String), usize> = HashMap::new();
+        for i in 0..50 {
+            map.insert((format!("host{}", i), format!("/path/{}", i)), i);
+        }
+
+        let owned = ("host7".to_string(), "/path/7".to_string());
+        assert_eq!(map.hasher().hash_one(Pair("host7", "/path/7")), map.hasher().hash_one(&owned));
+        assert_eq!(map.get(&Pair("host7", "/path/7")), Some(&7));
+        assert_eq!(map.get(&Pair("host7", "/path/8")), None);
+        assert_eq!(map.remove(&Pair("host49", "/path/49")), Some(49));
+        // the parts can be any `Equivalent` types, owned or not
+        assert!(map.contains_key(&Pair(&"host1".to_string(), "/path/1")));
+    }
+
+    #[test]
+    fn triple_lookup() {
+        let mut map: HashMap<(String, u16, Vec<u8>), usize> = HashMap::new();
+        for i in 0..50u16 {
+            map.insert((format!("h{}", i), i, vec![i as u8; 3]), i as usize);
+        }
+
+        let owned = ("h3".to_string(), 3u16, vec![3u8; 3]);
+        let query = Triple("h3", &3u16, &[3u8, 3, 3][..]);
+        assert_eq!(map.hasher().hash_one(query), map.hasher().hash_one(&owned));
+        assert_eq!(map.get(&query), Some(&3));
+        assert_eq!(map.get(&Triple("h3", &4u16, &[3u8, 3, 3][..])), None);
+        assert_eq!(map.get_key_value(&query), Some((&owned, &3)));
+    }
+}
@@ -0,0 +1,346 @@
+//! A read-only map packed for lookups.
+//!
+//! [`HashMap::freeze`] sorts the arena by home bucket, so every bucket's
+//! entries sit next to each other, and replaces the bucket array and the
+//! `next` links with one array of offsets into it: bucket `b` holds
+//! `entries[offsets[b]..offsets[b + 1]]`. A lookup reads two offsets and
+//! scans a short run of adjacent entries, comparing cached hashes before
+//! keys. There are no empty slots, tombstones or spare capacity, and
+//! nothing is hashed again while packing.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::ops::Index;
+
+use crate::{bucket_index, make_hash, DefaultState, Equivalent, HashMap, IntoIter, Iter, Keys, Values};
+
+/// An immutable map made by [`HashMap::freeze`].
+///
+/// It has lookups and iteration but nothing that changes it, so it can be
+/// shared freely, e.g. behind an `Arc`; it is `Send` and `Sync` whenever
+/// its keys, values and hasher are.
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::HashMap;
+///
+/// let mut map = HashMap::new();
+/// map.insert("a", 1);
+/// map.insert("b", 2);
+/// let frozen = map.freeze();
+/// assert_eq!(frozen.get("a"), Some(&1));
+/// assert!(!frozen.contains_key("c"));
+/// assert_eq!(frozen.len(), 2);
+/// ```
+pub struct FrozenMap<K, V, S = DefaultState> {
+    entries: Box<[(u64, K, V)]>,
+    // empty for an empty map, else one more than the power-of-two number
+    // of buckets
+    offsets: Box<[usize]>,
+    hash_builder: S,
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// Packs the map into a [`FrozenMap`] for the rest of its life. Hashes
+    /// are cached, so no key is hashed again.
+    pub fn freeze(mut self) -> FrozenMap<K, V, S> {
+        // one to two entries a bucket keeps runs short, and with no more
+        // buckets than entries the offsets take no more room than the
+        // `next` links they replace
+        let nbuckets = match self.entries.len() {
+            0 => 0,
+            len => (len.next_power_of_two() / 2).max(1),
+        };
+        self.entries.sort_unstable_by_key(|&(hash, _, _)| bucket_index(hash, nbuckets));
+        let mut offsets = Vec::new();
+        if nbuckets > 0 {
+            offsets.reserve_exact(nbuckets + 1);
+            let mut at = 0;
+            for bucket in 0..nbuckets {
+                offsets.push(at);
+                while at < self.entries.len() && bucket_index(self.entries[at].0, nbuckets) == bucket {
+                    at += 1;
+                }
+            }
+            offsets.push(at);
+        }
+        FrozenMap {
+            entries: self.entries.into_boxed_slice(),
+            offsets: offsets.into_boxed_slice(),
+            hash_builder: self.hash_builder,
+        }
+    }
+}
+
+impl<K, V, S> FrozenMap<K, V, S> {
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Iterates in bucket order, not in the order entries were inserted.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { inner: self.entries.iter() }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    /// Entries that an entry with this hash would be among.
+    fn run(&self, hash: u64) -> &[(u64, K, V)] {
+        if self.offsets.is_empty() {
+            return &[];
+        }
+        let bucket = bucket_index(hash, self.offsets.len() - 1);
+        &self.entries[self.offsets[bucket]..self.offsets[bucket + 1]]
+    }
+}
+
+impl<K, V, S> FrozenMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn lookup<Q>(&self, key: &Q) -> Option<&(u64, K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let hash = make_hash(&self.hash_builder, key);
+        self.run(hash)
+            .iter()
+            .find(|(ehash, ekey, _)| *ehash == hash && key.equivalent(ekey))
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).map(|(_, _, evalue)| evalue)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).map(|(_, ekey, evalue)| (ekey, evalue))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).is_some()
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone> Clone for FrozenMap<K, V, S> {
+    fn clone(&self) -> Self {
+        FrozenMap {
+            entries: self.entries.clone(),
+            offsets: self.offsets.clone(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for FrozenMap<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, S> PartialEq for FrozenMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K, V, S> Eq for FrozenMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Eq,
+    S: BuildHasher,
+{}
+
+impl<K, V, S> PartialEq<HashMap<K, V, S>> for FrozenMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &HashMap<K, V, S>) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K, V, S, Q> Index<&Q> for FrozenMap<K, V, S>
+where
+    K: Hash + Eq,
+    Q: Hash + Equivalent<K> + ?Sized,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Panics if `key` is not in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("FrozenMap::index: key not found")
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a FrozenMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, S> IntoIterator for FrozenMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.entries.into_vec().into_iter(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::mem::size_of;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_util::Colliding;
+
+    /// Heap bytes behind the map's arrays, spare capacity included.
+    fn footprint<K, V, S>(map: &HashMap<K, V, S>) -> usize {
+        map.entries.capacity() * size_of::<(u64, K, V)>()
+            + (map.table.next.capacity() + map.table.buckets.capacity() + map.table.old_buckets.capacity()) * size_of::<usize>()
+    }
+
+    fn frozen_footprint<K, V, S>(map: &FrozenMap<K, V, S>) -> usize {
+        map.entries.len() * size_of::<(u64, K, V)>() + map.offsets.len() * size_of::<usize>()
+    }
+
+    /// Offsets cover the arena in order and every entry sits in the run of
+    /// its home bucket.
+    fn check_runs<K, V, S>(map: &FrozenMap<K, V, S>) {
+        if map.offsets.is_empty() {
+            assert!(map.is_empty());
+            return;
+        }
+        let nbuckets = map.offsets.len() - 1;
+        assert!(nbuckets.is_power_of_two());
+        assert_eq!(map.offsets[0], 0);
+        assert_eq!(map.offsets[nbuckets], map.len());
+        for bucket in 0..nbuckets {
+            for &(hash, _, _) in &map.entries[map.offsets[bucket]..map.offsets[bucket + 1]] {
+                assert_eq!(bucket_index(hash, nbuckets), bucket);
+            }
+        }
+    }
+
+    #[test]
+    fn every_key_found_and_misses_missed() {
+        for len in [0, 1, 2, 7, 100, 1000, 4097] {
+            let mut map = HashMap::new();
+            for i in 0..len {
+                map.insert(i, i * 3);
+            }
+            let model = map.clone();
+            let frozen = map.freeze();
+            check_runs(&frozen);
+            assert_eq!(frozen.len(), len as usize);
+            assert!(frozen == model);
+            for i in 0..len {
+                assert_eq!(frozen.get(&i), Some(&(i * 3)));
+                assert_eq!(frozen[&i], i * 3);
+            }
+            for i in len..len * 2 + 10 {
+                assert_eq!(frozen.get(&i), None);
+                assert!(!frozen.contains_key(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn colliding_keys_share_one_run() {
+        let mut map = HashMap::with_hasher(Colliding::default());
+        for i in 0..50 {
+            map.insert(i, i);
+        }
+        let frozen = map.freeze();
+        check_runs(&frozen);
+        assert!((0..50).all(|i| frozen[&i] == i));
+        assert_eq!(frozen.get(&50), None);
+    }
+
+    #[test]
+    fn no_bigger_than_the_source() {
+        for len in [1u32, 3, 12, 13, 500, 10_000] {
+            let mut map = HashMap::new();
+            for i in 0..len {
+                map.insert(i.to_string(), i);
+            }
+            let before = footprint(&map);
+            let frozen = map.freeze();
+            assert!(frozen_footprint(&frozen) <= before, "{} entries", len);
+        }
+        // even a map already trimmed to fit
+        let mut map: HashMap<u64, u64> = (0..1000).map(|i| (i, i)).collect();
+        map.shrink_to_fit();
+        let before = footprint(&map);
+        assert!(frozen_footprint(&map.freeze()) <= before);
+    }
+
+    #[test]
+    fn iteration_and_sharing() {
+        let map: HashMap<u32, String> = (0..200).map(|i| (i, i.to_string())).collect();
+        let frozen = Arc::new(map.freeze());
+        let mut keys: Vec<_> = frozen.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (0..200).collect::<Vec<_>>());
+        assert!(frozen.values().all(|v| frozen[&v.parse::<u32>().unwrap()] == *v));
+        assert_eq!((&*frozen).into_iter().count(), 200);
+
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let frozen = Arc::clone(&frozen);
+                std::thread::spawn(move || (t * 50..t * 50 + 50).all(|i| frozen[&i] == i.to_string()))
+            })
+            .collect();
+        assert!(handles.into_iter().all(|h| h.join().unwrap()));
+
+        let owned = Arc::try_unwrap(frozen).unwrap();
+        let mut pairs: Vec<_> = owned.clone().into_iter().collect();
+        pairs.sort_unstable();
+        assert_eq!(pairs.len(), 200);
+        assert_eq!(pairs[7], (7, "7".to_string()));
+        assert_eq!(format!("{:?}", HashMap::from([(1, 2)]).freeze()), "{1: 2}");
+    }
+}
@@ -0,0 +1,150 @@
+use core::fmt;
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::ops::Deref;
+
+/// A key that hashes itself once, up front.
+///
+/// [`Hashed::new`] runs `key` through a [`BuildHasher`] and keeps the result;
+/// after that, hashing a `Hashed` only feeds that single `u64` to the hasher,
+/// however big the key is. Build it with the map's own hasher so equal keys
+/// always end up with equal hashes:
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::{HashMap, Hashed};
+///
+/// let mut map = HashMap::new();
+/// let blob = Hashed::new(vec![7u8; 4096], map.hasher());
+/// map.insert(blob.clone(), "big");
+/// assert_eq!(map.get(&blob), Some(&"big"));
+/// ```
+///
+/// Lookups go through `&Hashed<K>`, not `&K`: a bare key hashes its
+/// contents, not the cached value, so there is deliberately no
+/// `Borrow<K>` impl. Two `Hashed` values are equal when their hashes and
+/// keys are, which lets a mismatch be rejected without comparing keys.
+pub struct Hashed<K> {
+    hash: u64,
+    key: K,
+}
+
+impl<K: Hash> Hashed<K> {
+    pub fn new<S: BuildHasher>(key: K, hash_builder: &S) -> Self {
+        Hashed { hash: hash_builder.hash_one(&key), key }
+    }
+}
+
+impl<K> Hashed<K> {
+    /// The hash computed at construction.
+    pub fn cached_hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_inner(self) -> K {
+        self.key
+    }
+}
+
+impl<K> Hash for Hashed<K> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl<K: PartialEq> PartialEq for Hashed<K> {
+    fn eq(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.key == other.key
+    }
+}
+
+impl<K: Eq> Eq for Hashed<K> {}
+
+impl<K> Deref for Hashed<K> {
+    type Target = K;
+
+    fn deref(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K> AsRef<K> for Hashed<K> {
+    fn as_ref(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K: Clone> Clone for Hashed<K> {
+    fn clone(&self) -> Self {
+        Hashed { hash: self.hash, key: self.key.clone() }
+    }
+}
+
+impl<K: fmt::Debug> fmt::Debug for Hashed<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hashed")
+            .field("hash", &self.hash)
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::HashMap;
+
+    thread_local! {
+        static HASHED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    struct Blob(Vec<u8>);
+
+    impl Hash for Blob {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            HASHED.with(|n| n.set(n.get() + 1));
+            self.0.hash(state);
+        }
+    }
+
+    #[test]
+    fn lookups_do_not_rehash() {
+        let mut map = HashMap::new();
+        let keys: Vec<_> = (0..100u8)
+            .map(|i| Hashed::new(Blob(vec![i; 1024]), map.hasher()))
+            .collect();
+        assert_eq!(HASHED.with(Cell::get), 100);
+
+        for (i, key) in keys.iter().enumerate() {
+            map.insert(key.clone(), i);
+        }
+        for _ in 0..10 {
+            for (i, key) in keys.iter().enumerate() {
+                assert_eq!(map.get(key), Some(&i));
+            }
+        }
+        assert_eq!(map.remove(&keys[3]), Some(3));
+        assert!(!map.contains_key(&keys[3]));
+        assert_eq!(HASHED.with(Cell::get), 100);
+    }
+
+    #[test]
+    fn equality_and_accessors() {
+        let map: HashMap<Hashed<String>, ()> = HashMap::new();
+        let a = Hashed::new("abc".to_string(), map.hasher());
+        let b = Hashed::new("abc".to_string(), map.hasher());
+        let c = Hashed::new("abd".to_string(), map.hasher());
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.cached_hash(), map.hasher().hash_one("abc"));
+        assert_eq!(a.len(), 3);
+        assert_eq!(a.key(), "abc");
+        assert_eq!(c.into_inner(), "abd");
+    }
+}
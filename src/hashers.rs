@@ -0,0 +1,434 @@
+//! Other hashers, and map aliases for them.
+//!
+//! These trade the default hasher's resistance to crafted keys for speed,
+//! so only use them when the keys are trusted.
+
+use core::fmt;
+use core::hash::{BuildHasherDefault, Hasher};
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::{collections::hash_map::DefaultHasher, hash::BuildHasher};
+
+use crate::{Entry, HashMap};
+
+/// Marks the key types [`NoHashHasher`] can be used with: types whose `Hash`
+/// impl makes a single integer write.
+pub trait IsEnabled {}
+
+macro_rules! enable_nohash {
+    ($($ty:ty)*) => {$(
+        impl IsEnabled for $ty {}
+    )*};
+}
+
+enable_nohash!(u8 u16 u32 u64 usize i8 i16 i32 i64 isize char);
+
+/// A [`Hasher`] that uses an integer key as its own hash.
+///
+/// The map mixes every hash before masking it down to a bucket index, so
+/// sequential IDs and strided ones (multiples of 1024, say) both spread
+/// out. Nothing is keyed, though: anyone who can pick the keys can still
+/// make them collide.
+///
+/// The `T: IsEnabled` bound keeps it from being used for keys that hash
+/// through byte slices, like strings; hashing more than one integer trips a
+/// debug assertion.
+pub struct NoHashHasher<T> {
+    hash: u64,
+    #[cfg(debug_assertions)]
+    written: bool,
+    marker: PhantomData<fn(T)>,
+}
+
+impl<T> Default for NoHashHasher<T> {
+    fn default() -> Self {
+        NoHashHasher {
+            hash: 0,
+            #[cfg(debug_assertions)]
+            written: false,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for NoHashHasher<T> {
+    fn clone(&self) -> Self {
+        NoHashHasher {
+            hash: self.hash,
+            #[cfg(debug_assertions)]
+            written: self.written,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for NoHashHasher<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NoHashHasher").field(&self.hash).finish()
+    }
+}
+
+impl<T> NoHashHasher<T> {
+    fn set(&mut self, hash: u64) {
+        #[cfg(debug_assertions)]
+        {
+            debug_assert!(!self.written, "NoHashHasher can only hash a single integer");
+            self.written = true;
+        }
+        self.hash = hash;
+    }
+}
+
+impl<T: IsEnabled> Hasher for NoHashHasher<T> {
+    fn write(&mut self, _bytes: &[u8]) {
+        panic!("NoHashHasher can only hash integer keys");
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.set(n as u64)
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.set(n as u64)
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.set(n as u64)
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.set(n)
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.set(n as u64)
+    }
+
+    fn write_i8(&mut self, n: i8) {
+        self.set(n as u64)
+    }
+
+    fn write_i16(&mut self, n: i16) {
+        self.set(n as u64)
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.set(n as u64)
+    }
+
+    fn write_i64(&mut self, n: i64) {
+        self.set(n as u64)
+    }
+
+    fn write_isize(&mut self, n: isize) {
+        self.set(n as u64)
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// A [`BuildHasher`] keyed by an explicit seed, for tests and snapshots that
+/// need a reproducible layout: two maps with the same seed that see the same
+/// inserts and removals iterate in the same order.
+///
+/// Anyone who knows the seed can craft keys that all land in one bucket, so
+/// never use this for maps filled from untrusted input. The layout is only
+/// stable for a given Rust toolchain, since it builds on `DefaultHasher`.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeterministicState {
+    seed: u64,
+}
+
+#[cfg(feature = "std")]
+impl DeterministicState {
+    pub fn new(seed: u64) -> Self {
+        DeterministicState { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+#[cfg(feature = "std")]
+impl BuildHasher for DeterministicState {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        let mut hasher = DefaultHasher::new();
+        hasher.write_u64(self.seed);
+        hasher
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V> HashMap<K, V, DeterministicState> {
+    /// Creates an empty map whose layout is fixed by `seed`; see
+    /// [`DeterministicState`].
+    pub fn with_seed(seed: u64) -> Self {
+        HashMap::with_hasher(DeterministicState::new(seed))
+    }
+}
+
+/// Builds [`NoHashHasher`]s for keys of type `T`.
+pub type BuildNoHashHasher<T> = BuildHasherDefault<NoHashHasher<T>>;
+
+/// A map from `u64` IDs that skips hashing them; see [`NoHashHasher`].
+pub type IntMap<V> = HashMap<u64, V, BuildNoHashHasher<u64>>;
+
+/// A map hashed with [aHash](ahash). Build one with `AHashMap::default()`,
+/// or with `HashMap::with_hasher(ahash::RandomState::with_seed(..))`.
+#[cfg(feature = "ahash")]
+pub type AHashMap<K, V> = HashMap<K, V, ahash::RandomState>;
+
+/// A map hashed with the Firefox (FxHash) hasher from [`fxhash`]. FxHash is
+/// unkeyed, so the layout is the same on every run.
+#[cfg(feature = "fxhash")]
+pub type FxHashMap<K, V> = HashMap<K, V, fxhash::FxBuildHasher>;
+
+/// Hashes keys with a plain function instead of a [`Hasher`], for key types
+/// that implement `Eq` but not `Hash` (float wrappers, FFI handles), or to
+/// hash only part of a key. Build maps with [`HashMap::with_hash_fn`].
+///
+/// Keys that are equal must get equal hashes from the function. Lookups take
+/// `&K` only, since there is no function for borrowed forms of the key.
+#[derive(Clone, Copy)]
+pub struct HashFn<F>(F);
+
+impl<F> fmt::Debug for HashFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("HashFn")
+    }
+}
+
+impl<K, V, F> HashMap<K, V, HashFn<F>>
+where
+    K: Eq,
+    F: Fn(&K) -> u64,
+{
+    /// Creates an empty map that places keys by `hash_fn`; see [`HashFn`].
+    pub fn with_hash_fn(hash_fn: F) -> Self {
+        HashMap::with_hasher(HashFn(hash_fn))
+    }
+
+    fn hash_of(&self, key: &K) -> u64 {
+        (self.hasher().0)(key)
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, HashFn<F>> {
+        let hash = self.hash_of(&key);
+        self.entry_hashed(hash, key)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash_of(&key);
+        self.insert_hashed(hash, key, value)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.get_hashed(self.hash_of(key), key)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        let hash = self.hash_of(key);
+        self.get_mut_hashed(hash, key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let hash = self.hash_of(key);
+        self.remove_hashed(hash, key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn max_chain<V, S>(map: &HashMap<u64, V, S>) -> usize {
+        map.chains().iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn int_map_sequential() {
+        let mut map: IntMap<u64> = IntMap::default();
+        for i in 0..1_000_000 {
+            map.insert(i, i + 1);
+        }
+        assert_eq!(map.len(), 1_000_000);
+        assert!((0..1_000_000).all(|i| map.get(&i) == Some(&(i + 1))));
+        assert_eq!(map.get(&1_000_000), None);
+        // identity hashes are mixed before picking a bucket, which spreads
+        // consecutive keys about as evenly as random ones
+        assert!(max_chain(&map) < 16);
+
+        assert!((0..1_000_000).step_by(2).all(|i| map.remove(&i) == Some(i + 1)));
+        assert_eq!(map.len(), 500_000);
+    }
+
+    #[test]
+    fn int_map_sparse() {
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let keys: Vec<u64> = (0..1_000_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state
+            })
+            .collect();
+
+        let mut map = IntMap::with_capacity_and_hasher(keys.len(), Default::default());
+        for (i, &key) in keys.iter().enumerate() {
+            *map.entry(key).or_insert(0) += i;
+        }
+        assert_eq!(map.len(), keys.len());
+        assert!(keys.iter().enumerate().all(|(i, key)| map[key] == i));
+        assert!(max_chain(&map) < 16);
+    }
+
+    #[test]
+    fn nohash_small_ints() {
+        let mut map: HashMap<i8, char, BuildNoHashHasher<i8>> = HashMap::default();
+        for i in -128..=127i8 {
+            map.insert(i, 'x');
+        }
+        assert_eq!(map.len(), 256);
+        assert_eq!(map.remove(&-1), Some('x'));
+    }
+
+    #[test]
+    #[should_panic(expected = "NoHashHasher can only hash integer keys")]
+    fn nohash_rejects_bytes() {
+        let mut hasher = NoHashHasher::<u64>::default();
+        hasher.write(b"not an integer");
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "single integer")]
+    fn nohash_rejects_composite_keys() {
+        let mut hasher = NoHashHasher::<u64>::default();
+        hasher.write_u64(1);
+        hasher.write_u64(2);
+    }
+
+    #[cfg(feature = "std")]
+    fn order(map: &HashMap<String, usize, DeterministicState>) -> Vec<Vec<&str>> {
+        map.chains()
+            .into_iter()
+            .map(|chain| chain.into_iter().map(|(_, key, _)| key.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn deterministic_state() {
+        let build = |seed| {
+            let mut map = HashMap::with_seed(seed);
+            for i in 0..200 {
+                map.insert(i.to_string(), i);
+            }
+            for i in (0..200).step_by(3) {
+                map.remove(&i.to_string());
+            }
+            map
+        };
+
+        let a = build(7);
+        let b = build(7);
+        assert_eq!(a.hasher().seed(), 7);
+        assert_eq!(order(&a), order(&b));
+
+        // any single other seed could match by chance, but not all of them
+        assert!((8..16).any(|seed| order(&build(seed)) != order(&a)));
+        assert!((8..16).all(|seed| build(seed) == a));
+    }
+
+    #[cfg(any(feature = "ahash", feature = "fxhash"))]
+    fn exercise<S: std::hash::BuildHasher + Default>(mut map: HashMap<String, usize, S>) {
+        for i in 0..1000 {
+            assert_eq!(map.insert(i.to_string(), i), None);
+        }
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.get("500"), Some(&500));
+        assert_eq!(map.insert("500".to_string(), 0), Some(500));
+        assert_eq!(map.remove("999"), Some(999));
+        assert!(!map.contains_key("999"));
+
+        *map.entry("0".to_string()).or_insert(7) += 1;
+        *map.entry("1000".to_string()).or_insert(7) += 1;
+        assert_eq!(map["0"], 1);
+        assert_eq!(map["1000"], 8);
+        assert_eq!(map.entry_ref("1").or_insert(0), &mut 1);
+
+        map.shrink_to_fit();
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.iter().filter(|(k, v)| k.parse::<usize>().unwrap() == **v).count(), 997);
+    }
+
+    #[test]
+    #[cfg(feature = "ahash")]
+    fn ahash_map() {
+        exercise(AHashMap::default());
+        exercise(HashMap::with_hasher(ahash::RandomState::with_seed(42)));
+    }
+
+    #[test]
+    #[cfg(feature = "fxhash")]
+    fn fxhash_map() {
+        exercise(FxHashMap::default());
+        exercise(HashMap::with_capacity_and_hasher(16, fxhash::FxBuildHasher::default()));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Meters(f64);
+
+    impl Eq for Meters {}
+
+    #[test]
+    fn hash_fn_map() {
+        // `Meters` has no `Hash` impl; hash the bits of the float instead
+        let mut map = HashMap::with_hash_fn(|key: &Meters| key.0.to_bits());
+        for i in 0..1000 {
+            assert_eq!(map.insert(Meters(i as f64 * 0.5), i), None);
+        }
+        assert_eq!(map.len(), 1000);
+        assert!(map.capacity() >= 1000);
+        assert_eq!(map.insert(Meters(2.5), 0), Some(5));
+        assert_eq!(map.get(&Meters(2.5)), Some(&0));
+        assert_eq!(map.get(&Meters(0.25)), None);
+        *map.get_mut(&Meters(1.0)).unwrap() += 100;
+        assert_eq!(map.get(&Meters(1.0)), Some(&102));
+        assert_eq!(map.remove(&Meters(3.0)), Some(6));
+        assert!(!map.contains_key(&Meters(3.0)));
+        *map.entry(Meters(3.0)).or_insert(0) += 1;
+        *map.entry(Meters(3.0)).or_insert(0) += 1;
+        assert_eq!(map.get(&Meters(3.0)), Some(&2));
+        assert!((7..1000).all(|i| map.get(&Meters(i as f64 * 0.5)) == Some(&i)));
+    }
+
+    #[test]
+    fn hash_fn_on_a_subfield() {
+        // only the id takes part in hashing, and equal ids must mean equal keys
+        #[derive(PartialEq, Eq)]
+        struct Handle {
+            id: u32,
+            label: &'static str,
+        }
+
+        let mut map = HashMap::with_hash_fn(|key: &Handle| u64::from(key.id));
+        map.insert(Handle { id: 1, label: "a" }, 1);
+        map.insert(Handle { id: 2, label: "b" }, 2);
+        assert_eq!(map.get(&Handle { id: 1, label: "a" }), Some(&1));
+        assert_eq!(map.get(&Handle { id: 1, label: "z" }), None);
+        let keys: Vec<_> = map.keys().map(|key| key.label).collect();
+        assert_eq!(keys.len(), 2);
+    }
+}
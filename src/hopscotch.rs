@@ -0,0 +1,790 @@
+//! A hopscotch hashing map: every key lives within a fixed neighborhood of
+//! its home slot.
+//!
+//! [`HopscotchMap`] keeps its entries in a dense arena like the crate's
+//! other maps and finds them through a table of slots. Each slot also owns
+//! a bitmap of which of the `NEIGHBORHOOD` slots starting at it hold keys
+//! that call it home, so a lookup reads one bitmap and checks only the
+//! slots it names, all close together in memory.
+//!
+//! An insertion takes the nearest free slot. If that is outside the new
+//! key's neighborhood, entries between the two are hopped forward into it,
+//! each staying within its own neighborhood, until the free slot is close
+//! enough. When no entry can hop, the table grows.
+//!
+//! Growing can't help keys that share a hash, which share a home at every
+//! size, and is unlikely to help much in a table already mostly empty. So
+//! once more than `NEIGHBORHOOD` keys have one hash, or the table is very
+//! sparse, a key with no room goes to an overflow list instead, which
+//! lookups scan after the neighborhood.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::{
+    bucket_index, make_hash, DefaultState, Drain, Equivalent, HashMap, IntoIter, IntoKeys,
+    IntoValues, Iter, IterMut, Keys, Values, ValuesMut, NONE,
+};
+
+/// Slots a key may sit at from its home slot, counting the home slot.
+const NEIGHBORHOOD: usize = 32;
+
+/// Slots in the smallest allocated table.
+const MIN_SLOTS: usize = 8;
+
+/// A table using less than one part in this many of its capacity doesn't
+/// grow for a full neighborhood; the entry overflows instead.
+const SPARSE_FRACTION: usize = 64;
+
+/// Entries `nslots` slots hold before the table grows. Hopscotch copes
+/// with far fuller tables than plain linear probing.
+fn capacity_of(nslots: usize) -> usize {
+    nslots / 8 * 7
+}
+
+/// Smallest power-of-two slot count with room for `capacity` entries, or
+/// `None` if that count does not fit in a `usize`.
+fn slots_for(capacity: usize) -> Option<usize> {
+    if capacity == 0 {
+        return Some(0);
+    }
+    let mut nslots = MIN_SLOTS;
+    while capacity_of(nslots) < capacity {
+        nslots = nslots.checked_mul(2)?;
+    }
+    Some(nslots)
+}
+
+#[cfg(all(test, feature = "std"))]
+std::thread_local! {
+    /// Entries hopped towards a free slot, for tests to see that the
+    /// displacement actually ran.
+    static HOPS: core::cell::Cell<usize> = const { core::cell::Cell::new(0) };
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Slot {
+    /// Arena index of the entry here, or `NONE`.
+    index: usize,
+    /// Bit `d` is set when slot `self + d` holds a key whose home is here.
+    hop: u32,
+}
+
+const EMPTY: Slot = Slot { index: NONE, hop: 0 };
+
+/// A hash map that resolves collisions by hopscotch hashing.
+///
+/// It offers the core of [`HashMap`]'s API, with the
+/// crate's iterators and an entry API of its own in this module:
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::HopscotchMap;
+///
+/// let mut map = HopscotchMap::new();
+/// map.insert("a", 1);
+/// *map.entry("b").or_insert(0) += 2;
+/// assert_eq!(map.get("a"), Some(&1));
+/// assert_eq!(map.remove("b"), Some(2));
+/// assert_eq!(map.len(), 1);
+/// ```
+///
+/// Lookups stay within one neighborhood as long as the hasher spreads the
+/// keys; past 32 keys with one hash, the rest are found by a scan.
+pub struct HopscotchMap<K, V, S = DefaultState> {
+    entries: Vec<(u64, K, V)>,
+    slots: Vec<Slot>,
+    // arena indices of entries with no slot in their neighborhood
+    overflow: Vec<usize>,
+    hash_builder: S,
+}
+
+#[cfg(feature = "std")]
+impl<K, V> HopscotchMap<K, V, DefaultState> {
+    pub fn new() -> Self {
+        HopscotchMap::with_hasher(DefaultState::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        HopscotchMap::with_capacity_and_hasher(capacity, DefaultState::default())
+    }
+}
+
+impl<K, V, S> HopscotchMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HopscotchMap::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let nslots = slots_for(capacity).expect("capacity overflow");
+        HopscotchMap {
+            entries: Vec::with_capacity(capacity),
+            slots: alloc::vec![EMPTY; nslots],
+            overflow: Vec::new(),
+            hash_builder,
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of entries the map can hold before its table grows, unless
+    /// an insertion finds no way to make room in a neighborhood sooner.
+    pub fn capacity(&self) -> usize {
+        capacity_of(self.slots.len())
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.slots.fill(EMPTY);
+        self.overflow.clear();
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let len = self.len();
+        self.entries.retain_mut(|(_, ekey, evalue)| f(ekey, evalue));
+        if self.len() != len {
+            let nslots = self.slots.len();
+            self.resize(nslots);
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { inner: self.entries.iter() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut { inner: self.entries.iter_mut() }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { inner: self.iter_mut() }
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys { inner: self.into_iter() }
+    }
+
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues { inner: self.into_iter() }
+    }
+
+    /// Removes every entry, handing them out by value. The table keeps its
+    /// size, as with [`clear`](HopscotchMap::clear).
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        self.slots.fill(EMPTY);
+        self.overflow.clear();
+        Drain {
+            inner: self.entries.drain(..),
+            marker: PhantomData,
+        }
+    }
+
+    fn mask(&self) -> usize {
+        self.slots.len() - 1
+    }
+
+    /// Neighborhood size for this table: small tables have fewer slots
+    /// than `NEIGHBORHOOD` to offer.
+    fn reach(&self) -> usize {
+        NEIGHBORHOOD.min(self.slots.len())
+    }
+
+    /// Slots holding keys whose home is `home`, nearest first.
+    fn neighbors(&self, home: usize) -> impl Iterator<Item = usize> {
+        let mask = self.mask();
+        let mut hop = self.slots[home].hop;
+        core::iter::from_fn(move || {
+            if hop == 0 {
+                return None;
+            }
+            let distance = hop.trailing_zeros() as usize;
+            hop &= hop - 1;
+            Some((home + distance) & mask)
+        })
+    }
+
+    /// Slot and arena index of the entry with this `hash` whose key
+    /// `is_match` accepts. The slot is `NONE` for an overflowed entry.
+    fn find<F>(&self, hash: u64, mut is_match: F) -> Option<(usize, usize)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        let home = bucket_index(hash, self.slots.len());
+        self.neighbors(home)
+            .map(|slot| (slot, self.slots[slot].index))
+            .chain(self.overflow.iter().map(|&index| (NONE, index)))
+            .find(|&(_, index)| {
+                let (ehash, ekey, _) = &self.entries[index];
+                *ehash == hash && is_match(ekey)
+            })
+    }
+
+    /// Slot that points at `entries[index]`, or `NONE` if it overflowed.
+    fn slot_of(&self, index: usize) -> usize {
+        let home = bucket_index(self.entries[index].0, self.slots.len());
+        self.neighbors(home)
+            .find(|&slot| self.slots[slot].index == index)
+            .unwrap_or(NONE)
+    }
+
+    /// Position of arena `index` in the overflow list.
+    fn overflow_pos(&self, index: usize) -> usize {
+        self.overflow
+            .iter()
+            .position(|&i| i == index)
+            .expect("entry missing from its neighborhood and the overflow list")
+    }
+
+    /// Whether growing the table might find `entries[index]` a slot.
+    fn worth_growing(&self, index: usize) -> bool {
+        let hash = self.entries[index].0;
+        let same = self
+            .entries
+            .iter()
+            .filter(|(ehash, _, _)| *ehash == hash)
+            .take(NEIGHBORHOOD + 1)
+            .count();
+        same <= NEIGHBORHOOD && self.len() * SPARSE_FRACTION >= self.capacity()
+    }
+
+    /// Gives `entries[index]` a slot within its neighborhood, hopping other
+    /// entries forward to make one. Returns false, with nothing changed,
+    /// if that is impossible at this table size.
+    fn place(&mut self, index: usize) -> bool {
+        let nslots = self.slots.len();
+        if nslots == 0 {
+            return false;
+        }
+        let (mask, reach) = (self.mask(), self.reach());
+        let home = bucket_index(self.entries[index].0, nslots);
+        let free = (0..nslots).find(|d| self.slots[(home + d) & mask].index == NONE);
+        let Some(mut distance) = free else {
+            return false;
+        };
+
+        while distance >= reach {
+            let free = (home + distance) & mask;
+            // the furthest-back slot with a neighbor it can hop into `free`
+            let hop = (1..reach).rev().find_map(|back| {
+                let owner = (free + nslots - back) & mask;
+                let hop = self.slots[owner].hop & ((1 << back) - 1);
+                (hop != 0).then(|| (owner, back, hop.trailing_zeros() as usize))
+            });
+            let Some((owner, back, from)) = hop else {
+                return false;
+            };
+            let slot = (owner + from) & mask;
+            self.slots[free].index = mem::replace(&mut self.slots[slot].index, NONE);
+            self.slots[owner].hop ^= (1 << from) | (1 << back);
+            distance -= back - from;
+            #[cfg(all(test, feature = "std"))]
+            HOPS.with(|n| n.set(n.get() + 1));
+        }
+
+        let slot = (home + distance) & mask;
+        self.slots[slot].index = index;
+        self.slots[home].hop |= 1 << distance;
+        true
+    }
+
+    /// Adds an entry to the arena and gives it a slot, growing the table
+    /// until one can be found or overflowing it. Returns its arena index.
+    fn push_entry(&mut self, hash: u64, key: K, value: V) -> usize {
+        let index = self.entries.len();
+        self.entries.push((hash, key, value));
+        if !self.place(index) {
+            match self.worth_growing(index) {
+                true => self.grow(),
+                false => self.overflow.push(index),
+            }
+        }
+        index
+    }
+
+    /// Doubles the table until every entry has a slot, or is left without
+    /// one that growing wouldn't help.
+    fn grow(&mut self) {
+        let mut nslots = self.slots.len();
+        loop {
+            nslots = (nslots * 2).max(MIN_SLOTS);
+            if self.rebuild(nslots) {
+                return;
+            }
+        }
+    }
+
+    /// Lays the arena out over `nslots` fresh slots, overflowing entries
+    /// that find none. Returns false, giving up part way, if one of those
+    /// is worth growing the table for instead.
+    fn rebuild(&mut self, nslots: usize) -> bool {
+        self.slots.clear();
+        self.slots.resize(nslots, EMPTY);
+        self.overflow.clear();
+        for index in 0..self.entries.len() {
+            if !self.place(index) {
+                if self.worth_growing(index) {
+                    return false;
+                }
+                self.overflow.push(index);
+            }
+        }
+        true
+    }
+
+    /// Like `rebuild`, but keeps growing past `nslots` if it must.
+    fn resize(&mut self, nslots: usize) {
+        if !self.rebuild(nslots) {
+            self.grow();
+        }
+    }
+
+    /// Removes the entry at arena `index`, which `slot` points at. The last
+    /// entry is swapped into its place in the arena, and its slot updated.
+    fn remove_at(&mut self, slot: usize, index: usize) -> (u64, K, V) {
+        if slot == NONE {
+            let at = self.overflow_pos(index);
+            self.overflow.swap_remove(at);
+        } else {
+            let home = bucket_index(self.entries[index].0, self.slots.len());
+            let distance = (slot + self.slots.len() - home) & self.mask();
+            self.slots[home].hop &= !(1 << distance);
+            self.slots[slot].index = NONE;
+        }
+        let last = self.entries.len() - 1;
+        if index != last {
+            match self.slot_of(last) {
+                NONE => {
+                    let at = self.overflow_pos(last);
+                    self.overflow[at] = index;
+                }
+                moved => self.slots[moved].index = index,
+            }
+        }
+        self.entries.swap_remove(index)
+    }
+}
+
+impl<K, V, S> HopscotchMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn lookup<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.find(make_hash(&self.hash_builder, key), |ekey| key.equivalent(ekey))
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        if self.len() >= self.capacity() {
+            let nslots = (self.slots.len() * 2).max(MIN_SLOTS);
+            self.resize(nslots);
+        }
+        let hash = make_hash(&self.hash_builder, &key);
+        match self.find(hash, |ekey| ekey == &key) {
+            Some((slot, index)) => Entry::Occupied(OccupiedEntry { map: self, slot, index }),
+            None => Entry::Vacant(VacantEntry { key, hash, map: self }),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => Some(e.insert(value)),
+            Entry::Vacant(e) => {
+                e.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let (_, index) = self.lookup(key)?;
+        let (_, ekey, evalue) = &self.entries[index];
+        Some((ekey, evalue))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let (_, index) = self.lookup(key)?;
+        Some(&mut self.entries[index].2)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let (slot, index) = self.lookup(key)?;
+        let (_, ekey, evalue) = self.remove_at(slot, index);
+        Some((ekey, evalue))
+    }
+
+    /// Makes room for at least `additional` more entries.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len().checked_add(additional).expect("capacity overflow");
+        self.entries.reserve(additional);
+        if needed > self.capacity() {
+            self.resize(slots_for(needed).expect("capacity overflow"));
+        }
+    }
+
+    /// Shrinks the table as far as the current entries allow.
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        self.resize(slots_for(self.len()).expect("capacity overflow"));
+        self.slots.shrink_to_fit();
+    }
+}
+
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    map: &'a mut HopscotchMap<K, V, S>,
+    slot: usize,
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    key: K,
+    hash: u64,
+    map: &'a mut HopscotchMap<K, V, S>,
+}
+
+/// A view into a single entry of a [`HopscotchMap`], from
+/// [`HopscotchMap::entry`].
+pub enum Entry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+impl<'a, K: 'a, V: 'a, S> OccupiedEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.map.entries[self.index].1
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.entries[self.index].2
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entries[self.index].2
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entries[self.index].2
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        let (_, ekey, evalue) = self.map.remove_at(self.slot, self.index);
+        (ekey, evalue)
+    }
+}
+
+impl<'a, K: 'a, V: 'a, S> VacantEntry<'a, K, V, S> {
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S> {
+        let index = self.map.push_entry(self.hash, self.key, value);
+        let slot = self.map.slot_of(index);
+        OccupiedEntry { map: self.map, slot, index }
+    }
+}
+
+entry_impls!();
+
+impl<K: Clone, V: Clone, S: Clone> Clone for HopscotchMap<K, V, S> {
+    fn clone(&self) -> Self {
+        HopscotchMap {
+            entries: self.entries.clone(),
+            slots: self.slots.clone(),
+            overflow: self.overflow.clone(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+map_impls!(HopscotchMap);
+
+impl<K, V, S> IntoIterator for HopscotchMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.entries.into_iter(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::test_util::{differential, sorted, Colliding, XorShift};
+    use crate::{BuildNoHashHasher, DeterministicState};
+
+    // Keys hash in groups of eight to the same value, so neighborhoods
+    // fill up long before the table does.
+    type Clustering = crate::test_util::Clustering<8>;
+
+    fn hops() -> usize {
+        HOPS.with(Cell::get)
+    }
+
+    /// Every entry is pointed at exactly once, by a slot inside its home's
+    /// neighborhood or by the overflow list, and every bitmap bit names a
+    /// slot of that home.
+    fn check_table<K, V, S>(map: &HopscotchMap<K, V, S>) {
+        let nslots = map.slots.len();
+        let mut seen = vec![false; map.len()];
+        for home in 0..nslots {
+            let hop = map.slots[home].hop;
+            assert_eq!(u64::from(hop) >> map.reach(), 0);
+            for slot in map.neighbors(home) {
+                let index = map.slots[slot].index;
+                assert_ne!(index, NONE);
+                assert_eq!(bucket_index(map.entries[index].0, nslots), home);
+                assert!(!mem::replace(&mut seen[index], true));
+            }
+        }
+        for &index in &map.overflow {
+            assert!(!mem::replace(&mut seen[index], true));
+        }
+        assert!(seen.into_iter().all(|pointed_at| pointed_at));
+        let full = map.slots.iter().filter(|slot| slot.index != NONE).count();
+        assert_eq!(full + map.overflow.len(), map.len());
+    }
+
+    #[test]
+    fn differential_against_chaining() {
+        for seed in 0..8 {
+            // few keys, so inserts and removals keep hitting the same ones
+            differential(seed, 64, HopscotchMap::new(), check_table);
+            differential(seed, 4096, HopscotchMap::new(), check_table);
+            differential(seed, 4096, HopscotchMap::with_hasher(Clustering::default()), check_table);
+        }
+    }
+
+    #[test]
+    fn full_table_under_clustering() {
+        // Fill to capacity without ever growing: with keys homed in groups
+        // of eight, most insertions find their neighborhood crowded and
+        // have to hop entries out of the way.
+        let mut map: HopscotchMap<u32, u32, Clustering> =
+            HopscotchMap::with_capacity_and_hasher(7 * 1024, Clustering::default());
+        let nslots = map.slots.len();
+        let mut rng = XorShift(0x5eed);
+        let before = hops();
+        while map.len() < map.capacity() {
+            let key = rng.next() as u32;
+            map.insert(key, key ^ 1);
+        }
+        assert_eq!(map.slots.len(), nslots);
+        assert!(hops() - before > 100, "only {} hops", hops() - before);
+        check_table(&map);
+        for (&key, &value) in map.iter() {
+            assert_eq!(map.get(&key), Some(&value));
+        }
+    }
+
+    #[test]
+    fn stress_at_high_load() {
+        // Fill the table to its growth point, then churn it there: every
+        // removal opens a hole that a later insertion may hop entries into.
+        let mut rng = XorShift(0xdead_beef);
+        let mut map = HopscotchMap::with_capacity_and_hasher(1 << 12, DeterministicState::new(0));
+        let (nslots, capacity) = (map.slots.len(), map.capacity());
+        let mut keys = Vec::new();
+        let before = hops();
+        while map.len() < capacity {
+            let key = rng.next() as u32;
+            if map.insert(key, !key).is_none() {
+                keys.push(key);
+            }
+        }
+        let filled = hops();
+        for step in 0..40_000 {
+            let victim = keys.swap_remove(rng.next() as usize % keys.len());
+            assert_eq!(map.remove(&victim), Some(!victim));
+            loop {
+                let key = rng.next() as u32;
+                if map.insert(key, !key).is_none() {
+                    keys.push(key);
+                    break;
+                }
+            }
+            if step % 1000 == 0 {
+                check_table(&map);
+            }
+        }
+        assert!(filled - before > 100, "only {} hops filling", filled - before);
+        assert!(hops() - filled > 100, "only {} hops churning", hops() - filled);
+        // churn at full load may crowd one neighborhood past rescue, but a
+        // single doubling is plenty
+        assert!(map.slots.len() <= nslots * 2);
+        check_table(&map);
+        assert_eq!(map.len(), capacity);
+        assert!(keys.iter().all(|k| map.get(k) == Some(&!k)));
+    }
+
+    #[test]
+    fn hops_keep_keys_near_home() {
+        let mut map: HopscotchMap<u64, u64, BuildNoHashHasher<u64>> =
+            HopscotchMap::with_capacity_and_hasher(200, Default::default());
+        let nslots = map.slots.len();
+        let hasher = BuildNoHashHasher::<u64>::default();
+        let home_of = |k: u64| bucket_index(hasher.hash_one(k), nslots);
+        // 28 keys homed at slot 1 fill slots 1..29 and 10 homed at slot 20
+        // fill 29..39, so the next key homed at slot 1 finds its nearest
+        // free slot 38 away, too far. The first slot-20 key hops from 29
+        // into it, freeing a slot within reach.
+        let mut ones = (0..).filter(|&k| home_of(k) == 1);
+        let near: Vec<u64> = ones.by_ref().take(28).collect();
+        let far: Vec<u64> = (0..).filter(|&k| home_of(k) == 20).take(10).collect();
+        for &k in near.iter().chain(&far) {
+            map.insert(k, k);
+        }
+        let late = ones.next().unwrap();
+        let before = hops();
+        map.insert(late, 0);
+        assert_eq!(map.slots.len(), nslots);
+        assert_eq!(hops() - before, 1);
+        assert_eq!(map.slots[29].index, map.len() - 1);
+        assert_eq!(map.slots[39].index, 28);
+        check_table(&map);
+        assert_eq!(map.get(&late), Some(&0));
+        assert!(near.iter().chain(&far).all(|k| map.get(k) == Some(k)));
+    }
+
+    #[test]
+    fn grows_when_no_entry_can_hop() {
+        let mut map: HopscotchMap<u64, u64, BuildNoHashHasher<u64>> =
+            HopscotchMap::with_capacity_and_hasher(200, Default::default());
+        let nslots = map.slots.len();
+        let keys: Vec<u64> = (0..)
+            .filter(|&k| bucket_index(map.hash_builder.hash_one(k), nslots) == 5)
+            .take(NEIGHBORHOOD + 1)
+            .collect();
+        for &k in &keys {
+            map.insert(k, k);
+        }
+        assert!(map.slots.len() > nslots);
+        check_table(&map);
+        assert!(keys.iter().all(|k| map.get(k) == Some(k)));
+    }
+
+    #[test]
+    fn equal_hashes_overflow() {
+        let mut map = HopscotchMap::with_hasher(Colliding::default());
+        for i in 0..200 {
+            assert_eq!(map.insert(i, i), None);
+            check_table(&map);
+        }
+        // growing can't separate them, so the table only grew with the load
+        assert_eq!(map.slots.len(), slots_for(200).unwrap());
+        assert_eq!(map.overflow.len(), 200 - NEIGHBORHOOD);
+        assert!((0..200).all(|i| map[&i] == i));
+        assert_eq!(map.get(&200), None);
+        for i in (0..200).step_by(3) {
+            assert_eq!(map.remove(&i), Some(i));
+            assert_eq!(map.get(&i), None);
+        }
+        check_table(&map);
+        assert!((0..200).filter(|i| i % 3 != 0).all(|i| map[&i] == i));
+        map.shrink_to_fit();
+        check_table(&map);
+
+        for seed in 0..2 {
+            differential(seed, 100, HopscotchMap::with_hasher(Colliding::default()), check_table);
+        }
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut map = HopscotchMap::new();
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(map["the"], 3);
+        match map.entry("fox") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.insert(10), 1);
+                assert_eq!(e.remove_entry(), ("fox", 10));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry("fox") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(e) => assert_eq!(e.insert_entry(5).get(), &5),
+        }
+        map.entry("end").and_modify(|v| *v += 1).or_insert(0);
+        assert_eq!(map["end"], 2);
+        assert_eq!(*map.entry("zero").or_insert_default(), 0);
+        check_table(&map);
+    }
+
+    #[test]
+    fn parity_with_linear_map() {
+        let linear: crate::LinearMap<u32, u32> = (0..1000).map(|i| (i, i * 3)).collect();
+        let mut map: HopscotchMap<u32, u32> = linear.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(sorted(map.iter()), sorted(linear.iter()));
+        map.retain(|&k, _| k % 2 == 0);
+        map.reserve(5000);
+        check_table(&map);
+        map.shrink_to_fit();
+        check_table(&map);
+        assert_eq!(map.len(), 500);
+        assert_eq!(map.clone().into_keys().count(), 500);
+        assert_eq!(map.drain().map(|(_, v)| v).sum::<u32>(), (0..1000).step_by(2).map(|k| k * 3).sum::<u32>());
+        assert!(map.is_empty());
+        assert_eq!(format!("{:?}", HopscotchMap::from([(1, 2)])), "{1: 2}");
+    }
+}
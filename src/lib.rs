@@ -1,339 +1,5012 @@
-use std::borrow::Borrow;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::{self, Vec};
+use core::error::Error;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use core::ops::Index;
+use core::slice;
+
+use bloom::MissFilter;
+#[cfg(feature = "std")]
 use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-use std::mem;
+
+mod macros;
+// before the map modules, which use its macros
+#[macro_use]
+mod map_impls;
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impl;
+mod bloom;
+pub mod cuckoo;
+mod equivalent;
+pub mod frozen;
+mod hashed;
+mod hashers;
+pub mod hopscotch;
+pub mod linear;
+#[cfg(feature = "std")]
+mod persist;
+#[cfg(feature = "rayon")]
+mod rayon_impl;
+pub mod robin_hood;
+pub mod small;
+pub mod soa;
+pub mod static_map;
+#[cfg(feature = "swiss")]
+pub mod swiss;
+#[cfg(feature = "rkyv")]
+mod rkyv_impl;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(all(test, feature = "std"))]
+mod test_util;
+#[cfg(feature = "arbitrary")]
+pub use arbitrary_impl::fuzz;
+#[cfg(feature = "ahash")]
+pub use hashers::AHashMap;
+pub use cuckoo::CuckooMap;
+pub use equivalent::{Equivalent, Pair, Triple};
+pub use frozen::FrozenMap;
+pub use hashed::Hashed;
+#[cfg(feature = "fxhash")]
+pub use hashers::FxHashMap;
+pub use hashers::{BuildNoHashHasher, HashFn, IntMap, IsEnabled, NoHashHasher};
+pub use hopscotch::HopscotchMap;
+pub use linear::{LinearMap, LinearTable};
+pub use robin_hood::RobinHoodMap;
+pub use small::SmallMap;
+pub use soa::SoaHashMap;
+pub use static_map::{StaticKey, StaticMap};
+#[cfg(feature = "swiss")]
+pub use swiss::{SwissMap, SwissTable};
+#[cfg(feature = "std")]
+pub use hashers::DeterministicState;
+#[cfg(feature = "std")]
+pub use persist::{FromBytes, ReadError, ToBytes};
+#[cfg(feature = "rayon")]
+pub use rayon_impl::{IntoParIter, ParIter, ParIterMut};
+#[cfg(feature = "serde")]
+pub use serde_impl::{serde_seq, DeserializeWithPolicy, DuplicatePolicy};
 
 const INITIAL_NBUCKETS: usize = 1;
 
-pub struct HashMap<K, V> {
-    buckets: Vec<Vec<(K, V)>>,
-    items: usize,
-}
+/// Bucket count from which growing no longer relinks every entry at once:
+/// the old buckets are kept and moved over a few at a time instead.
+const INCREMENTAL_REHASH_MIN: usize = 1 << 10;
+
+/// Old buckets moved per insert or removal while a rehash is in flight.
+/// Any value of at least two finishes well before the new buckets fill.
+const REHASH_STEP: usize = 64;
+
+/// Fraction of the buckets a map may fill before it grows, unless
+/// configured otherwise with [`HashMap::set_load_factor`].
+pub const DEFAULT_LOAD_FACTOR: f32 = 0.75;
+
+/// Number of items `nbuckets` buckets hold at `load_factor` before the next
+/// insertion grows them.
+fn capacity_of(nbuckets: usize, load_factor: f32) -> usize {
+    match nbuckets {
+        0 => 0,
+        n => (n as f64 * f64::from(load_factor)) as usize + 1,
+    }
+}
+
+/// Smallest power-of-two bucket count whose capacity at `load_factor` is at
+/// least `capacity`, or `None` if that count does not fit in a `usize`.
+fn buckets_for(capacity: usize, load_factor: f32) -> Option<usize> {
+    if capacity == 0 {
+        return Some(0);
+    }
+    let min = (capacity - 1) as f64 / f64::from(load_factor);
+    if min >= usize::MAX as f64 {
+        return None;
+    }
+    let mut nbuckets = (min as usize).max(INITIAL_NBUCKETS).checked_next_power_of_two()?;
+    // truncating `min` can leave the count one doubling short
+    while capacity_of(nbuckets, load_factor) < capacity {
+        nbuckets = nbuckets.checked_mul(2)?;
+    }
+    Some(nbuckets)
+}
+
+fn check_load_factor(load_factor: f32) {
+    assert!(
+        load_factor > 0.1 && load_factor <= 0.95,
+        "load factor must be in (0.1, 0.95], got {}",
+        load_factor,
+    );
+}
+
+/// Bucket for `hash` among `nbuckets`, which must be a power of two.
+///
+/// Masking only looks at the low bits, so the high ones are mixed in first;
+/// otherwise hashes differing only above the mask (like a `NoHashHasher`'s
+/// multiples of 1024) would all share a bucket.
+const fn bucket_index(hash: u64, nbuckets: usize) -> usize {
+    debug_assert!(nbuckets.is_power_of_two());
+    let hash = hash.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    ((hash ^ (hash >> 32)) & (nbuckets as u64 - 1)) as usize
+}
+
+/// The buckets an entry with this hash may live in: the usual one, and
+/// with `two_choice` a second one picked from the rotated hash. Both are
+/// the same bucket otherwise.
+fn candidate_buckets(hash: u64, nbuckets: usize, two_choice: bool) -> [usize; 2] {
+    let first = bucket_index(hash, nbuckets);
+    if !two_choice {
+        return [first, first];
+    }
+    [first, bucket_index(hash.rotate_left(32) ^ 0x5851_f42d_4c95_7f2d, nbuckets)]
+}
+
+/// A [`BuildHasher`] keyed with fresh random keys each time one is created,
+/// so bucket placement can't be predicted (or attacked with keys crafted to
+/// collide) from outside. Every map gets its own set of keys: two maps with
+/// the same entries generally lay them out, and iterate them, differently.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct RandomState {
+    inner: std::collections::hash_map::RandomState,
+}
+
+#[cfg(feature = "std")]
+impl RandomState {
+    pub fn new() -> Self {
+        RandomState::default()
+    }
+}
+
+#[cfg(feature = "std")]
+impl BuildHasher for RandomState {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        self.inner.build_hasher()
+    }
+}
+
+/// The hasher used by maps that don't pick one.
+#[cfg(feature = "std")]
+pub type DefaultState = RandomState;
+
+/// Without `std` there is no source of random keys, so there is no default
+/// hasher either: this stand-in can't be constructed and doesn't implement
+/// `BuildHasher`. Pick a hasher with [`HashMap::with_hasher`] instead.
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Debug)]
+pub enum DefaultState {}
+
+fn make_hash<Q: Hash + ?Sized, S: BuildHasher>(hash_builder: &S, key: &Q) -> u64 {
+    hash_builder.hash_one(key)
+}
+
+/// Heap bytes held by a map, from [`HashMap::memory_usage`].
+///
+/// Only the map's own arrays are counted. Heap memory that keys and values
+/// own themselves, like a `String`'s contents, is not.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct MapMemoryUsage {
+    /// The bucket arrays, the chain links and the miss filter, if any, as
+    /// far as they are in use.
+    pub table_bytes: usize,
+    /// The arena's live entries, each a cached hash, key and value.
+    pub entries_bytes: usize,
+    /// Capacity allocated beyond what all of the above use.
+    pub wasted_capacity_bytes: usize,
+}
+
+impl MapMemoryUsage {
+    pub fn total(&self) -> usize {
+        self.table_bytes + self.entries_bytes + self.wasted_capacity_bytes
+    }
+}
+
+/// The error returned by [`HashMap::try_reserve`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum TryReserveError {
+    /// The requested capacity exceeds what the bucket array can address.
+    CapacityOverflow,
+    /// The allocator refused to hand out the bucket array.
+    AllocError,
+}
+
+impl fmt::Display for TryReserveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryReserveError::CapacityOverflow => {
+                f.write_str("capacity overflow while reserving buckets")
+            }
+            TryReserveError::AllocError => {
+                f.write_str("memory allocation failed while reserving buckets")
+            }
+        }
+    }
+}
+
+impl Error for TryReserveError {}
+
+/// Marks the end of a chain in `HashMap::buckets` and `HashMap::next`.
+const NONE: usize = usize::MAX;
+
+/// Walks a chain of entry indices through `HashMap::next`.
+struct Chain<'a> {
+    next: &'a [usize],
+    at: usize,
+}
+
+impl Iterator for Chain<'_> {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        if self.at == NONE {
+            return None;
+        }
+        let index = self.at;
+        self.at = self.next[index];
+        Some(index)
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// How a [`HashMap`] finds its entries, picked by its last type parameter.
+///
+/// Every table indexes the same dense arena of entries, so iteration, the
+/// entry API and the std traits work the same over any of them; they
+/// differ in the lookup structure and in the knobs it has.
+///
+/// - [`ChainedTable`], the default, links each bucket's entries into a
+///   chain and carries the load factor, incremental rehashing, two-choice
+///   insertion and the miss filter.
+/// - [`LinearTable`] probes a flat array of slots instead.
+/// - `SwissTable`, with the `swiss` feature, probes control bytes a group
+///   of slots at a time.
+///
+/// Maps on another table are built with [`HashMap::with_table`] or
+/// [`HashMap::with_table_and_hasher`], or through `Default` and `collect`
+/// under a name like [`LinearMap`] that fixes the table:
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::{HashMap, LinearMap, LinearTable};
+///
+/// let mut map = HashMap::with_table(LinearTable::new());
+/// map.insert("a", 1);
+/// assert_eq!(map["a"], 1);
+///
+/// let squares: LinearMap<u32, u32> = (0..10).map(|i| (i, i * i)).collect();
+/// let chained: HashMap<u32, u32> = squares.clone().into_iter().collect();
+/// assert!(squares == chained);
+/// ```
+///
+/// The trait is sealed: its methods are the map's internals, hidden from
+/// the docs.
+pub trait Table: sealed::Sealed + Sized {
+    #[doc(hidden)]
+    fn capacity(&self) -> usize;
+
+    /// Index of the first entry with this `hash` whose key `is_match`
+    /// accepts.
+    #[doc(hidden)]
+    fn find<K, V, S>(map: &HashMap<K, V, S, Self>, hash: u64, is_match: impl FnMut(&K) -> bool) -> Option<usize>;
+
+    /// Makes room for one more entry, ahead of a lookup that may end in
+    /// `push`.
+    #[doc(hidden)]
+    fn reserve_one<K, V, S>(map: &mut HashMap<K, V, S, Self>);
+
+    /// Appends an entry whose key is known to be absent, after
+    /// `reserve_one`, and returns its index in the arena.
+    #[doc(hidden)]
+    fn push<K, V, S>(map: &mut HashMap<K, V, S, Self>, hash: u64, key: K, value: V) -> usize;
+
+    /// Removes the entry at `index`; the last one is swapped into its
+    /// place.
+    #[doc(hidden)]
+    fn remove<K, V, S>(map: &mut HashMap<K, V, S, Self>, index: usize) -> (u64, K, V);
+
+    /// Empties the table while the arena still holds every entry, just
+    /// before the arena is cleared.
+    #[doc(hidden)]
+    fn unlink_all<K, V, S>(map: &mut HashMap<K, V, S, Self>);
+
+    /// Rebuilds the table after `retain` dropped entries from the arena.
+    #[doc(hidden)]
+    fn retained<K, V, S>(map: &mut HashMap<K, V, S, Self>);
+
+    #[doc(hidden)]
+    fn reserve<K, V, S>(map: &mut HashMap<K, V, S, Self>, additional: usize);
+
+    #[doc(hidden)]
+    fn try_reserve<K, V, S>(map: &mut HashMap<K, V, S, Self>, additional: usize) -> Result<(), TryReserveError>;
+
+    #[doc(hidden)]
+    fn shrink_to_fit<K, V, S>(map: &mut HashMap<K, V, S, Self>);
+
+    /// Gives back memory once the map is much emptier than its table, if
+    /// the table does that on its own.
+    #[doc(hidden)]
+    fn maybe_shrink<K, V, S>(map: &mut HashMap<K, V, S, Self>);
+}
+
+/// The default [`Table`]: separate chaining through index links.
+///
+/// A bucket holds the index of the first entry in its chain, and each
+/// entry links to the next one in the same bucket. This is the table
+/// [`HashMap`]'s load factor, rehashing, two-choice and miss filter
+/// settings apply to.
+#[derive(Debug)]
+pub struct ChainedTable {
+    // `next[i]` is the index of the entry after `entries[i]`. With
+    // `two_choice`, each entry is in whichever of its two candidate buckets
+    // was shorter when it was linked.
+    //
+    // While a large map grows, its previous buckets stay in `old_buckets`
+    // and those from `migrated` on still hold their chains; everything else
+    // is linked from `buckets`. `old_buckets` is empty the rest of the time.
+    //
+    // `miss_filter`, when on, holds the hash of every entry in the arena,
+    // and maybe some removed since it was last rebuilt.
+    next: Vec<usize>,
+    buckets: Vec<usize>,
+    old_buckets: Vec<usize>,
+    migrated: usize,
+    load_factor: f32,
+    auto_shrink: bool,
+    two_choice: bool,
+    miss_filter: Option<MissFilter>,
+}
+
+impl ChainedTable {
+    /// An empty table with the default settings.
+    pub fn new() -> Self {
+        ChainedTable {
+            next: Vec::new(),
+            buckets: Vec::new(),
+            old_buckets: Vec::new(),
+            migrated: 0,
+            load_factor: DEFAULT_LOAD_FACTOR,
+            auto_shrink: true,
+            two_choice: false,
+            miss_filter: None,
+        }
+    }
+}
+
+impl Default for ChainedTable {
+    fn default() -> Self {
+        ChainedTable::new()
+    }
+}
+
+impl Clone for ChainedTable {
+    fn clone(&self) -> Self {
+        // the chains are plain indices, so nothing has to be rehashed
+        ChainedTable {
+            next: self.next.clone(),
+            buckets: self.buckets.clone(),
+            old_buckets: self.old_buckets.clone(),
+            migrated: self.migrated,
+            load_factor: self.load_factor,
+            auto_shrink: self.auto_shrink,
+            two_choice: self.two_choice,
+            miss_filter: self.miss_filter.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.next.clone_from(&source.next);
+        self.buckets.clone_from(&source.buckets);
+        self.old_buckets.clone_from(&source.old_buckets);
+        self.migrated = source.migrated;
+        self.load_factor = source.load_factor;
+        self.auto_shrink = source.auto_shrink;
+        self.two_choice = source.two_choice;
+        self.miss_filter.clone_from(&source.miss_filter);
+    }
+}
+
+impl sealed::Sealed for ChainedTable {}
+
+impl Table for ChainedTable {
+    fn capacity(&self) -> usize {
+        capacity_of(self.buckets.len(), self.load_factor)
+    }
+
+    fn find<K, V, S>(map: &HashMap<K, V, S, Self>, hash: u64, is_match: impl FnMut(&K) -> bool) -> Option<usize> {
+        map.find(hash, is_match)
+    }
+
+    fn reserve_one<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        if map.len() >= map.capacity() {
+            map.resize();
+        }
+    }
+
+    fn push<K, V, S>(map: &mut HashMap<K, V, S, Self>, hash: u64, key: K, value: V) -> usize {
+        map.push_entry(hash, key, value)
+    }
+
+    fn remove<K, V, S>(map: &mut HashMap<K, V, S, Self>, index: usize) -> (u64, K, V) {
+        let removed = map.remove_index(index);
+        map.maybe_shrink();
+        removed
+    }
+
+    fn unlink_all<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.unlink_all();
+    }
+
+    fn retained<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.table.next.truncate(map.entries.len());
+        map.relink();
+        map.maybe_shrink();
+    }
+
+    fn reserve<K, V, S>(map: &mut HashMap<K, V, S, Self>, additional: usize) {
+        let target_size = map.len()
+            .checked_add(additional)
+            .and_then(|capacity| buckets_for(capacity, map.table.load_factor))
+            .expect("capacity overflow");
+        map.entries.reserve(additional);
+        map.table.next.reserve(additional);
+        if target_size > map.table.buckets.len() {
+            map.rehash(target_size);
+        }
+    }
+
+    fn try_reserve<K, V, S>(map: &mut HashMap<K, V, S, Self>, additional: usize) -> Result<(), TryReserveError> {
+        let target_size = map.len()
+            .checked_add(additional)
+            .and_then(|capacity| buckets_for(capacity, map.table.load_factor))
+            .ok_or(TryReserveError::CapacityOverflow)?;
+        map.entries
+            .try_reserve(additional)
+            .and_then(|()| map.table.next.try_reserve(additional))
+            .map_err(|_| TryReserveError::AllocError)?;
+        if target_size > map.table.buckets.len() {
+            map.try_reserve_rehash(target_size)?;
+            map.rehash(target_size);
+        }
+        Ok(())
+    }
+
+    fn shrink_to_fit<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.shrink_to(0);
+    }
+
+    fn maybe_shrink<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.maybe_shrink();
+    }
+}
+
+pub struct HashMap<K, V, S = DefaultState, T = ChainedTable> {
+    // All entries live in one arena, each carrying its key's hash so probes
+    // compare hashes before keys and resizing never hashes a key again.
+    // `table` finds them by hash, by its own scheme; it never holds keys or
+    // values itself.
+    entries: Vec<(u64, K, V)>,
+    hash_builder: S,
+    table: T,
+}
+
+#[cfg(feature = "std")]
+impl<K, V> HashMap<K, V, DefaultState> {
+    pub fn new() -> Self {
+        HashMap::with_hasher(DefaultState::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        HashMap::with_capacity_and_hasher(capacity, DefaultState::default())
+    }
+
+    /// Creates a map that holds `capacity` items without resizing while
+    /// keeping at most `load_factor` of its buckets in use.
+    pub fn with_capacity_and_load_factor(capacity: usize, load_factor: f32) -> Self {
+        HashMap::with_capacity_load_factor_and_hasher(capacity, load_factor, DefaultState::default())
+    }
+
+    /// Creates an empty map with a miss filter of `bits_per_key` bits a
+    /// key; see [`set_miss_filter`](Self::set_miss_filter).
+    pub fn with_miss_filter(bits_per_key: usize) -> Self {
+        let mut map = HashMap::new();
+        map.set_miss_filter(Some(bits_per_key));
+        map
+    }
+
+    /// Moves the entries of a `std::collections::HashMap` into a new map.
+    pub fn from_std<H>(map: std::collections::HashMap<K, V, H>) -> Self
+    where
+        K: Hash + Eq,
+    {
+        let mut new = HashMap::with_capacity(map.len());
+        new.extend(map);
+        new
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, T: Table> HashMap<K, V, DefaultState, T> {
+    /// Creates an empty map that finds its entries through `table`, which
+    /// must be empty; see [`Table`].
+    pub fn with_table(table: T) -> Self {
+        HashMap::with_table_and_hasher(table, DefaultState::default())
+    }
+}
+
+impl<K, V, S, T: Table> HashMap<K, V, S, T> {
+    /// Creates an empty map on `table`, as [`with_table`](Self::with_table)
+    /// does, that hashes its keys with `hash_builder`.
+    pub fn with_table_and_hasher(table: T, hash_builder: S) -> Self {
+        HashMap {
+            entries: Vec::new(),
+            hash_builder,
+            table,
+        }
+    }
+
+    /// The hasher builder the map hashes its keys with.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of items the map can hold before the next insertion grows
+    /// its table.
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// Removes every entry but keeps the table's allocation. A sparse
+    /// chained map resets only the buckets its entries occupied, so
+    /// clearing a big pre-sized map costs about as much as its length.
+    pub fn clear(&mut self) {
+        T::unlink_all(self);
+        self.entries.clear();
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool
+    {
+        self.entries.retain_mut(|(_, ekey, evalue)| f(ekey, evalue));
+        T::retained(self);
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys {
+            inner: self.iter(),
+        }
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys {
+            inner: self.into_iter(),
+        }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values {
+            inner: self.iter(),
+        }
+    }
+
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues {
+            inner: self.into_iter(),
+        }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            inner: self.iter_mut(),
+        }
+    }
+
+    /// Iterates over the entries with mutable access to the values. Keys
+    /// stay behind shared references so their hashes can't change:
+    ///
+    #[cfg_attr(feature = "std", doc = "```compile_fail")]
+    #[cfg_attr(not(feature = "std"), doc = "```ignore")]
+    /// let mut map = hashmap::HashMap::new();
+    /// map.insert(String::from("foo"), 1);
+    /// for (key, _) in map.iter_mut() {
+    ///     key.push('!');
+    /// }
+    /// ```
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            inner: self.entries.iter_mut(),
+        }
+    }
+
+    /// Removes every entry, handing them out as owned pairs. The table is
+    /// kept, and the map is empty once the `Drain` is dropped even if it was
+    /// not fully consumed.
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        T::unlink_all(self);
+        Drain {
+            inner: self.entries.drain(..),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S> {
+    /// Creates an empty map that hashes its keys with `hash_builder`.
+    pub fn with_hasher(hash_builder: S) -> Self {
+        HashMap::with_table_and_hasher(ChainedTable::new(), hash_builder)
+    }
+
+    /// Creates a map that can hold `capacity` items without resizing and
+    /// hashes its keys with `hash_builder`.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        HashMap::with_capacity_load_factor_and_hasher(capacity, DEFAULT_LOAD_FACTOR, hash_builder)
+    }
+
+    /// Like `with_capacity_and_hasher`, but grows once `load_factor` of the
+    /// buckets are in use instead of [`DEFAULT_LOAD_FACTOR`]; see
+    /// [`set_load_factor`](Self::set_load_factor).
+    pub fn with_capacity_load_factor_and_hasher(
+        capacity: usize,
+        load_factor: f32,
+        hash_builder: S,
+    ) -> Self {
+        check_load_factor(load_factor);
+        let nbuckets = buckets_for(capacity, load_factor).expect("capacity overflow");
+        HashMap {
+            entries: Vec::with_capacity(capacity),
+            hash_builder,
+            table: ChainedTable {
+                next: Vec::with_capacity(capacity),
+                buckets: alloc::vec![NONE; nbuckets],
+                load_factor,
+                ..ChainedTable::new()
+            },
+        }
+    }
+
+    pub fn load_factor(&self) -> f32 {
+        self.table.load_factor
+    }
+
+    /// Sets the fraction of buckets the map fills before growing, which
+    /// must be in `(0.1, 0.95]`. Lower values trade memory for shorter
+    /// chains. If the items no longer fit, the map grows right away.
+    pub fn set_load_factor(&mut self, load_factor: f32) {
+        check_load_factor(load_factor);
+        self.table.load_factor = load_factor;
+        let target_size = buckets_for(self.len(), load_factor).expect("capacity overflow");
+        if target_size > self.table.buckets.len() {
+            self.rehash(target_size);
+        }
+    }
+
+    /// Number of buckets currently allocated, zero until the first insert.
+    pub fn bucket_count(&self) -> usize {
+        self.table.buckets.len()
+    }
+
+    /// Heap bytes the map's arrays take up, split by what they hold.
+    pub fn memory_usage(&self) -> MapMemoryUsage {
+        let index = mem::size_of::<usize>();
+        let (filter_bytes, filter_spare) = self.table.miss_filter.as_ref().map_or((0, 0), MissFilter::heap_bytes);
+        let links = self.table.buckets.len() + self.table.old_buckets.len() + self.table.next.len();
+        let spare_links = self.table.buckets.capacity() + self.table.old_buckets.capacity() + self.table.next.capacity() - links;
+        let entry = mem::size_of::<(u64, K, V)>();
+        MapMemoryUsage {
+            table_bytes: links * index + filter_bytes,
+            entries_bytes: self.entries.len() * entry,
+            wasted_capacity_bytes: spare_links * index
+                + (self.entries.capacity() - self.entries.len()) * entry
+                + filter_spare,
+        }
+    }
+
+    /// Buckets that entries with this hash may live in, the same one twice
+    /// unless two-choice insertion is on. The map must have allocated its
+    /// buckets.
+    fn hash_buckets(&self, hash: u64) -> [usize; 2] {
+        candidate_buckets(hash, self.table.buckets.len(), self.table.two_choice)
+    }
+
+    /// Indices of the entries chained from `bucket`.
+    fn chain(&self, bucket: usize) -> Chain<'_> {
+        Chain {
+            next: &self.table.next,
+            at: self.table.buckets[bucket],
+        }
+    }
+
+    /// Entries of every bucket, each in chain order.
+    #[cfg(test)]
+    pub(crate) fn chains(&self) -> Vec<Vec<&(u64, K, V)>> {
+        assert!(!self.is_rehashing(), "finish the rehash before looking at chains");
+        (0..self.table.buckets.len())
+            .map(|bucket| self.chain(bucket).map(|index| &self.entries[index]).collect())
+            .collect()
+    }
+
+    /// Index of the first entry with this `hash` whose key `is_match`
+    /// accepts.
+    fn find<F>(&self, hash: u64, mut is_match: F) -> Option<usize>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        if self.table.buckets.is_empty() {
+            return None;
+        }
+        if let Some(filter) = &self.table.miss_filter {
+            if !filter.may_contain(hash) {
+                return None;
+            }
+        }
+
+        self.homes(hash)
+            .into_iter()
+            .flatten()
+            .flat_map(|(old, bucket)| Chain {
+                next: &self.table.next,
+                at: if old { self.table.old_buckets[bucket] } else { self.table.buckets[bucket] },
+            })
+            .find(|&index| {
+                let (ehash, ekey, _) = &self.entries[index];
+                *ehash == hash && is_match(ekey)
+            })
+    }
+
+    /// Every distinct bucket an entry with this hash may be linked from, as
+    /// `(old, bucket)` where `old` picks `old_buckets` over `buckets`.
+    fn homes(&self, hash: u64) -> [Option<(bool, usize)>; 4] {
+        let [first, second] = self.hash_buckets(hash);
+        let mut homes = [Some((false, first)), (second != first).then_some((false, second)), None, None];
+        if self.is_rehashing() {
+            let [first, second] = candidate_buckets(hash, self.table.old_buckets.len(), self.table.two_choice);
+            homes[2] = (first >= self.table.migrated).then_some((true, first));
+            homes[3] = (second != first && second >= self.table.migrated).then_some((true, second));
+        }
+        homes
+    }
+
+    /// Appends an entry to the arena and links it into its bucket, which
+    /// must already exist. Returns the new entry's index.
+    fn push_entry(&mut self, hash: u64, key: K, value: V) -> usize {
+        let [first, second] = self.hash_buckets(hash);
+        let bucket = if first != second && self.chain(second).count() < self.chain(first).count() {
+            second
+        } else {
+            first
+        };
+        let index = self.entries.len();
+        self.entries.push((hash, key, value));
+        self.table.next.push(self.table.buckets[bucket]);
+        if let Some(filter) = &mut self.table.miss_filter {
+            filter.insert(hash);
+        }
+        self.table.buckets[bucket] = index;
+        self.rehash_step();
+        index
+    }
+
+    /// The link (a bucket head or another entry's `next`) that points at
+    /// `entries[index]`.
+    fn link_to(&mut self, index: usize) -> &mut usize {
+        let mut homes = self.homes(self.entries[index].0).into_iter().flatten().peekable();
+        let (old, bucket) = loop {
+            let home = homes.next().expect("entry not linked from any of its buckets");
+            if homes.peek().is_none() {
+                break home;
+            }
+            let (old, bucket) = home;
+            let head = if old { self.table.old_buckets[bucket] } else { self.table.buckets[bucket] };
+            if (Chain { next: &self.table.next, at: head }).any(|at| at == index) {
+                break home;
+            }
+        };
+        let heads = if old { &mut self.table.old_buckets } else { &mut self.table.buckets };
+        if heads[bucket] == index {
+            return &mut heads[bucket];
+        }
+        let mut at = heads[bucket];
+        while self.table.next[at] != index {
+            at = self.table.next[at];
+        }
+        &mut self.table.next[at]
+    }
+
+    /// Unlinks and removes `entries[index]`. The last entry is swapped into
+    /// its slot, and whatever linked to it is pointed at the new index.
+    fn remove_index(&mut self, index: usize) -> (u64, K, V) {
+        let after = self.table.next[index];
+        *self.link_to(index) = after;
+        let last = self.entries.len() - 1;
+        if index != last {
+            *self.link_to(last) = index;
+        }
+        self.table.next.swap_remove(index);
+        let removed = self.entries.swap_remove(index);
+        if let Some(filter) = &mut self.table.miss_filter {
+            if filter.note_removal(self.entries.len()) {
+                self.rebuild_miss_filter();
+            }
+        }
+        self.rehash_step();
+        removed
+    }
+
+    /// Rebuilds every chain for the current bucket count from the hashes
+    /// stored in the arena.
+    fn relink(&mut self) {
+        // chain lengths so far, only needed to pick between two candidates;
+        // a leftover old bucket array is recycled to count them in
+        let mut lens = mem::take(&mut self.table.old_buckets);
+        self.table.migrated = 0;
+        lens.clear();
+        if self.table.two_choice {
+            lens.resize(self.table.buckets.len(), 0);
+        }
+        self.table.buckets.fill(NONE);
+        for (index, &(hash, _, _)) in self.entries.iter().enumerate() {
+            let [first, second] = candidate_buckets(hash, self.table.buckets.len(), self.table.two_choice);
+            let bucket = if first != second && lens[second] < lens[first] {
+                second
+            } else {
+                first
+            };
+            if let Some(len) = lens.get_mut(bucket) {
+                *len += 1;
+            }
+            self.table.next[index] = self.table.buckets[bucket];
+            self.table.buckets[bucket] = index;
+        }
+        self.rebuild_miss_filter();
+    }
+
+    /// Starts a lookup that can be driven by a precomputed hash instead of
+    /// a `Borrow`able key.
+    pub fn raw_entry(&self) -> RawEntryBuilder<'_, K, V, S> {
+        RawEntryBuilder { map: self }
+    }
+
+    /// Mutable counterpart of [`raw_entry`](HashMap::raw_entry), whose
+    /// lookups end in an occupied or vacant [`RawEntryMut`].
+    pub fn raw_entry_mut(&mut self) -> RawEntryBuilderMut<'_, K, V, S> {
+        RawEntryBuilderMut { map: self }
+    }
+
+    /// Whether removals may shrink the bucket array; on by default.
+    ///
+    /// Once fewer than an eighth of `capacity()` items are left after a
+    /// `remove` or `retain`, the buckets are reallocated so the map is
+    /// between a quarter and half full. Growing back takes several times as
+    /// many inserts as the shrink took removals, so churn near either
+    /// threshold doesn't keep rehashing. `clear` and `drain` empty the map
+    /// in one go and keep the buckets for refilling.
+    pub fn auto_shrink(&self) -> bool {
+        self.table.auto_shrink
+    }
+
+    /// Turns automatic shrinking on or off. Turn it off for latency-sensitive
+    /// maps that shouldn't rehash during a removal; `shrink_to_fit` still
+    /// frees memory on demand.
+    pub fn set_auto_shrink(&mut self, enabled: bool) {
+        self.table.auto_shrink = enabled;
+    }
+
+    /// Whether inserts pick the shorter of two candidate buckets; off by
+    /// default.
+    ///
+    /// With two choices the longest chain stays much closer to the average,
+    /// even when many keys pile into the same usual bucket. In exchange,
+    /// lookups walk both candidate chains before concluding a key is
+    /// missing, and inserts count both chains' lengths.
+    pub fn two_choice(&self) -> bool {
+        self.table.two_choice
+    }
+
+    /// Turns two-choice insertion on or off, relinking every entry into
+    /// the buckets the new mode allows. Resizes keep the mode.
+    pub fn set_two_choice(&mut self, enabled: bool) {
+        if self.table.two_choice != enabled {
+            self.table.two_choice = enabled;
+            self.relink();
+        }
+    }
+
+    /// Bits per key of the miss filter, or `None` if it is off, as it is
+    /// by default.
+    ///
+    /// The filter is a Bloom filter over the keys' hashes. A lookup for a
+    /// missing key usually finds one of its bits clear and returns without
+    /// walking a chain, at the cost of a few bits of memory per key and of
+    /// setting them on every insert. Ten bits a key lets about one miss in
+    /// a hundred through to the buckets.
+    pub fn miss_filter(&self) -> Option<usize> {
+        self.table.miss_filter.as_ref().map(MissFilter::bits_per_key)
+    }
+
+    /// Turns the miss filter on with `bits_per_key` bits a key, building it
+    /// from the current entries, or off with `None`. The filter is sized
+    /// along with the buckets, and rebuilt whenever they are or once
+    /// removals leave it holding more dead keys than live ones.
+    ///
+    /// Panics if `bits_per_key` is zero.
+    pub fn set_miss_filter(&mut self, bits_per_key: Option<usize>) {
+        self.table.miss_filter = bits_per_key.map(MissFilter::new);
+        self.rebuild_miss_filter();
+    }
+
+    fn rebuild_miss_filter(&mut self) {
+        if let Some(filter) = &mut self.table.miss_filter {
+            let capacity = capacity_of(self.table.buckets.len(), self.table.load_factor);
+            filter.rebuild(capacity, self.entries.iter().map(|&(hash, _, _)| hash));
+        }
+    }
+
+    fn maybe_shrink(&mut self) {
+        if self.table.auto_shrink && self.len() < self.capacity() / 8 {
+            let target_size = buckets_for(self.len() * 2, self.table.load_factor).expect("capacity overflow");
+            self.rehash(target_size);
+        }
+    }
+
+    fn resize(&mut self) {
+        let target_size = match self.table.buckets.len() {
+            0 => INITIAL_NBUCKETS,
+            n => 2*n
+        };
+        // size the arena for everything the new table will hold, so the
+        // pushes until the next resize never reallocate it
+        let room = capacity_of(target_size, self.table.load_factor).saturating_sub(self.entries.len());
+        self.entries.reserve_exact(room);
+        self.table.next.reserve_exact(room);
+        if target_size / 2 < INCREMENTAL_REHASH_MIN {
+            return self.rehash(target_size);
+        }
+        self.finish_rehash();
+        self.table.old_buckets = mem::replace(&mut self.table.buckets, alloc::vec![NONE; target_size]);
+        self.table.migrated = 0;
+        self.rebuild_miss_filter();
+    }
+
+    /// Whether the map is partway through moving its entries to a bigger
+    /// bucket array.
+    ///
+    /// Maps of 1024 buckets or more don't rehash in
+    /// one go when they grow: each later insert or removal moves a few
+    /// more of the old buckets over, so no single call pays for the whole
+    /// map. Lookups check both arrays meanwhile.
+    pub fn is_rehashing(&self) -> bool {
+        !self.table.old_buckets.is_empty()
+    }
+
+    /// Completes a rehash in progress right away, e.g. before a
+    /// latency-sensitive stretch of work.
+    pub fn finish_rehash(&mut self) {
+        while self.is_rehashing() {
+            self.rehash_step();
+        }
+    }
+
+    /// Moves the next `REHASH_STEP` old buckets' chains to the new array.
+    fn rehash_step(&mut self) {
+        if !self.is_rehashing() {
+            return;
+        }
+        let end = (self.table.migrated + REHASH_STEP).min(self.table.old_buckets.len());
+        for bucket in self.table.migrated..end {
+            let mut at = mem::replace(&mut self.table.old_buckets[bucket], NONE);
+            while at != NONE {
+                let after = self.table.next[at];
+                let [first, second] = self.hash_buckets(self.entries[at].0);
+                let target = if first != second && self.chain(second).count() < self.chain(first).count() {
+                    second
+                } else {
+                    first
+                };
+                self.table.next[at] = self.table.buckets[target];
+                self.table.buckets[target] = at;
+                at = after;
+            }
+        }
+        self.table.migrated = end;
+        if end == self.table.old_buckets.len() {
+            self.end_rehash();
+        }
+    }
+
+    /// Drops the old bucket array. Only correct once nothing is linked
+    /// from it, or when every chain is about to be rebuilt.
+    fn end_rehash(&mut self) {
+        self.table.old_buckets = Vec::new();
+        self.table.migrated = 0;
+    }
+
+    fn rehash(&mut self, target_size: usize) {
+        // only the links move; the entries stay where they are. Every slot
+        // is overwritten by relink, so the bucket array is resized where it
+        // stands, letting the allocator grow or trim it without a copy
+        // into a fresh one
+        let nbuckets = self.table.buckets.len();
+        if target_size > nbuckets {
+            self.table.buckets.reserve_exact(target_size - nbuckets);
+        } else {
+            self.table.buckets.truncate(target_size);
+            self.table.buckets.shrink_to(target_size);
+        }
+        self.table.buckets.resize(target_size, NONE);
+        self.relink();
+    }
+
+    /// Allocates everything a rehash to `target_size` buckets needs, so
+    /// that `rehash` itself can't fail halfway: the bigger bucket array,
+    /// with two-choice insertion the chain lengths `relink` counts, and
+    /// the miss filter's bits for the new capacity.
+    fn try_reserve_rehash(&mut self, target_size: usize) -> Result<(), TryReserveError> {
+        let additional = target_size.saturating_sub(self.table.buckets.len());
+        self.table.buckets.try_reserve_exact(additional).map_err(|_| TryReserveError::AllocError)?;
+        if self.table.two_choice {
+            // `relink` counts in whatever `old_buckets` holds
+            let additional = target_size.saturating_sub(self.table.old_buckets.len());
+            self.table.old_buckets.try_reserve_exact(additional).map_err(|_| TryReserveError::AllocError)?;
+        }
+        if let Some(filter) = &mut self.table.miss_filter {
+            filter.try_reserve(capacity_of(target_size, self.table.load_factor))?;
+        }
+        Ok(())
+    }
+
+    /// Shrinks the bucket array, but never below what is needed to hold
+    /// `min_capacity` items (or the current length, if that is larger).
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let min_capacity = self.len().max(min_capacity);
+        let target_size = buckets_for(min_capacity, self.table.load_factor).expect("capacity overflow");
+        self.entries.shrink_to(min_capacity);
+        self.table.next.shrink_to(min_capacity);
+        if target_size < self.table.buckets.len() {
+            self.rehash(target_size);
+        }
+    }
+
+    /// Empties every chain while the arena still holds the entries: when
+    /// they are few next to the buckets, only their own candidate buckets
+    /// can be non-empty, so those are reset instead of the whole array.
+    fn unlink_all(&mut self) {
+        self.end_rehash();
+        if self.entries.len() * 2 < self.table.buckets.len() {
+            for &(hash, _, _) in &self.entries {
+                for bucket in candidate_buckets(hash, self.table.buckets.len(), self.table.two_choice) {
+                    self.table.buckets[bucket] = NONE;
+                }
+            }
+        } else {
+            self.table.buckets.fill(NONE);
+        }
+        self.table.next.clear();
+        if let Some(filter) = &mut self.table.miss_filter {
+            filter.rebuild(capacity_of(self.table.buckets.len(), self.table.load_factor), core::iter::empty());
+        }
+    }
+
+    /// Lazily removes and yields the entries for which `pred` returns true.
+    /// Like std's `extract_if`, only visited entries are affected: dropping
+    /// the iterator early leaves the remaining entries, matching or not, in
+    /// the map.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, K, V, F, S>
+    where
+        F: FnMut(&K, &mut V) -> bool
+    {
+        ExtractIf {
+            map: self,
+            at: 0,
+            pred,
+        }
+    }
+}
+
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState, T: 'a = ChainedTable> {
+    // the key passed to `entry`, kept for `replace_key`/`replace_entry`
+    key: Option<K>,
+    map: &'a mut HashMap<K, V, S, T>,
+    index: usize,
+}
+pub struct VacantEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState, T: 'a = ChainedTable> {
+    key: K,
+    hash: u64,
+    map: &'a mut HashMap<K, V, S, T>,
+}
+
+pub enum Entry<'a, K: 'a, V: 'a, S: 'a = DefaultState, T: 'a = ChainedTable> {
+    Occupied(OccupiedEntry<'a, K, V, S, T>),
+    Vacant(VacantEntry<'a, K, V, S, T>),
+}
+
+impl<'a, K: fmt::Debug, V: fmt::Debug, S, T: Table> fmt::Debug for Entry<'a, K, V, S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::Occupied(e) => f.debug_tuple("Entry").field(e).finish(),
+            Entry::Vacant(e) => f.debug_tuple("Entry").field(e).finish(),
+        }
+    }
+}
+
+impl<'a, K: fmt::Debug, V: fmt::Debug, S, T: Table> fmt::Debug for OccupiedEntry<'a, K, V, S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.get())
+            .finish()
+    }
+}
+
+impl<'a, K: fmt::Debug, V, S, T: Table> fmt::Debug for VacantEntry<'a, K, V, S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("VacantEntry").field(self.key()).finish()
+    }
+}
+
+/// The error returned by [`HashMap::try_insert`] when the key is already
+/// present. Holds the occupied entry along with the value that was rejected.
+pub struct OccupiedError<'a, K: 'a, V: 'a, S: 'a = DefaultState, T: 'a = ChainedTable> {
+    pub entry: OccupiedEntry<'a, K, V, S, T>,
+    pub value: V,
+}
+
+impl<'a, K: fmt::Debug, V: fmt::Debug, S, T: Table> fmt::Debug for OccupiedError<'a, K, V, S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedError")
+            .field("key", self.entry.key())
+            .field("old_value", self.entry.get())
+            .field("new_value", &self.value)
+            .finish()
+    }
+}
+
+impl<'a, K: fmt::Debug, V: fmt::Debug, S, T: Table> fmt::Display for OccupiedError<'a, K, V, S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to insert {:?}, key {:?} already exists with value {:?}",
+            self.value, self.entry.key(), self.entry.get(),
+        )
+    }
+}
+
+impl<'a, K: fmt::Debug, V: fmt::Debug, S, T: Table> Error for OccupiedError<'a, K, V, S, T> {}
+
+impl<'a, K: 'a, V: 'a, S, T: Table> OccupiedEntry<'a, K, V, S, T> {
+    pub fn key(&self) -> &K {
+        &self.map.entries[self.index].1
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.entries[self.index].2
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entries[self.index].2
+    }
+
+    /// Replaces the value of the entry, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    /// Converts the entry into a reference to its value that lives as long
+    /// as the borrow of the map.
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entries[self.index].2
+    }
+
+    /// Swaps the stored key for the one passed to `entry`, returning the
+    /// old key. Panics if the entry came from `insert_entry` or `entry_ref`,
+    /// which have no second owned key to swap in.
+    pub fn replace_key(self) -> K {
+        let key = self.key.expect("replace_key called on an entry without a pending key");
+        mem::replace(&mut self.map.entries[self.index].1, key)
+    }
+
+    /// Swaps both the stored key (for the one passed to `entry`) and the
+    /// value, returning the old pair. Panics like `replace_key`.
+    pub fn replace_entry(self, value: V) -> (K, V) {
+        let key = self.key.expect("replace_entry called on an entry without a pending key");
+        let (_, ekey, evalue) = &mut self.map.entries[self.index];
+        (mem::replace(ekey, key), mem::replace(evalue, value))
+    }
+
+    /// Removes the entry from the map and returns its value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Removes the entry from the map and returns the stored key and value.
+    pub fn remove_entry(self) -> (K, V) {
+        let (_, key, value) = T::remove(self.map, self.index);
+        (key, value)
+    }
+}
+
+impl<'a, K: 'a, V: 'a, S, T: Table> VacantEntry<'a, K, V, S, T> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Gives the key back without touching the map.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V 
+    {
+        self.insert_entry(value).into_mut()
+    }
+
+    /// Like `insert`, but keeps hold of the new entry instead of just its
+    /// value.
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S, T> {
+        let index = T::push(self.map, self.hash, self.key, value);
+        OccupiedEntry {
+            key: None,
+            map: self.map,
+            index,
+        }
+    }
+}
+
+impl<'a, K: 'a, V: 'a, S, T: Table> Entry<'a, K, V, S, T> {
+    pub fn or_insert(self, value: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                e.insert(value)
+            }
+        }
+    }
+
+    pub fn or_insert_with<F>(self, maker: F) -> &'a mut V 
+    where
+        F: FnOnce() -> V
+    {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                e.insert(maker())
+            }
+        }
+    }
+
+    /// The key of this entry: the stored key if occupied, or the key that
+    /// was passed to `entry` if vacant.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// Sets the value of the entry, overwriting it if occupied, and returns
+    /// the now occupied entry.
+    pub fn insert(self, value: V) -> OccupiedEntry<'a, K, V, S, T> {
+        match self {
+            Entry::Occupied(mut e) => {
+                *e.get_mut() = value;
+                e
+            },
+            Entry::Vacant(e) => e.insert_entry(value),
+        }
+    }
+
+    /// Applies `f` to the value if the entry is occupied, then hands the
+    /// entry back for chaining with one of the `or_insert` methods.
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut V)
+    {
+        match self {
+            Entry::Occupied(mut e) => {
+                f(e.get_mut());
+                Entry::Occupied(e)
+            },
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+
+    /// Like `or_insert_with`, but for initializers that can fail. On `Err`
+    /// nothing is inserted and the error is passed through.
+    pub fn or_try_insert_with<F, E>(self, maker: F) -> Result<&'a mut V, E>
+    where
+        F: FnOnce() -> Result<V, E>
+    {
+        match self {
+            Entry::Occupied(e) => Ok(e.into_mut()),
+            Entry::Vacant(e) => Ok(e.insert(maker()?)),
+        }
+    }
+
+    /// Like `or_insert_with`, but the closure gets to see the key it is
+    /// producing a value for.
+    pub fn or_insert_with_key<F>(self, maker: F) -> &'a mut V
+    where
+        F: FnOnce(&K) -> V
+    {
+        match self {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => {
+                let value = maker(&e.key);
+                e.insert(value)
+            }
+        }
+    }
+
+    pub fn or_insert_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(Default::default)
+        // match self {
+        //     Entry::Occupied(e) => e.entry.1,
+        //     Entry::Vacant(e) => e.insert(V::default())
+        // }
+    }
+
+}
+
+/// Like [`Entry`], but created from a borrowed key by
+/// [`HashMap::entry_ref`]. An owned key is only built, through `K: From<&Q>`,
+/// when a vacant entry is actually filled.
+pub enum EntryRef<'a, 'b, K: 'a, Q: ?Sized + 'b, V: 'a, S: 'a = DefaultState, T: 'a = ChainedTable> {
+    Occupied(OccupiedEntry<'a, K, V, S, T>),
+    Vacant(VacantEntryRef<'a, 'b, K, Q, V, S, T>),
+}
+
+pub struct VacantEntryRef<'a, 'b, K: 'a, Q: ?Sized + 'b, V: 'a, S: 'a = DefaultState, T: 'a = ChainedTable> {
+    key: &'b Q,
+    hash: u64,
+    map: &'a mut HashMap<K, V, S, T>,
+}
+
+impl<'a, 'b, K: 'a, Q: ?Sized + 'b, V: 'a, S, T: Table> VacantEntryRef<'a, 'b, K, Q, V, S, T> {
+    pub fn key(&self) -> &'b Q {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V
+    where
+        K: From<&'b Q>,
+    {
+        VacantEntry {
+            key: K::from(self.key),
+            hash: self.hash,
+            map: self.map,
+        }
+        .insert(value)
+    }
+}
+
+impl<'a, 'b, K: 'a, Q: ?Sized + 'b, V: 'a, S, T: Table> EntryRef<'a, 'b, K, Q, V, S, T> {
+    pub fn or_insert(self, value: V) -> &'a mut V
+    where
+        K: From<&'b Q>,
+    {
+        match self {
+            EntryRef::Occupied(e) => e.into_mut(),
+            EntryRef::Vacant(e) => e.insert(value),
+        }
+    }
+
+    pub fn or_insert_with<F>(self, maker: F) -> &'a mut V
+    where
+        K: From<&'b Q>,
+        F: FnOnce() -> V
+    {
+        match self {
+            EntryRef::Occupied(e) => e.into_mut(),
+            EntryRef::Vacant(e) => e.insert(maker()),
+        }
+    }
+
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut V)
+    {
+        match self {
+            EntryRef::Occupied(mut e) => {
+                f(e.get_mut());
+                EntryRef::Occupied(e)
+            },
+            EntryRef::Vacant(e) => EntryRef::Vacant(e),
+        }
+    }
+}
+
+/// Builder for lookups through [`HashMap::raw_entry`].
+pub struct RawEntryBuilder<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    map: &'a HashMap<K, V, S>,
+}
+
+impl<'a, K: 'a, V: 'a, S> RawEntryBuilder<'a, K, V, S> {
+    pub fn from_key<Q>(self, key: &Q) -> Option<(&'a K, &'a V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        S: BuildHasher,
+    {
+        let hash = make_hash(&self.map.hash_builder, key);
+        self.from_key_hashed_nocheck(hash, key)
+    }
+
+    /// Looks `key` up in the bucket for `hash`, without checking that
+    /// `hash` is really the hash of `key`. A wrong hash can only make the
+    /// lookup miss.
+    pub fn from_key_hashed_nocheck<Q>(self, hash: u64, key: &Q) -> Option<(&'a K, &'a V)>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        self.from_hash(hash, |ekey| key.equivalent(ekey))
+    }
+
+    /// Returns the first entry in the bucket for `hash` that `is_match`
+    /// accepts.
+    pub fn from_hash<F>(self, hash: u64, mut is_match: F) -> Option<(&'a K, &'a V)>
+    where
+        F: FnMut(&K) -> bool
+    {
+        let (_, ekey, evalue) = &self.map.entries[self.map.find(hash, &mut is_match)?];
+        Some((ekey, evalue))
+    }
+}
+
+/// Builder for lookups through [`HashMap::raw_entry_mut`].
+pub struct RawEntryBuilderMut<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    map: &'a mut HashMap<K, V, S>,
+}
+
+pub enum RawEntryMut<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    Occupied(RawOccupiedEntryMut<'a, K, V, S>),
+    Vacant(RawVacantEntryMut<'a, K, V, S>),
+}
+
+pub struct RawOccupiedEntryMut<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    inner: OccupiedEntry<'a, K, V, S>,
+}
+
+pub struct RawVacantEntryMut<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    map: &'a mut HashMap<K, V, S>,
+}
+
+impl<'a, K: 'a, V: 'a, S> RawEntryBuilderMut<'a, K, V, S> {
+    pub fn from_key<Q>(self, key: &Q) -> RawEntryMut<'a, K, V, S>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+        S: BuildHasher,
+    {
+        let hash = make_hash(&self.map.hash_builder, key);
+        self.from_key_hashed_nocheck(hash, key)
+    }
+
+    /// Looks `key` up in the bucket for `hash`, without checking that
+    /// `hash` is really the hash of `key`.
+    pub fn from_key_hashed_nocheck<Q>(self, hash: u64, key: &Q) -> RawEntryMut<'a, K, V, S>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        self.from_hash(hash, |ekey| key.equivalent(ekey))
+    }
+
+    pub fn from_hash<F>(self, hash: u64, mut is_match: F) -> RawEntryMut<'a, K, V, S>
+    where
+        F: FnMut(&K) -> bool
+    {
+        match self.map.find(hash, &mut is_match) {
+            Some(index) => RawEntryMut::Occupied(RawOccupiedEntryMut {
+                inner: OccupiedEntry {
+                    key: None,
+                    map: self.map,
+                    index,
+                },
+            }),
+            None => RawEntryMut::Vacant(RawVacantEntryMut { map: self.map }),
+        }
+    }
+}
+
+impl<'a, K: 'a, V: 'a, S> RawOccupiedEntryMut<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        self.inner.key()
+    }
+
+    pub fn get(&self) -> &V {
+        self.inner.get()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.inner.get_mut()
+    }
+
+    /// Mutable access to both halves of the entry. Changing the key in a
+    /// way that alters its hash or equality makes the entry unreachable.
+    pub fn get_key_value_mut(&mut self) -> (&mut K, &mut V) {
+        let (_, ekey, evalue) = &mut self.inner.map.entries[self.inner.index];
+        (ekey, evalue)
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.inner.into_mut()
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        self.inner.insert(value)
+    }
+
+    pub fn remove(self) -> V {
+        self.inner.remove()
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.inner.remove_entry()
+    }
+}
+
+impl<'a, K: 'a, V: 'a, S> RawVacantEntryMut<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn insert(self, key: K, value: V) -> (&'a mut K, &'a mut V) {
+        let hash = make_hash(&self.map.hash_builder, &key);
+        self.insert_hashed_nocheck(hash, key, value)
+    }
+
+    /// Inserts into the bucket for `hash`, which the caller promises is the
+    /// hash of `key`. A wrong hash only makes the entry hard to find again.
+    pub fn insert_hashed_nocheck(self, hash: u64, key: K, value: V) -> (&'a mut K, &'a mut V) {
+        let map = self.map;
+        if map.len() >= map.capacity() {
+            map.resize();
+        }
+
+        let index = map.push_entry(hash, key, value);
+        let (_, ekey, evalue) = &mut map.entries[index];
+        (ekey, evalue)
+    }
+}
+
+impl<K, V, S, T: Table> HashMap<K, V, S, T>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Index of the entry for `key`, if there is one.
+    fn lookup<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        T::find(self, make_hash(&self.hash_builder, key), |ekey| key.equivalent(ekey))
+    }
+
+    /// Hashes `key` exactly as `insert` and `get` do, with the map's hasher.
+    pub fn hash_one<Q: Hash + ?Sized>(&self, key: &Q) -> u64 {
+        make_hash(&self.hash_builder, key)
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S, T> {
+        let hash = make_hash(&self.hash_builder, &key);
+        self.entry_hashed(hash, key)
+    }
+
+    pub fn entry_ref<'a, 'b, Q>(&'a mut self, key: &'b Q) -> EntryRef<'a, 'b, K, Q, V, S, T>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        T::reserve_one(self);
+
+        let hash = make_hash(&self.hash_builder, key);
+        match T::find(self, hash, |ekey| key.equivalent(ekey)) {
+            Some(index) => EntryRef::Occupied(OccupiedEntry {
+                key: None,
+                map: self,
+                index,
+            }),
+            None => EntryRef::Vacant(VacantEntryRef {
+                key,
+                hash,
+                map: self,
+            }),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = make_hash(&self.hash_builder, &key);
+        self.insert_hashed(hash, key, value)
+    }
+
+    /// Inserts `value` only if `key` is not present yet. Unlike `insert`, an
+    /// existing value is never overwritten; the occupied entry and the
+    /// rejected value are handed back instead.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<&mut V, OccupiedError<'_, K, V, S, T>> {
+        match self.entry(key) {
+            Entry::Occupied(entry) => Err(OccupiedError { entry, value }),
+            Entry::Vacant(entry) => Ok(entry.insert(value)),
+        }
+    }
+
+    /// Inserts without checking whether `key` is already present, saving
+    /// the scan of its bucket. This is safe, but the caller must guarantee
+    /// the key is new: a duplicate leaves two entries for the same key, and
+    /// which one lookups find is unspecified.
+    pub fn insert_unique_unchecked(&mut self, key: K, value: V) -> (&K, &mut V) {
+        T::reserve_one(self);
+
+        let hash = make_hash(&self.hash_builder, &key);
+        let index = T::push(self, hash, key, value);
+        let (_, ekey, evalue) = &mut self.entries[index];
+        (ekey, evalue)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V> 
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let (_, ekey, evalue) = &self.entries[self.lookup(key)?];
+        Some((ekey, evalue))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value_mut(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn get_key_value_mut<Q>(&mut self, key: &Q) -> Option<(&K, &mut V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        let (_, ekey, evalue) = &mut self.entries[index];
+        Some((&*ekey, evalue))
+    }
+
+    /// Looks up every key in `keys`, answering in the same order (duplicates
+    /// included) exactly as one `get` per key would.
+    pub fn get_each<'m, Q>(&'m self, keys: &[&Q]) -> Vec<Option<&'m V>>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        keys.iter().map(|key| self.get(*key)).collect()
+    }
+
+    /// Returns mutable references to the values of all `keys` at once, or
+    /// `None` if any key is missing or the same key is asked for twice.
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let mut indices = [0; N];
+        for (index, key) in indices.iter_mut().zip(keys) {
+            *index = self.lookup(key)?;
+        }
+        for (i, index) in indices.iter().enumerate() {
+            if indices[..i].contains(index) {
+                return None;
+            }
+        }
+
+        let entries = self.entries.as_mut_ptr();
+        // SAFETY: every index is in bounds of `self.entries`, which stays
+        // mutably borrowed for the returned lifetime, and no two indices are
+        // equal, so the references never alias.
+        Some(indices.map(|index| unsafe { &mut (*entries.add(index)).2 }))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool 
+    where 
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V> 
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        let (_, ekey, evalue) = T::remove(self, index);
+        Some((ekey, evalue))
+    }
+
+    /// Moves every entry of `other` into `self`, overwriting values for keys
+    /// present in both. `other` is left empty but keeps its table.
+    pub fn append(&mut self, other: &mut HashMap<K, V, S, T>) {
+        self.reserve(other.len());
+        for (key, value) in other.drain() {
+            self.insert(key, value);
+        }
+    }
+
+    /// Moves the entries into a new `std::collections::HashMap`.
+    #[cfg(feature = "std")]
+    pub fn into_std(self) -> std::collections::HashMap<K, V> {
+        let mut map = std::collections::HashMap::with_capacity(self.len());
+        map.extend(self);
+        map
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        T::reserve(self, additional);
+    }
+
+    /// Like [`reserve`](HashMap::reserve), but reports failure instead of
+    /// panicking or aborting. Everything is allocated before any entry is
+    /// moved to the bigger table, so on error the map holds the same
+    /// entries as before.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        T::try_reserve(self, additional)
+    }
+
+    /// Shrinks the table to the smallest that holds the remaining items;
+    /// an empty map gives up its table entirely.
+    pub fn shrink_to_fit(&mut self) {
+        T::shrink_to_fit(self);
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Index of the bucket `key` is, or would be, stored in: the same one
+    /// `insert` and `get` pick, in `0..bucket_count()`. `None` while no
+    /// buckets are allocated. Any resize can move keys to other buckets.
+    ///
+    /// With [two-choice insertion](Self::set_two_choice) on, this is only
+    /// the first of the key's [`candidate_buckets`](Self::candidate_buckets).
+    /// While [rehashing](Self::is_rehashing), a key may still be linked
+    /// from the old buckets instead.
+    pub fn bucket_for<Q: Hash + ?Sized>(&self, key: &Q) -> Option<usize> {
+        self.candidate_buckets(key).map(|[first, _]| first)
+    }
+
+    /// Both buckets `key` may be stored in; they are the same one unless
+    /// [two-choice insertion](Self::set_two_choice) is on. `None` while no
+    /// buckets are allocated.
+    pub fn candidate_buckets<Q: Hash + ?Sized>(&self, key: &Q) -> Option<[usize; 2]> {
+        if self.table.buckets.is_empty() {
+            return None;
+        }
+        Some(self.hash_buckets(self.hash_one(key)))
+    }
+
+    /// Moves every entry into a new map built with `hash_builder`, hashing
+    /// each key again under it; for instance to switch to a seeded hasher
+    /// once keys start to look adversarial.
+    pub fn rehash_with<S2: BuildHasher>(self, hash_builder: S2) -> HashMap<K, V, S2> {
+        let mut map = HashMap::with_capacity_and_hasher(self.len(), hash_builder);
+        for (key, value) in self {
+            // keys of one map are already unique
+            map.insert_unique_unchecked(key, value);
+        }
+        map
+    }
+
+    /// Like `rehash_with`, but in place, for a hasher of the same type. The
+    /// buckets are kept, only the chains through them are rebuilt.
+    pub fn set_hasher(&mut self, hash_builder: S) {
+        self.hash_builder = hash_builder;
+        for entry in &mut self.entries {
+            entry.0 = make_hash(&self.hash_builder, &entry.1);
+        }
+        self.relink();
+    }
+}
+
+// Once a key's hash is known only `Eq` is needed, so these also back the
+// maps built by `with_hash_fn`, whose keys needn't implement `Hash`.
+impl<K: Eq, V, S, T: Table> HashMap<K, V, S, T> {
+    /// Like `insert`, but places the key by a `hash` the caller already
+    /// computed instead of running the map's hasher; keys are still compared
+    /// with `Eq`. The hash should be the one [`hasher`](Self::hasher) would
+    /// produce. A different one is never unsafe, it just puts the entry
+    /// where the ordinary methods won't look, so only the `_hashed` methods
+    /// given the same hash will find it again.
+    pub fn insert_hashed(&mut self, hash: u64, key: K, value: V) -> Option<V> {
+        T::reserve_one(self);
+
+        if let Some(index) = T::find(self, hash, |ekey| ekey == &key) {
+            return Some(mem::replace(&mut self.entries[index].2, value));
+        }
+        T::push(self, hash, key, value);
+        None
+    }
+
+    /// Looks `key` up by a precomputed `hash`, as [`insert_hashed`]
+    /// describes.
+    ///
+    /// [`insert_hashed`]: Self::insert_hashed
+    pub fn get_hashed<Q>(&self, hash: u64, key: &Q) -> Option<&V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        let index = T::find(self, hash, |ekey| key.equivalent(ekey))?;
+        Some(&self.entries[index].2)
+    }
+
+    /// Removes `key` by a precomputed `hash`, as [`insert_hashed`]
+    /// describes.
+    ///
+    /// [`insert_hashed`]: Self::insert_hashed
+    pub fn remove_hashed<Q>(&mut self, hash: u64, key: &Q) -> Option<V>
+    where
+        Q: Equivalent<K> + ?Sized,
+    {
+        let index = T::find(self, hash, |ekey| key.equivalent(ekey))?;
+        let (_, _, evalue) = T::remove(self, index);
+        Some(evalue)
+    }
+
+    fn entry_hashed(&mut self, hash: u64, key: K) -> Entry<'_, K, V, S, T> {
+        T::reserve_one(self);
+
+        match T::find(self, hash, |ekey| ekey == &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry {
+                key: Some(key),
+                map: self,
+                index,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                key,
+                hash,
+                map: self,
+            }),
+        }
+    }
+
+    fn get_mut_hashed(&mut self, hash: u64, key: &K) -> Option<&mut V> {
+        let index = T::find(self, hash, |ekey| ekey == key)?;
+        Some(&mut self.entries[index].2)
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    inner: slice::Iter<'a, (u64, K, V)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, ekey, evalue) = self.inner.next()?;
+        Some((ekey, evalue))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> Clone for Iter<'a, K, V> {
+    fn clone(&self) -> Self {
+        Iter {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V, S, T: Table> IntoIterator for &'a HashMap<K, V, S, T> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+pub struct IntoIter<K, V> {
+    inner: vec::IntoIter<(u64, K, V)>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, ekey, evalue) = self.inner.next()?;
+        Some((ekey, evalue))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+impl<K, V, S, T> IntoIterator for HashMap<K, V, S, T> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.entries.into_iter(),
+        }
+    }
+}
+
+pub struct IntoKeys<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+impl<K, V> Iterator for IntoKeys<K, V> {
+    type Item = K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(ekey, _)| ekey)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoKeys<K, V> {}
+
+impl<K, V> FusedIterator for IntoKeys<K, V> {}
+
+pub struct IntoValues<K, V> {
+    inner: IntoIter<K, V>,
+}
+
+impl<K, V> Iterator for IntoValues<K, V> {
+    type Item = V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, evalue)| evalue)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoValues<K, V> {}
+
+impl<K, V> FusedIterator for IntoValues<K, V> {}
+
+pub struct Keys<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(ekey, _)| ekey)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Keys<'a, K, V> {}
+
+pub struct Values<'a, K, V> {
+    inner: Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, evalue)| evalue)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Values<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for Values<'a, K, V> {}
+
+pub struct IterMut<'a, K, V> {
+    inner: slice::IterMut<'a, (u64, K, V)>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, ekey, evalue) = self.inner.next()?;
+        Some((&*ekey, evalue))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V, S, T: Table> IntoIterator for &'a mut HashMap<K, V, S, T> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+pub struct ValuesMut<'a, K, V> {
+    inner: IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, evalue)| evalue)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for ValuesMut<'a, K, V> {}
+
+pub struct Drain<'a, K, V, S = DefaultState> {
+    inner: vec::Drain<'a, (u64, K, V)>,
+    marker: PhantomData<&'a mut S>,
+}
+
+impl<'a, K, V, S> Iterator for Drain<'a, K, V, S> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        let (_, ekey, evalue) = self.inner.next()?;
+        Some((ekey, evalue))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for Drain<'a, K, V, S> {}
+
+impl<'a, K, V, S> FusedIterator for Drain<'a, K, V, S> {}
+
+pub struct ExtractIf<'a, K, V, F, S = DefaultState>
+where
+    F: FnMut(&K, &mut V) -> bool
+{
+    map: &'a mut HashMap<K, V, S>,
+    at: usize,
+    pred: F,
+}
+
+impl<'a, K, V, F, S> Iterator for ExtractIf<'a, K, V, F, S>
+where
+    F: FnMut(&K, &mut V) -> bool
+{
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((_, ekey, evalue)) = self.map.entries.get_mut(self.at) {
+            if (self.pred)(ekey, evalue) {
+                // the last entry moves into `at`, so don't advance
+                let (_, ekey, evalue) = self.map.remove_index(self.at);
+                return Some((ekey, evalue));
+            }
+            self.at += 1;
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.map.len() - self.at))
+    }
+}
+
+impl<'a, K, V, F, S> FusedIterator for ExtractIf<'a, K, V, F, S>
+where
+    F: FnMut(&K, &mut V) -> bool
+{}
+
+impl<K, V, S: Default, T: Table + Default> Default for HashMap<K, V, S, T> {
+    fn default() -> Self {
+        HashMap::with_table_and_hasher(T::default(), S::default())
+    }
+}
+
+impl<K: Clone, V: Clone, S: Clone, T: Clone> Clone for HashMap<K, V, S, T> {
+    fn clone(&self) -> Self {
+        HashMap {
+            entries: self.entries.clone(),
+            hash_builder: self.hash_builder.clone(),
+            table: self.table.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        // `Vec::clone_from` clones element-wise into the existing arena and
+        // index arrays, so capacity already held by `self` is reused rather
+        // than reallocated.
+        self.entries.clone_from(&source.entries);
+        // the table was laid out by `source`'s hasher
+        self.hash_builder.clone_from(&source.hash_builder);
+        self.table.clone_from(&source.table);
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug, S, T: Table> fmt::Debug for HashMap<K, V, S, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// maps on different tables compare by their entries too
+impl<K, V, S, T, U> PartialEq<HashMap<K, V, S, U>> for HashMap<K, V, S, T>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+    T: Table,
+    U: Table,
+{
+    fn eq(&self, other: &HashMap<K, V, S, U>) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K, V, S, T> Eq for HashMap<K, V, S, T>
+where
+    K: Hash + Eq,
+    V: Eq,
+    S: BuildHasher,
+    T: Table,
+{}
+
+#[cfg(feature = "std")]
+impl<K, V, S, T, H> PartialEq<std::collections::HashMap<K, V, H>> for HashMap<K, V, S, T>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+    T: Table,
+    H: BuildHasher,
+{
+    fn eq(&self, other: &std::collections::HashMap<K, V, H>) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, T, H> PartialEq<HashMap<K, V, S, T>> for std::collections::HashMap<K, V, H>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+    T: Table,
+    H: BuildHasher,
+{
+    fn eq(&self, other: &HashMap<K, V, S, T>) -> bool {
+        other == self
+    }
+}
+
+impl<K, V, S, T> FromIterator<(K, V)> for HashMap<K, V, S, T>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+    T: Table + Default,
+{
+    fn from_iter<I>(iter: I) -> Self 
+    where
+        I: IntoIterator<Item = (K, V)>
+    {
+        let iter = iter.into_iter();
+        let mut map = Self::default();
+        // only the lower bound is trusted, and even that may lie: a hint too
+        // big to allocate is dropped, and one the iterator failed to live up
+        // to is given back once collection is done
+        let _ = map.try_reserve(iter.size_hint().0);
+        for (k, v) in iter {
+            map.insert(k ,v);
+        }
+        T::maybe_shrink(&mut map);
+        map
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, H> From<std::collections::HashMap<K, V, H>> for HashMap<K, V>
+where
+    K: Hash + Eq
+{
+    fn from(map: std::collections::HashMap<K, V, H>) -> Self {
+        HashMap::from_std(map)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S, T> From<HashMap<K, V, S, T>> for std::collections::HashMap<K, V>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+    T: Table,
+{
+    fn from(map: HashMap<K, V, S, T>) -> Self {
+        map.into_std()
+    }
+}
+
+impl<K, V, S, T, Q> Index<&Q> for HashMap<K, V, S, T>
+where
+    K: Hash + Eq,
+    Q: Hash + Equivalent<K> + ?Sized,
+    S: BuildHasher,
+    T: Table,
+{
+    type Output = V;
+
+    /// Panics if `key` is not in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("HashMap::index: key not found")
+    }
+}
+
+impl<K, V, S, T> Extend<(K, V)> for HashMap<K, V, S, T>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+    T: Table,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>
+    {
+        let iter = iter.into_iter();
+        // like std: if the map already has entries, assume about half of the
+        // new keys are duplicates rather than reserving for all of them
+        let additional = match self.is_empty() {
+            true => iter.size_hint().0,
+            false => iter.size_hint().0.div_ceil(2),
+        };
+        self.reserve(additional);
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<'a, K, V, S, T> Extend<(&'a K, &'a V)> for HashMap<K, V, S, T>
+where
+    K: Hash + Eq + Copy,
+    V: Copy,
+    S: BuildHasher,
+    T: Table,
+{
+    fn extend<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (&'a K, &'a V)>
+    {
+        self.extend(iter.into_iter().map(|(&k, &v)| (k, v)));
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V, const N: usize> From<[(K, V); N]> for HashMap<K, V>
+where 
+    K: Hash + Eq
+{
+    /// Builds a map sized for all `N` pairs up front. If a key appears more
+    /// than once, the last pair wins.
+    fn from(arr: [(K, V); N]) -> Self {
+        let mut map = HashMap::with_capacity(N);
+        map.extend(arr);
+        map
+    }
+}
+
+
+// Runs without the `std` feature too: only `core` hashing is involved.
+#[cfg(test)]
+mod no_std_tests {
+    use super::*;
+    use core::hash::{BuildHasherDefault, Hasher};
+
+    // 64-bit FNV-1a, which needs nothing beyond `core`.
+    struct Fnv(u64);
+
+    impl Default for Fnv {
+        fn default() -> Self {
+            Fnv(0xcbf2_9ce4_8422_2325)
+        }
+    }
+
+    impl Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            self.0
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            for &byte in bytes {
+                self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    #[test]
+    fn core_hasher() {
+        let mut map: HashMap<u32, &str, BuildHasherDefault<Fnv>> = HashMap::default();
+        assert_eq!(map.insert(1, "one"), None);
+        assert_eq!(map.insert(2, "two"), None);
+        assert_eq!(map.insert(1, "uno"), Some("one"));
+        assert_eq!(map.get(&1), Some(&"uno"));
+        assert_eq!(map.get(&3), None);
+
+        *map.entry(3).or_insert("three") = "tres";
+        match map.entry(2) {
+            Entry::Occupied(e) => assert_eq!(e.remove(), "two"),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&3], "tres");
+
+        for i in 10..1000 {
+            map.insert(i, "many");
+        }
+        assert_eq!(map.len(), 992);
+        assert!((10..1000).all(|i| map.contains_key(&i)));
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::test_util::Colliding;
+    use std::borrow::Borrow;
+    use std::hash::Hasher;
+
+    #[test]
+    fn insert_get() {
+        let mut map = HashMap::new();
+        map.insert("foo", 42);
+        assert_eq!(map.get(&"foo"), Some(&42));
+    }
+
+    #[test]
+    fn remove() {
+        let mut map = HashMap::new();
+        map.insert("foo", "bar");
+        assert_eq!(map.get(&"foo"), Some(&"bar"));
+        assert_eq!(map.remove(&"foo"), Some("bar"));
+    }
+
+    #[test]
+    fn len_0() {
+        let mut map = HashMap::new();
+        assert_eq!(map.len(), 0);
+        map.insert("foo", "bar");
+    }
+
+    #[test]
+    fn len_1() {
+        let mut map = HashMap::new();
+        map.insert("foo", "bar");
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn is_empty() {
+        let mut map = HashMap::new();
+        assert_eq!(map.is_empty(), true);
+        map.insert("foo", "bar");
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
+    fn contains_key() {
+        let mut map = HashMap::new();
+        map.insert("foo", "bar");
+        assert_eq!(map.contains_key(&"foo"), true);
+        assert_eq!(map.contains_key(&"bar"), false);
+    }
+
+    #[test]
+    fn iter() {
+        let mut map = HashMap::new();
+        map.insert("foo", 41);
+        map.insert("bar", 42);
+        map.insert("baz", 413);
+        map.insert("quox", 4);
+
+        for (&key, &value) in &map {
+            match key {
+                "foo" => assert_eq!(value, 41),
+                "bar" => assert_eq!(value, 42),
+                "baz" => assert_eq!(value, 413),
+                "quox" => assert_eq!(value, 4),
+                _ => unreachable!(),
+            }
+        }
+
+        assert_eq!((&map).into_iter().count(), 4);
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut map = HashMap::new();
+        map.insert("foo", 42);
+        *map.get_mut(&"foo").unwrap() += 1;
+        assert_eq!(map.get(&"foo"), Some(&43));
+    }
+
+    #[test]
+    fn get_mut_missing() {
+        let mut map: HashMap<&str, i32> = HashMap::new();
+        assert_eq!(map.get_mut(&"foo"), None);
+        map.insert("foo", 42);
+        assert_eq!(map.get_mut(&"bar"), None);
+    }
+
+    #[test]
+    fn get_mut_borrowed() {
+        let mut map = HashMap::new();
+        map.insert("count".to_string(), 0);
+        *map.get_mut("count").unwrap() += 1;
+        assert_eq!(map.get("count"), Some(&1));
+    }
+
+    #[derive(Debug)]
+    struct Tagged {
+        name: &'static str,
+        tag: u32,
+    }
+
+    impl PartialEq for Tagged {
+        fn eq(&self, other: &Self) -> bool {
+            self.name == other.name
+        }
+    }
+
+    impl Eq for Tagged {}
+
+    impl Hash for Tagged {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.name.hash(state);
+        }
+    }
+
+    #[test]
+    fn get_key_value() {
+        let mut map = HashMap::new();
+        map.insert(Tagged { name: "foo", tag: 1 }, 42);
+
+        let (key, value) = map.get_key_value(&Tagged { name: "foo", tag: 2 }).unwrap();
+        assert_eq!(key.tag, 1);
+        assert_eq!(*value, 42);
+        assert!(map.get_key_value(&Tagged { name: "bar", tag: 1 }).is_none());
+    }
+
+    #[test]
+    fn get_key_value_borrowed() {
+        let mut map: HashMap<String, i32> = HashMap::new();
+        assert_eq!(map.get_key_value("foo"), None);
+        map.insert("foo".to_string(), 42);
+        assert_eq!(map.get_key_value("foo"), Some((&"foo".to_string(), &42)));
+    }
+
+    #[test]
+    fn get_key_value_mut() {
+        let mut map = HashMap::new();
+        map.insert(Tagged { name: "foo", tag: 1 }, 42);
+
+        let (key, value) = map.get_key_value_mut(&Tagged { name: "foo", tag: 2 }).unwrap();
+        assert_eq!(key.tag, 1);
+        *value += 1;
+        assert_eq!(map.get(&Tagged { name: "foo", tag: 3 }), Some(&43));
+        assert!(map.get_key_value_mut(&Tagged { name: "bar", tag: 1 }).is_none());
+    }
+
+    #[test]
+    fn remove_entry() {
+        let mut map = HashMap::new();
+        map.insert(Tagged { name: "foo", tag: 1 }, 42);
+        map.insert(Tagged { name: "bar", tag: 2 }, 43);
+
+        let (key, value) = map.remove_entry(&Tagged { name: "foo", tag: 3 }).unwrap();
+        assert_eq!(key.tag, 1);
+        assert_eq!(value, 42);
+        assert_eq!(map.len(), 1);
+        assert!(map.remove_entry(&Tagged { name: "foo", tag: 1 }).is_none());
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn clear() {
+        let mut map = HashMap::new();
+        for i in 0..16 {
+            map.insert(i, i.to_string());
+        }
+        let nbuckets = map.table.buckets.len();
+
+        map.clear();
+        assert_eq!(map.len(), 0);
+        assert!(map.is_empty());
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.table.buckets.len(), nbuckets);
+
+        map.insert(1, "one".to_string());
+        assert_eq!(map.get(&1), Some(&"one".to_string()));
+    }
+
+    #[test]
+    fn capacity() {
+        let mut map = HashMap::new();
+        assert_eq!(map.capacity(), 0);
+
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        let capacity = map.capacity();
+        let nbuckets = map.table.buckets.len();
+        assert!(capacity >= map.len());
+
+        for i in map.len()..capacity {
+            map.insert(i, i);
+        }
+        assert_eq!(map.table.buckets.len(), nbuckets);
+
+        map.insert(capacity, capacity);
+        assert!(map.table.buckets.len() > nbuckets);
+        assert!(map.capacity() > capacity);
+    }
+
+    #[test]
+    fn insert_overwrite_len() {
+        let mut map = HashMap::new();
+        assert_eq!(map.insert("foo", 1), None);
+        assert_eq!(map.insert("foo", 2), Some(1));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&"foo"), Some(&2));
+    }
+
+    #[test]
+    fn with_capacity() {
+        let map: HashMap<i32, i32> = HashMap::with_capacity(0);
+        assert_eq!(map.table.buckets.len(), 0);
+        assert_eq!(map.capacity(), 0);
+
+        for capacity in 1..200 {
+            let mut map = HashMap::with_capacity(capacity);
+            assert!(map.capacity() >= capacity);
+            let nbuckets = map.table.buckets.len();
+            for i in 0..capacity {
+                map.insert(i, i);
+            }
+            assert_eq!(map.table.buckets.len(), nbuckets);
+        }
+    }
+
+    #[test]
+    fn power_of_two_buckets() {
+        for capacity in [1, 2, 3, 5, 13, 100, 1000, 4097] {
+            let map: HashMap<u32, u32> = HashMap::with_capacity(capacity);
+            assert!(map.table.buckets.len().is_power_of_two());
+            assert!(map.capacity() >= capacity);
+        }
+        let mut map = HashMap::new();
+        for i in 0..10_000 {
+            map.insert(i, i);
+            assert!(map.table.buckets.len().is_power_of_two());
+        }
+        map.retain(|&k, _| k < 10);
+        map.shrink_to_fit();
+        assert!(map.table.buckets.len().is_power_of_two());
+    }
+
+    #[test]
+    fn reserve_empty() {
+        let mut map = HashMap::new();
+        map.reserve(0);
+        assert_eq!(map.table.buckets.len(), 0);
+
+        map.reserve(100);
+        let nbuckets = map.table.buckets.len();
+        assert!(map.capacity() >= 100);
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.table.buckets.len(), nbuckets);
+    }
+
+    #[test]
+    fn reserve_headroom() {
+        let mut map = HashMap::with_capacity(100);
+        for i in 0..10 {
+            map.insert(i, i);
+        }
+        let nbuckets = map.table.buckets.len();
+        map.reserve(50);
+        assert_eq!(map.table.buckets.len(), nbuckets);
+    }
+
+    #[test]
+    fn reserve_grows() {
+        let mut map = HashMap::new();
+        for i in 0..10 {
+            map.insert(i, i);
+        }
+        map.reserve(1000);
+        let nbuckets = map.table.buckets.len();
+        for i in 10..1010 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.table.buckets.len(), nbuckets);
+        for i in 0..1010 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+    }
+
+    #[test]
+    fn try_reserve() {
+        let mut map: HashMap<i32, i32> = HashMap::new();
+        assert_eq!(map.try_reserve(100), Ok(()));
+        assert!(map.capacity() >= 100);
+    }
+
+    #[test]
+    fn try_reserve_overflow() {
+        let mut map: HashMap<i32, i32> = HashMap::new();
+        assert_eq!(map.try_reserve(usize::MAX), Err(TryReserveError::CapacityOverflow));
+
+        map.insert(1, 1);
+        assert_eq!(map.try_reserve(usize::MAX), Err(TryReserveError::CapacityOverflow));
+        assert_eq!(map.try_reserve(usize::MAX / 8), Err(TryReserveError::AllocError));
+
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&1), Some(&1));
+        map.insert(2, 2);
+        assert_eq!(map.get(&2), Some(&2));
+    }
+
+    #[test]
+    fn try_reserve_error_display() {
+        assert!(TryReserveError::CapacityOverflow.to_string().contains("capacity overflow"));
+        assert!(TryReserveError::AllocError.to_string().contains("allocation failed"));
+    }
+
+    /// Counts fresh allocations made by this thread, and separately the
+    /// reallocations that grow a block.
+    struct CountingAlloc;
+
+    thread_local! {
+        static FRESH_ALLOCS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        static GROWN_ALLOCS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = FRESH_ALLOCS.try_with(|n| n.set(n.get() + 1));
+            unsafe { std::alloc::System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            unsafe { std::alloc::System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+            if new_size > layout.size() {
+                let _ = GROWN_ALLOCS.try_with(|n| n.set(n.get() + 1));
+            }
+            unsafe { std::alloc::System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+    fn fresh_allocs<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = FRESH_ALLOCS.with(std::cell::Cell::get);
+        let out = f();
+        (out, FRESH_ALLOCS.with(std::cell::Cell::get) - before)
+    }
+
+    /// Fresh allocations and growing reallocations `f` makes.
+    fn heap_growth<T>(f: impl FnOnce() -> T) -> (T, usize) {
+        let before = GROWN_ALLOCS.with(std::cell::Cell::get);
+        let (out, fresh) = fresh_allocs(f);
+        (out, fresh + GROWN_ALLOCS.with(std::cell::Cell::get) - before)
+    }
+
+    #[test]
+    fn growth_resizes_allocations_in_place() {
+        // below the incremental threshold every growth is a plain rehash;
+        // the buckets, entries and next links are each allocated once and
+        // only reallocated after that
+        let (map, allocs) = fresh_allocs(|| {
+            let mut map = HashMap::with_hasher(DeterministicState::new(1));
+            for i in 0..700u64 {
+                map.insert(i, i);
+            }
+            map
+        });
+        assert_eq!(allocs, 3);
+        assert!(map.bucket_count() > INITIAL_NBUCKETS);
+        // the arena was sized along with the table
+        assert!(map.entries.capacity() >= map.capacity());
+        check_arena(&map);
+
+        // shrinking trims the same arrays rather than allocating new ones
+        let (map, allocs) = fresh_allocs(move || {
+            let mut map = map;
+            map.retain(|&k, _| k < 10);
+            map.shrink_to_fit();
+            map
+        });
+        assert_eq!(allocs, 0);
+        assert_eq!(map.len(), 10);
+        check_arena(&map);
+
+        // two-choice relinking counts chain lengths in the old bucket array
+        // left by an incremental rehash instead of a new one
+        let mut map = HashMap::new();
+        grow_until_rehashing(&mut map, &mut std::collections::HashMap::new());
+        let ((), allocs) = fresh_allocs(|| map.set_two_choice(true));
+        assert_eq!(allocs, 0);
+        assert!(!map.is_rehashing());
+        check_arena(&map);
+    }
+
+    #[test]
+    fn try_reserve_allocates_before_relinking() {
+        let mut map = HashMap::with_hasher(DeterministicState::new(2));
+        map.set_two_choice(true);
+        map.set_miss_filter(Some(10));
+        map.extend((0..100u32).map(|i| (i, i)));
+        let target_size = 4 * map.bucket_count();
+        map.try_reserve_rehash(target_size).unwrap();
+        let ((), grown) = heap_growth(|| map.rehash(target_size));
+        assert_eq!(grown, 0);
+        assert_eq!(map.bucket_count(), target_size);
+        assert!((0..100).all(|i| map.get(&i) == Some(&i)));
+        check_arena(&map);
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut map = HashMap::new();
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        for i in 100..1000 {
+            map.remove(&i);
+        }
+        let nbuckets = map.table.buckets.len();
+
+        map.shrink_to_fit();
+        assert!(map.table.buckets.len() < nbuckets);
+        assert!(map.capacity() >= map.len());
+        assert_eq!(map.len(), 100);
+        for i in 0..100 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+        assert_eq!(map.get(&500), None);
+
+        map.insert(1000, 1000);
+        assert_eq!(map.get(&1000), Some(&1000));
+    }
+
+    #[test]
+    fn shrink_to_fit_empty() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        map.clear();
+        map.shrink_to_fit();
+        assert_eq!(map.table.buckets.len(), 0);
+        assert_eq!(map.capacity(), 0);
+        assert_eq!(map.get(&1), None);
+
+        map.insert(1, 1);
+        assert_eq!(map.get(&1), Some(&1));
+    }
+
+    #[test]
+    fn shrink_to() {
+        let mut map = HashMap::new();
+        // keep the removals below from shrinking the map on their own
+        map.set_auto_shrink(false);
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        for i in 10..1000 {
+            map.remove(&i);
+        }
+
+        map.shrink_to(200);
+        assert!(map.capacity() >= 200);
+        let nbuckets = map.table.buckets.len();
+        for i in 10..200 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.table.buckets.len(), nbuckets);
+        assert_eq!(map.len(), 200);
+    }
+
+    #[test]
+    fn shrink_to_clamps_to_len() {
+        let mut map = HashMap::new();
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        for i in 100..1000 {
+            map.remove(&i);
+        }
+
+        map.shrink_to(0);
+        assert!(map.capacity() >= 100);
+        assert_eq!(map.len(), 100);
+        for i in 0..100 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+    }
+
+    #[test]
+    fn shrink_to_noop() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        let nbuckets = map.table.buckets.len();
+        map.shrink_to(10_000);
+        assert_eq!(map.table.buckets.len(), nbuckets);
+        assert_eq!(map.len(), 100);
+    }
+
+    #[test]
+    fn auto_shrink_after_mass_removal() {
+        let mut map = HashMap::new();
+        for i in 0..100_000 {
+            map.insert(i, i);
+        }
+        let full = map.table.buckets.len();
+        for i in 50..100_000 {
+            map.remove(&i);
+        }
+        assert!(map.table.buckets.len() * 64 < full);
+        assert!(map.capacity() >= 2 * map.len());
+        assert!((0..50).all(|i| map[&i] == i));
+
+        map.retain(|&k, _| k < 2);
+        assert!(map.table.buckets.len() <= 4);
+        *map.entry(1).or_insert(0) += 10;
+        assert_eq!(map.entry(1).or_insert(0).to_owned(), 11);
+        match map.entry(0) {
+            Entry::Occupied(e) => assert_eq!(e.remove(), 0),
+            Entry::Vacant(_) => panic!("0 should be present"),
+        }
+        assert_eq!(map.remove(&1), Some(11));
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn auto_shrink_no_thrashing() {
+        let mut map = HashMap::new();
+        for i in 0..1024 {
+            map.insert(i, i);
+        }
+        // walk down to the first shrink, then churn just around it
+        let mut next = 1023;
+        let nbuckets = map.table.buckets.len();
+        while map.table.buckets.len() == nbuckets {
+            map.remove(&next);
+            next -= 1;
+        }
+        let shrunk = map.table.buckets.len();
+        for _ in 0..1000 {
+            map.insert(next + 1, 0);
+            map.remove(&(next + 1));
+            map.remove(&next);
+            map.insert(next, next);
+            assert_eq!(map.table.buckets.len(), shrunk);
+        }
+
+        // and the same at the growth threshold
+        let nbuckets = map.table.buckets.len();
+        while map.table.buckets.len() == nbuckets {
+            next += 1;
+            map.insert(next, next);
+        }
+        let grown = map.table.buckets.len();
+        for _ in 0..1000 {
+            map.remove(&next);
+            map.insert(next, next);
+            assert_eq!(map.table.buckets.len(), grown);
+        }
+    }
+
+    #[test]
+    fn auto_shrink_opt_out() {
+        let mut map = HashMap::new();
+        assert!(map.auto_shrink());
+        map.set_auto_shrink(false);
+        for i in 0..10_000 {
+            map.insert(i, i);
+        }
+        let nbuckets = map.table.buckets.len();
+        map.retain(|&k, _| k == 0);
+        for i in 1..10_000 {
+            map.remove(&i);
+        }
+        assert_eq!(map.table.buckets.len(), nbuckets);
+        assert!(!map.clone().auto_shrink());
+        map.shrink_to_fit();
+        assert!(map.table.buckets.len() < nbuckets);
+    }
+
+    #[test]
+    fn retain() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        map.retain(|&key, value| {
+            *value *= 10;
+            key % 2 == 0
+        });
+        assert_eq!(map.len(), 50);
+        assert_eq!(map.get(&3), None);
+        assert_eq!(map.get(&4), Some(&40));
+        assert_eq!((&map).into_iter().count(), 50);
+    }
+
+    #[test]
+    fn retain_empty() {
+        let mut map: HashMap<i32, i32> = HashMap::new();
+        map.retain(|_, _| false);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn drain() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i * 10);
+        }
+        let nbuckets = map.table.buckets.len();
+
+        let mut drained: Vec<_> = map.drain().collect();
+        drained.sort();
+        assert_eq!(drained, (0..100).map(|i| (i, i * 10)).collect::<Vec<_>>());
+        assert!(map.is_empty());
+        assert_eq!(map.table.buckets.len(), nbuckets);
+        assert_eq!((&map).into_iter().count(), 0);
+    }
+
+    #[test]
+    fn sparse_presized_map() {
+        let mut map = HashMap::with_capacity(1_000_000);
+        map.set_auto_shrink(false);
+        for i in 0..100u32 {
+            map.insert(i, i * 2);
+        }
+        let nbuckets = map.bucket_count();
+        assert!(nbuckets > 1_000_000);
+        assert_eq!(map.iter().count(), 100);
+        assert_eq!(map.keys().count(), 100);
+        let mut values: Vec<_> = map.values().copied().collect();
+        values.sort();
+        assert_eq!(values, (0..100).map(|i| i * 2).collect::<Vec<_>>());
+
+        // removals that empty buckets out leave the rest reachable
+        for i in (0..100).step_by(3) {
+            assert_eq!(map.remove(&i), Some(i * 2));
+        }
+        let mut keys: Vec<_> = map.keys().copied().collect();
+        keys.sort();
+        assert_eq!(keys, (0..100).filter(|i| i % 3 != 0).collect::<Vec<_>>());
+        check_arena(&map);
+
+        let mut drained: Vec<_> = map.drain().collect();
+        drained.sort();
+        assert_eq!(drained.len(), 66);
+        assert!(map.table.buckets.iter().all(|&b| b == NONE));
+        assert_eq!(map.bucket_count(), nbuckets);
+
+        for i in 0..10 {
+            map.insert(i, i);
+        }
+        map.clear();
+        assert!(map.table.buckets.iter().all(|&b| b == NONE));
+        map.insert(7, 7);
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&7, &7)]);
+        check_arena(&map);
+
+        // two-choice maps reset both candidates
+        map.set_two_choice(true);
+        for i in 0..50 {
+            map.insert(i, i);
+        }
+        map.clear();
+        assert!(map.table.buckets.iter().all(|&b| b == NONE));
+    }
+
+    #[test]
+    fn drain_partial() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i.to_string());
+        }
+        let nbuckets = map.table.buckets.len();
+
+        let mut drain = map.drain();
+        assert_eq!(drain.len(), 100);
+        assert!(drain.next().is_some());
+        assert!(drain.next().is_some());
+        assert_eq!(drain.len(), 98);
+        drop(drain);
+
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.table.buckets.len(), nbuckets);
+        assert_eq!(map.get(&1), None);
+        map.insert(1, "one".to_string());
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn drain_empty() {
+        let mut map: HashMap<i32, i32> = HashMap::new();
+        assert_eq!(map.drain().next(), None);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn extract_if() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+
+        let mut extracted: Vec<_> = map.extract_if(|&key, _| key % 3 == 0).collect();
+        extracted.sort();
+        assert_eq!(extracted, (0..100).step_by(3).map(|i| (i, i)).collect::<Vec<_>>());
+        assert_eq!(map.len(), 100 - extracted.len());
+        for i in 0..100 {
+            assert_eq!(map.contains_key(&i), i % 3 != 0);
+        }
+    }
+
+    #[test]
+    fn extract_if_dropped_early() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+
+        let (key, value) = map
+            .extract_if(|_, value| {
+                *value += 1000;
+                true
+            })
+            .next()
+            .unwrap();
+        assert_eq!(value, key + 1000);
+
+        assert_eq!(map.len(), 99);
+        assert!(!map.contains_key(&key));
+        assert_eq!((&map).into_iter().count(), 99);
+    }
+
+    #[test]
+    fn keys() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i.to_string());
+        }
+
+        let mut keys: Vec<_> = map.keys().copied().collect();
+        keys.sort();
+        assert_eq!(keys, (0..100).collect::<Vec<_>>());
+        assert_eq!(map.keys().len(), map.len());
+
+        let mut keys = map.keys();
+        keys.next();
+        assert_eq!(keys.len(), 99);
+    }
+
+    #[test]
+    fn values() {
+        let mut map = HashMap::new();
+        for i in 0..1000u64 {
+            map.insert(i, i);
+        }
+        assert!(map.table.buckets.len() > 1);
+
+        assert_eq!(map.values().sum::<u64>(), (0..1000).sum::<u64>());
+        assert_eq!(map.values().size_hint(), (1000, Some(1000)));
+        assert_eq!(map.values().collect::<Vec<_>>().len(), 1000);
+    }
+
+    #[test]
+    fn values_mut() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+
+        assert_eq!(map.values_mut().len(), map.len());
+        let mut count = 0;
+        for value in map.values_mut() {
+            *value *= 3;
+            count += 1;
+        }
+        assert_eq!(count, 100);
+        for i in 0..100 {
+            assert_eq!(map.get(&i), Some(&(i * 3)));
+        }
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+
+        assert_eq!(map.iter_mut().len(), 100);
+        for (&key, value) in map.iter_mut() {
+            *value += key;
+        }
+        for (_, value) in &mut map {
+            *value += 1;
+        }
+        for i in 0..100 {
+            assert_eq!(map.get(&i), Some(&(2 * i + 1)));
+        }
+    }
+
+    #[test]
+    fn iter_size_hint() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        assert!(map.table.buckets.len() > 1);
+        assert_eq!(map.iter().len(), map.len());
+
+        let mut iter = map.iter();
+        for remaining in (0..100).rev() {
+            assert!(iter.next().is_some());
+            assert_eq!(iter.size_hint(), (remaining, Some(remaining)));
+        }
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.len(), 0);
+    }
+
+    fn sparse_map() -> HashMap<i32, i32> {
+        // plenty of empty buckets trailing the few occupied ones
+        let mut map = HashMap::with_capacity(1000);
+        for i in 0..3 {
+            map.insert(i, i);
+        }
+        map
+    }
+
+    fn assert_fused<I: Iterator>(mut iter: I) {
+        while iter.next().is_some() {}
+        for _ in 0..10 {
+            assert!(iter.next().is_none());
+        }
+    }
+
+    #[test]
+    fn fused() {
+        let mut map = sparse_map();
+        assert_fused(map.iter());
+        assert_fused(map.keys());
+        assert_fused(map.values());
+        assert_fused(map.iter_mut());
+        assert_fused(map.values_mut());
+        assert_fused(map.extract_if(|_, _| false));
+        assert_fused(map.drain());
+
+        let mut map: HashMap<i32, i32> = HashMap::new();
+        assert_fused(map.iter());
+        assert_fused(map.drain());
+    }
+
+    struct DropCounter<'a>(&'a std::cell::Cell<usize>);
+
+    impl Drop for DropCounter<'_> {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i.to_string());
+        }
+        assert_eq!(map.len(), 100);
+
+        let mut entries = Vec::new();
+        for (key, value) in map {
+            entries.push((key, value));
+        }
+        entries.sort();
+        assert_eq!(entries, (0..100).map(|i| (i, i.to_string())).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn into_iter_count() {
+        let map: HashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+        let len = map.len();
+        let iter = map.into_iter();
+        assert_eq!(iter.len(), len);
+        assert_eq!(iter.count(), len);
+    }
+
+    #[test]
+    fn into_iter_drop_early() {
+        let drops = std::cell::Cell::new(0);
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, DropCounter(&drops));
+        }
+
+        let mut iter = map.into_iter();
+        for _ in 0..10 {
+            iter.next();
+        }
+        assert_eq!(drops.get(), 10);
+        drop(iter);
+        assert_eq!(drops.get(), 100);
+    }
+
+    #[test]
+    fn into_iter_fused() {
+        assert_fused(sparse_map().into_iter());
+    }
+
+    #[test]
+    fn into_iter_mut_ref() {
+        fn bump<'a, I>(entries: I)
+        where
+            I: IntoIterator<Item = (&'a &'static str, &'a mut i32)>
+        {
+            for (_, value) in entries {
+                *value += 10;
+            }
+        }
+
+        let mut map = HashMap::new();
+        map.insert("foo", 1);
+        map.insert("bar", 2);
+
+        for (_, v) in &mut map {
+            *v += 1;
+        }
+        bump(&mut map);
+        assert_eq!(map.get(&"foo"), Some(&12));
+        assert_eq!(map.get(&"bar"), Some(&13));
+    }
+
+    #[test]
+    fn into_keys() {
+        let drops = std::cell::Cell::new(0);
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, DropCounter(&drops));
+        }
+
+        let mut keys = map.into_keys();
+        assert_eq!(keys.size_hint(), (100, Some(100)));
+        let mut seen: Vec<_> = keys.by_ref().take(50).collect();
+        assert_eq!(drops.get(), 50);
+        assert_eq!(keys.len(), 50);
+        seen.extend(keys);
+        seen.sort();
+        assert_eq!(seen, (0..100).collect::<Vec<_>>());
+        assert_eq!(drops.get(), 100);
+    }
+
+    struct DropKey<'a>(i32, &'a std::cell::Cell<usize>);
+
+    impl PartialEq for DropKey<'_> {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+
+    impl Eq for DropKey<'_> {}
+
+    impl Hash for DropKey<'_> {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.hash(state);
+        }
+    }
+
+    impl Drop for DropKey<'_> {
+        fn drop(&mut self) {
+            self.1.set(self.1.get() + 1);
+        }
+    }
+
+    #[test]
+    fn into_values() {
+        let drops = std::cell::Cell::new(0);
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(DropKey(i, &drops), i);
+        }
+
+        let mut values = map.into_values();
+        assert_eq!(values.len(), 100);
+        let mut seen: Vec<_> = values.by_ref().take(30).collect();
+        assert_eq!(drops.get(), 30);
+        assert_eq!(values.len(), 70);
+        seen.extend(values);
+        seen.sort();
+        assert_eq!(seen, (0..100).collect::<Vec<_>>());
+        assert_eq!(drops.get(), 100);
+    }
+
+    #[test]
+    fn into_values_drop_early() {
+        let drops = std::cell::Cell::new(0);
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(DropKey(i, &drops), i);
+        }
+
+        let mut values = map.into_values();
+        values.next();
+        drop(values);
+        assert_eq!(drops.get(), 100);
+    }
+
+    #[test]
+    fn try_insert() {
+        let mut map = HashMap::new();
+        let value = map.try_insert("foo", 1).unwrap();
+        *value += 1;
+        assert_eq!(map.get(&"foo"), Some(&2));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn try_insert_occupied() {
+        let mut map = HashMap::new();
+        map.insert("foo", 1);
+
+        let err = map.try_insert("foo", 2).unwrap_err();
+        assert_eq!(err.value, 2);
+        assert_eq!(
+            err.to_string(),
+            "failed to insert 2, key \"foo\" already exists with value 1"
+        );
+        assert!(format!("{:?}", err).contains("old_value: 1"));
+
+        assert_eq!(map.get(&"foo"), Some(&1));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn insert_unique_unchecked() {
+        let mut map = HashMap::new();
+        for i in 0..100_000 {
+            let (key, value) = map.insert_unique_unchecked(i, i * 2);
+            assert_eq!(*key, i);
+            *value += 1;
+        }
+        assert_eq!(map.len(), 100_000);
+        for i in 0..100_000 {
+            assert_eq!(map.get(&i), Some(&(i * 2 + 1)));
+        }
+    }
+
+    #[test]
+    fn get_many_mut_different_buckets() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        let (a, b) = (0..100)
+            .flat_map(|a| (0..100).map(move |b| (a, b)))
+            .find(|&(a, b)| map.bucket_for(&a) != map.bucket_for(&b))
+            .unwrap();
+
+        let [x, y] = map.get_many_mut([&a, &b]).unwrap();
+        *x += 1000;
+        *y += 2000;
+        assert_eq!(map.get(&a), Some(&(a + 1000)));
+        assert_eq!(map.get(&b), Some(&(b + 2000)));
+    }
+
+    #[test]
+    fn get_many_mut_same_bucket() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        let (a, b) = (0..100)
+            .flat_map(|a| (0..100).map(move |b| (a, b)))
+            .find(|&(a, b)| a != b && map.bucket_for(&a) == map.bucket_for(&b))
+            .unwrap();
+
+        let [x, y] = map.get_many_mut([&a, &b]).unwrap();
+        mem::swap(x, y);
+        assert_eq!(map.get(&a), Some(&b));
+        assert_eq!(map.get(&b), Some(&a));
+    }
+
+    #[test]
+    fn get_many_mut_missing() {
+        let mut map = HashMap::new();
+        assert!(map.get_many_mut([&1, &2]).is_none());
+        map.insert(1, 1);
+        assert!(map.get_many_mut([&1, &2]).is_none());
+        assert!(map.get_many_mut::<i32, 0>([]).is_some());
+    }
+
+    #[test]
+    fn get_many_mut_duplicate() {
+        let mut map = HashMap::new();
+        map.insert(1, 1);
+        map.insert(2, 2);
+        assert!(map.get_many_mut([&1, &2, &1]).is_none());
+        assert!(map.get_many_mut([&2, &2]).is_none());
+    }
+
+    #[test]
+    fn get_each() {
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), 1);
+        map.insert("bar".to_string(), 2);
+
+        assert_eq!(
+            map.get_each(&["bar", "baz", "foo", "bar"]),
+            vec![Some(&2), None, Some(&1), Some(&2)]
+        );
+        assert!(map.get_each::<str>(&[]).is_empty());
+        assert_eq!(HashMap::<String, i32>::new().get_each(&["foo"]), vec![None]);
+    }
+
+    #[test]
+    fn append() {
+        let mut a: HashMap<_, _> = (0..50).map(|i| (i, i)).collect();
+        let mut b: HashMap<_, _> = (50..100).map(|i| (i, i)).collect();
+        let nbuckets = b.table.buckets.len();
+
+        a.append(&mut b);
+        assert_eq!(a.len(), 100);
+        assert!(b.is_empty());
+        assert_eq!(b.table.buckets.len(), nbuckets);
+        for i in 0..100 {
+            assert_eq!(a.get(&i), Some(&i));
+        }
+    }
+
+    #[test]
+    fn append_overlapping() {
+        let mut a: HashMap<_, _> = (0..50).map(|i| (i, i)).collect();
+        let mut b: HashMap<_, _> = (25..75).map(|i| (i, i * 10)).collect();
+
+        a.append(&mut b);
+        assert_eq!(a.len(), 75);
+        assert!(b.is_empty());
+        assert_eq!(a.get(&10), Some(&10));
+        assert_eq!(a.get(&30), Some(&300));
+        assert_eq!(a.get(&70), Some(&700));
+    }
+
+    #[test]
+    fn append_empty() {
+        let mut a: HashMap<_, _> = (0..50).map(|i| (i, i)).collect();
+        let mut b = HashMap::new();
+        a.append(&mut b);
+        assert_eq!(a.len(), 50);
+
+        let mut c = HashMap::new();
+        c.append(&mut a);
+        assert_eq!(c.len(), 50);
+        assert!(a.is_empty());
+    }
+
+    #[test]
+    fn entry_and_modify() {
+        let mut map = HashMap::new();
+        let mut calls = 0;
+        map.entry("foo").and_modify(|_| calls += 1).or_insert(1);
+        assert_eq!(calls, 0);
+        assert_eq!(map.get(&"foo"), Some(&1));
+
+        map.entry("foo").and_modify(|v| { calls += 1; *v += 1 }).or_insert(1);
+        assert_eq!(calls, 1);
+        assert_eq!(map.get(&"foo"), Some(&2));
+    }
+
+    #[test]
+    fn entry_and_modify_word_count() {
+        let mut map = HashMap::new();
+        for word in "a b a c b a".split(' ') {
+            map.entry(word).and_modify(|c| *c += 1).or_insert(1);
+        }
+        assert_eq!(map.get(&"a"), Some(&3));
+        assert_eq!(map.get(&"b"), Some(&2));
+        assert_eq!(map.get(&"c"), Some(&1));
+    }
+
+    #[test]
+    fn entry_key() {
+        let mut map = HashMap::new();
+        assert_eq!(map.entry("foo").key(), &"foo");
+        map.insert("foo", 1);
+        assert_eq!(map.entry("foo").key(), &"foo");
+    }
+
+    #[test]
+    fn entry_key_stored() {
+        let mut map = HashMap::new();
+        map.insert(Tagged { name: "foo", tag: 1 }, 1);
+        assert_eq!(map.entry(Tagged { name: "foo", tag: 2 }).key().tag, 1);
+        assert_eq!(map.entry(Tagged { name: "bar", tag: 3 }).key().tag, 3);
+    }
+
+    #[test]
+    fn entry_or_insert_with_key() {
+        let mut map = HashMap::new();
+        assert_eq!(*map.entry("foo").or_insert_with_key(|key| key.len()), 3);
+
+        let mut called = false;
+        map.entry("foo").or_insert_with_key(|_| {
+            called = true;
+            0
+        });
+        assert!(!called);
+        assert_eq!(map.get(&"foo"), Some(&3));
+    }
+
+    #[test]
+    fn entry_insert() {
+        let mut map = HashMap::new();
+        map.entry("foo").insert(1);
+        assert_eq!(map.get(&"foo"), Some(&1));
+        map.entry("foo").insert(2);
+        assert_eq!(map.get(&"foo"), Some(&2));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn vacant_insert_entry() {
+        let mut map = HashMap::new();
+        match map.entry("foo") {
+            Entry::Vacant(e) => {
+                e.insert_entry(1);
+            },
+            Entry::Occupied(_) => unreachable!(),
+        }
+        assert_eq!(map.get(&"foo"), Some(&1));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn occupied_entry_accessors() {
+        let mut map = HashMap::new();
+        map.insert("foo", 1);
+
+        let value = match map.entry("foo") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.key(), &"foo");
+                assert_eq!(e.get(), &1);
+                *e.get_mut() += 1;
+                assert_eq!(e.get(), &2);
+                e.into_mut()
+            },
+            Entry::Vacant(_) => unreachable!(),
+        };
+        *value += 1;
+        assert_eq!(map.get(&"foo"), Some(&3));
+    }
+
+    #[test]
+    fn entry_insert_into_mut() {
+        let mut map = HashMap::new();
+        *map.entry("foo").insert(1).into_mut() += 10;
+        assert_eq!(map.get(&"foo"), Some(&11));
+        assert_eq!(map.entry("foo").insert(5).key(), &"foo");
+        assert_eq!(map.get(&"foo"), Some(&5));
+    }
+
+    #[test]
+    fn occupied_entry_insert() {
+        let mut map = HashMap::new();
+        map.insert("foo", 1);
+        match map.entry("foo") {
+            Entry::Occupied(mut e) => assert_eq!(e.insert(2), 1),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.get(&"foo"), Some(&2));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn occupied_entry_remove() {
+        let mut map = HashMap::new();
+        map.insert("foo", 1);
+        map.insert("bar", 2);
+        match map.entry("foo") {
+            Entry::Occupied(e) => assert_eq!(e.remove(), 1),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(&"foo"), None);
+        assert_eq!(map.get(&"bar"), Some(&2));
+    }
+
+    #[test]
+    fn occupied_entry_remove_entry() {
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), 1);
+        match map.entry("foo".to_string()) {
+            Entry::Occupied(e) => assert_eq!(e.remove_entry(), ("foo".to_string(), 1)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn occupied_entry_remove_entry_collisions() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        let chains = map.chains();
+        let bucket = (0..chains.len()).max_by_key(|&b| chains[b].len()).unwrap();
+        let colliding: Vec<_> = (0..100).filter(|i| map.bucket_for(i) == Some(bucket)).collect();
+        assert!(colliding.len() > 1);
+
+        let target = colliding[colliding.len() / 2];
+        match map.entry(target) {
+            Entry::Occupied(e) => assert_eq!(e.remove_entry(), (target, target)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.len(), 99);
+        for i in colliding {
+            assert_eq!(map.get(&i).is_some(), i != target);
+        }
+    }
+
+    #[test]
+    fn occupied_entry_replace_key() {
+        let mut map = HashMap::new();
+        map.insert(Tagged { name: "foo", tag: 1 }, 1);
+        match map.entry(Tagged { name: "foo", tag: 2 }) {
+            Entry::Occupied(e) => assert_eq!(e.replace_key().tag, 1),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        let (key, value) = map.get_key_value(&Tagged { name: "foo", tag: 0 }).unwrap();
+        assert_eq!(key.tag, 2);
+        assert_eq!(*value, 1);
+    }
+
+    #[test]
+    fn occupied_entry_replace_entry() {
+        let mut map = HashMap::new();
+        map.insert(Tagged { name: "foo", tag: 1 }, 1);
+        match map.entry(Tagged { name: "foo", tag: 2 }) {
+            Entry::Occupied(e) => {
+                let (key, value) = e.replace_entry(10);
+                assert_eq!(key.tag, 1);
+                assert_eq!(value, 1);
+            },
+            Entry::Vacant(_) => unreachable!(),
+        }
+        let (key, value) = map.get_key_value(&Tagged { name: "foo", tag: 0 }).unwrap();
+        assert_eq!(key.tag, 2);
+        assert_eq!(*value, 10);
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "replace_key")]
+    fn occupied_entry_replace_key_after_insert() {
+        let mut map = HashMap::new();
+        map.entry("foo").insert(1).replace_key();
+    }
+
+    #[test]
+    fn vacant_entry_into_key() {
+        let mut map = HashMap::with_capacity(16);
+        map.insert("foo".to_string(), 1);
+        let nbuckets = map.table.buckets.len();
+
+        let key = match map.entry("bar".to_string()) {
+            Entry::Vacant(e) => e.into_key(),
+            Entry::Occupied(_) => unreachable!(),
+        };
+        assert_eq!(key, "bar");
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.table.buckets.len(), nbuckets);
+        assert_eq!(map.iter().count(), 1);
+        assert!(!map.contains_key("bar"));
+    }
+
+    #[test]
+    fn vacant_entry_key() {
+        let mut map = HashMap::new();
+        for word in ["foo", "", "bar"] {
+            if let Entry::Vacant(e) = map.entry(word) {
+                if !e.key().is_empty() {
+                    e.insert(word.len());
+                }
+            }
+        }
+        assert_eq!(map.len(), 2);
+        assert!(!map.contains_key(&""));
+    }
+
+    #[test]
+    fn entry_debug() {
+        let mut map = HashMap::new();
+        assert_eq!(format!("{:?}", map.entry("foo")), "Entry(VacantEntry(\"foo\"))");
+        map.insert("foo", 1);
+        assert_eq!(
+            format!("{:?}", map.entry("foo")),
+            "Entry(OccupiedEntry { key: \"foo\", value: 1 })"
+        );
+    }
+
+    #[test]
+    fn entry_or_try_insert_with() {
+        let mut map = HashMap::new();
+        assert_eq!(map.entry("foo").or_try_insert_with(|| Ok::<_, ()>(1)), Ok(&mut 1));
+
+        let mut called = false;
+        let value = map.entry("foo").or_try_insert_with(|| {
+            called = true;
+            Err("unreachable")
+        });
+        assert_eq!(value, Ok(&mut 1));
+        assert!(!called);
+    }
+
+    #[test]
+    fn entry_or_try_insert_with_err() {
+        let mut map: HashMap<&str, i32> = HashMap::new();
+        assert_eq!(map.entry("foo").or_try_insert_with(|| Err("io")), Err("io"));
+        assert!(!map.contains_key(&"foo"));
+        assert_eq!(map.len(), 0);
+        assert_eq!(map.iter().count(), 0);
+    }
+
+    thread_local! {
+        static WORD_ALLOCS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    #[derive(PartialEq, Eq, Hash)]
+    struct Word(String);
+
+    impl Borrow<str> for Word {
+        fn borrow(&self) -> &str {
+            &self.0
+        }
+    }
+
+    impl From<&str> for Word {
+        fn from(word: &str) -> Self {
+            WORD_ALLOCS.with(|allocs| allocs.set(allocs.get() + 1));
+            Word(word.to_string())
+        }
+    }
+
+    #[test]
+    fn entry_ref_word_count() {
+        let mut map: HashMap<Word, usize> = HashMap::new();
+        let text = "the cat and the dog and the bird";
+        for word in text.split(' ') {
+            map.entry_ref(word).and_modify(|c| *c += 1).or_insert(1);
+        }
+        assert_eq!(WORD_ALLOCS.with(|allocs| allocs.get()), 5);
+        assert_eq!(map.len(), 5);
+        assert_eq!(map.get("the"), Some(&3));
+        assert_eq!(map.get("and"), Some(&2));
+        assert_eq!(map.get("dog"), Some(&1));
+    }
+
+    #[test]
+    fn entry_ref_string() {
+        let mut map: HashMap<String, usize> = HashMap::new();
+        *map.entry_ref("foo").or_insert_with(|| 1) += 1;
+        assert_eq!(map.get("foo"), Some(&2));
+        match map.entry_ref("bar") {
+            EntryRef::Vacant(e) => assert_eq!(e.key(), "bar"),
+            EntryRef::Occupied(_) => unreachable!(),
+        }
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn raw_entry_from_key() {
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), 1);
+        assert_eq!(map.raw_entry().from_key("foo"), Some((&"foo".to_string(), &1)));
+        assert_eq!(map.raw_entry().from_key("bar"), None);
+        assert_eq!(HashMap::<String, i32>::new().raw_entry().from_key("foo"), None);
+    }
+
+    #[test]
+    fn raw_entry_from_hash() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(Tagged { name: Box::leak(i.to_string().into_boxed_str()), tag: i }, i);
+        }
+
+        let hash = map.hasher().hash_one("42");
+        let (key, _) = map
+            .raw_entry()
+            .from_key_hashed_nocheck(hash, &Tagged { name: "42", tag: 0 })
+            .unwrap();
+        assert_eq!(key.tag, 42);
+        let (key, value) = map.raw_entry().from_hash(hash, |key| key.name == "42").unwrap();
+        assert_eq!(key.tag, 42);
+        assert_eq!(*value, 42);
+        assert!(map.raw_entry().from_hash(hash, |key| key.name == "43").is_none());
+    }
+
+    #[test]
+    fn raw_entry_mut_vacant() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            let key = i.to_string();
+            let hash = map.hasher().hash_one(key.as_str());
+            match map.raw_entry_mut().from_hash(hash, |k: &String| *k == key) {
+                RawEntryMut::Vacant(e) => {
+                    e.insert_hashed_nocheck(hash, key, i);
+                },
+                RawEntryMut::Occupied(_) => unreachable!(),
+            }
+        }
+        assert_eq!(map.len(), 100);
+        for i in 0..100 {
+            assert_eq!(map.get(i.to_string().as_str()), Some(&i));
+        }
+
+        match map.raw_entry_mut().from_key("100") {
+            RawEntryMut::Vacant(e) => {
+                let (_, value) = e.insert("100".to_string(), 0);
+                *value = 100;
+            },
+            RawEntryMut::Occupied(_) => unreachable!(),
+        }
+        assert_eq!(map.get("100"), Some(&100));
+    }
+
+    #[test]
+    fn raw_entry_mut_occupied() {
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), 1);
+        map.insert("bar".to_string(), 2);
+
+        match map.raw_entry_mut().from_key("foo") {
+            RawEntryMut::Occupied(mut e) => {
+                let (key, value) = e.get_key_value_mut();
+                assert_eq!(key, "foo");
+                *value += 10;
+                assert_eq!(e.insert(20), 11);
+            },
+            RawEntryMut::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.get("foo"), Some(&20));
+
+        let hash = map.hasher().hash_one("bar");
+        match map.raw_entry_mut().from_key_hashed_nocheck(hash, "bar") {
+            RawEntryMut::Occupied(e) => assert_eq!(e.remove(), 2),
+            RawEntryMut::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map.len(), 1);
+        assert!(!map.contains_key("bar"));
+    }
+
+    #[test]
+    fn clone() {
+        let mut map = HashMap::new();
+        for i in 0..100 {
+            map.insert(i, i.to_string());
+        }
+
+        let copy = map.clone();
+        assert_eq!(copy.len(), map.len());
+        map.insert(0, "zero".to_string());
+        map.remove(&1);
+        map.insert(100, "100".to_string());
+
+        assert_eq!(copy.len(), 100);
+        for i in 0..100 {
+            assert_eq!(copy.get(&i), Some(&i.to_string()));
+        }
+        assert_eq!(copy.get(&100), None);
+    }
+
+    #[test]
+    fn clone_from() {
+        let source: HashMap<_, _> = (0..100).map(|i| (i, i.to_string())).collect();
+
+        let mut scratch = HashMap::with_capacity(1000);
+        for i in 0..1000 {
+            scratch.insert(i + 1000, String::new());
+        }
+        assert!(scratch.table.buckets.len() > source.table.buckets.len());
+        let arrays = (scratch.entries.as_ptr(), scratch.table.next.as_ptr(), scratch.table.buckets.as_ptr());
+
+        scratch.clone_from(&source);
+        assert_eq!((scratch.entries.as_ptr(), scratch.table.next.as_ptr(), scratch.table.buckets.as_ptr()), arrays);
+
+        assert_eq!(scratch.len(), 100);
+        for i in 0..100 {
+            assert_eq!(scratch.get(&i), Some(&i.to_string()));
+        }
+        assert_eq!(scratch.get(&1000), None);
+    }
+
+    #[test]
+    fn debug() {
+        let map: HashMap<&str, i32> = HashMap::new();
+        assert_eq!(format!("{:?}", map), "{}");
+
+        let map = HashMap::from([("a", 1), ("b", 2)]);
+        let debug = format!("{:?}", map);
+        assert!(debug == r#"{"a": 1, "b": 2}"# || debug == r#"{"b": 2, "a": 1}"#);
+    }
+
+    #[test]
+    fn eq_insertion_order() {
+        let a: HashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+        let mut b = HashMap::with_capacity(1000);
+        for i in (0..100).rev() {
+            b.insert(i, i);
+        }
+        assert_ne!(a.table.buckets.len(), b.table.buckets.len());
+        assert_eq!(a, b);
+        assert_eq!(HashMap::<i32, i32>::new(), HashMap::new());
+    }
+
+    #[test]
+    fn ne() {
+        let a: HashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+        let b: HashMap<_, _> = (0..99).map(|i| (i, i)).collect();
+        assert_ne!(a, b);
+        assert_ne!(b, a);
+
+        let mut c = a.clone();
+        c.insert(50, 0);
+        assert_ne!(a, c);
+    }
+
+    #[test]
+    fn default() {
+        #[derive(Default)]
+        struct Registry {
+            names: HashMap<String, u32>,
+        }
+
+        let mut registry = Registry::default();
+        assert!(registry.names.is_empty());
+        registry.names.insert("foo".to_string(), 1);
+        *registry.names.entry("bar".to_string()).or_insert_default() += 2;
+        assert_eq!(registry.names.get("foo"), Some(&1));
+        assert_eq!(registry.names.get("bar"), Some(&2));
+    }
+
+    #[test]
+    fn extend() {
+        let mut map: HashMap<_, _> = (0..50).map(|i| (i, i)).collect();
+        map.extend((25..75).map(|i| (i, i * 10)));
+        assert_eq!(map.len(), 75);
+        assert_eq!(map.get(&10), Some(&10));
+        assert_eq!(map.get(&30), Some(&300));
+
+        map.extend(vec![(0, 1), (0, 2)]);
+        assert_eq!(map.get(&0), Some(&2));
+        assert_eq!(map.len(), 75);
+    }
+
+    #[test]
+    fn extend_empty() {
+        let mut map = HashMap::new();
+        map.extend((0..100_000).map(|i| (i, i)));
+        let nbuckets = map.table.buckets.len();
+        assert_eq!(nbuckets, buckets_for(100_000, DEFAULT_LOAD_FACTOR).unwrap());
+        assert_eq!(map.len(), 100_000);
+
+        map.extend(std::iter::empty::<(i32, i32)>());
+        assert_eq!(map.len(), 100_000);
+        assert_eq!(map.table.buckets.len(), nbuckets);
+    }
+
+    #[test]
+    fn extend_copied() {
+        let mut a: HashMap<u32, u64> = (0..50).map(|i| (i, i as u64)).collect();
+        let b: HashMap<u32, u64> = (25..75).map(|i| (i, i as u64 * 10)).collect();
+        a.extend(&b);
+        assert_eq!(a.len(), 75);
+        assert_eq!(a.get(&10), Some(&10));
+        assert_eq!(a.get(&30), Some(&300));
+        assert_eq!(a.get(&74), Some(&740));
+        assert_eq!(b.len(), 50);
+    }
+
+    /// Reports its length exactly, like a `TrustedLen` iterator would.
+    struct Exact<I>(I, usize);
+
+    impl<I: Iterator> Iterator for Exact<I> {
+        type Item = I::Item;
+
+        fn next(&mut self) -> Option<I::Item> {
+            let item = self.0.next()?;
+            self.1 -= 1;
+            Some(item)
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.1, Some(self.1))
+        }
+    }
+
+    /// Claims a lower bound it has no intention of meeting.
+    struct Liar<I>(I, usize);
+
+    impl<I: Iterator> Iterator for Liar<I> {
+        type Item = I::Item;
+
+        fn next(&mut self) -> Option<I::Item> {
+            self.0.next()
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.1, None)
+        }
+    }
+
+    #[test]
+    fn from_iter_presizes_from_hint() {
+        let n = 100_000;
+        let map: HashMap<usize, usize> = Exact((0..n).map(|i| (i, i)), n).collect();
+        assert_eq!(map.len(), n);
+        // growing would have doubled past the pre-sized count
+        assert_eq!(map.bucket_count(), buckets_for(n, DEFAULT_LOAD_FACTOR).unwrap());
+        assert!(!map.is_rehashing());
+        check_arena(&map);
+
+        let pairs: Vec<_> = (0..n).map(|i| (i, i)).collect();
+        let map: HashMap<_, _> = pairs.into_iter().collect();
+        assert_eq!(map.bucket_count(), buckets_for(n, DEFAULT_LOAD_FACTOR).unwrap());
+    }
+
+    #[test]
+    fn from_iter_survives_lying_hints() {
+        // a hint no allocator could satisfy is ignored
+        let map: HashMap<u32, u32> = Liar((0..10).map(|i| (i, i)), usize::MAX).collect();
+        assert_eq!(map.len(), 10);
+        check_arena(&map);
+
+        // an overstated hint doesn't leave the table oversized
+        let map: HashMap<u32, u32> = Liar((0..10).map(|i| (i, i)), 100_000).collect();
+        assert_eq!(map.len(), 10);
+        assert!(map.capacity() < 100);
+        check_arena(&map);
+
+        // an understated one just grows as usual
+        let map: HashMap<u32, u32> = Liar((0..5000).map(|i| (i, i)), 1).collect();
+        assert_eq!(map.len(), 5000);
+        for i in 0..5000 {
+            assert_eq!(map[&i], i);
+        }
+    }
+
+    #[test]
+    fn index() {
+        let mut map = HashMap::new();
+        map.insert("foo".to_string(), 42);
+        assert_eq!(map["foo"], 42);
+        assert_eq!(map[&"foo".to_string()], 42);
+    }
+
+    #[test]
+    #[should_panic(expected = "HashMap::index")]
+    fn index_missing() {
+        let mut map = HashMap::new();
+        map.insert("foo", 42);
+        let _ = map[&"bar"];
+    }
+
+    #[test]
+    fn from_array() {
+        let map = HashMap::from([("a", 1), ("b", 2), ("c", 3)]);
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get(&"b"), Some(&2));
+        assert_eq!(map.table.buckets.len(), buckets_for(3, DEFAULT_LOAD_FACTOR).unwrap());
+
+        let map: HashMap<&str, i32> = HashMap::from([]);
+        assert!(map.is_empty());
+        assert_eq!(map.table.buckets.len(), 0);
+    }
+
+    #[test]
+    fn from_array_duplicates() {
+        let map = HashMap::from([("a", 1), ("b", 2), ("a", 3)]);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get(&"a"), Some(&3));
+    }
+
+    #[test]
+    fn std_round_trip() {
+        let mut map: HashMap<_, _> = (0..1000).map(|i| (i, i.to_string())).collect();
+        for i in (0..1000).step_by(3) {
+            map.remove(&i);
+        }
 
-impl<K, V> HashMap<K ,V> 
-{
-    pub fn new() -> Self {
-        HashMap {
-            buckets: Vec::new(),
-            items: 0,
+        let std_map: std::collections::HashMap<_, _> = map.clone().into();
+        assert_eq!(std_map.len(), map.len());
+        for (key, value) in &map {
+            assert_eq!(std_map.get(key), Some(value));
         }
+
+        let back = HashMap::from(std_map);
+        assert_eq!(back, map);
+        assert_eq!(HashMap::from_std(back.clone().into_std()), map);
     }
-}
 
-pub struct OccupiedEntry<'a, K: 'a, V: 'a> {
-    entry: &'a mut (K, V),
-}
-pub struct VacantEntry<'a, K: 'a, V: 'a> {
-    key: K,
-    map: &'a mut HashMap<K, V>,
-    bucket: usize,
-}
+    #[test]
+    fn eq_std() {
+        let map: HashMap<_, _> = (0..100).map(|i| (i, i)).collect();
+        let std_map: std::collections::HashMap<_, _> = (0..100).rev().map(|i| (i, i)).collect();
+        assert_eq!(map, std_map);
+        assert_eq!(std_map, map);
 
-pub enum Entry<'a, K: 'a, V: 'a> {
-    Occupied(OccupiedEntry<'a, K, V>),
-    Vacant(VacantEntry<'a, K, V>),
-}
+        assert_eq!(HashMap::<i32, i32>::new(), std::collections::HashMap::new());
+        assert_eq!(std::collections::HashMap::new(), HashMap::<i32, i32>::new());
 
-impl<'a, K: 'a, V: 'a> VacantEntry<'a, K, V> {
-    pub fn insert(self, value: V) -> &'a mut V 
-    {
-        self.map.buckets[self.bucket].push((self.key, value));
-        self.map.items += 1;
-        &mut self.map.buckets[self.bucket].last_mut().unwrap().1
+        let mut different = std_map.clone();
+        different.insert(0, 1);
+        assert_ne!(map, different);
+        assert_ne!(different, map);
+
+        let subset: std::collections::HashMap<_, _> = (0..50).map(|i| (i, i)).collect();
+        assert_ne!(map, subset);
+        assert_ne!(subset, map);
     }
-}
 
-impl<'a, K: 'a, V: 'a> Entry<'a, K, V> {
-    pub fn or_insert(self, value: V) -> &'a mut V {
-        match self {
-            Entry::Occupied(e) => &mut e.entry.1,
-            Entry::Vacant(e) => {
-                e.insert(value)
-            }
+    #[derive(Clone, Debug, PartialEq)]
+    struct SeededState(u64);
+
+    impl BuildHasher for SeededState {
+        type Hasher = DefaultHasher;
+
+        fn build_hasher(&self) -> DefaultHasher {
+            let mut hasher = DefaultHasher::new();
+            hasher.write_u64(self.0);
+            hasher
         }
     }
 
-    pub fn or_insert_with<F>(self, maker: F) -> &'a mut V 
-    where
-        F: FnOnce() -> V
-    {
-        match self {
-            Entry::Occupied(e) => &mut e.entry.1,
-            Entry::Vacant(e) => {
-                e.insert(maker())
-            }
+    /// Keys in each bucket, in chain order.
+    fn layout<S>(map: &HashMap<u32, u32, S>) -> Vec<Vec<u32>> {
+        map.chains()
+            .iter()
+            .map(|chain| chain.iter().map(|(_, k, _)| *k).collect())
+            .collect()
+    }
+
+    #[test]
+    fn custom_hasher_places_buckets() {
+        let mut map = HashMap::with_hasher(Colliding::default());
+        for i in 0..100 {
+            map.insert(i, i * 2);
         }
+        assert!(map.table.buckets.len() > 1);
+        let chains = map.chains();
+        assert_eq!(chains[0].len(), 100);
+        assert!(chains[1..].iter().all(|chain| chain.is_empty()));
+
+        assert_eq!(map.get(&42), Some(&84));
+        *map.entry(7).or_insert(0) += 1;
+        assert_eq!(map[&7], 15);
+        assert_eq!(map.remove(&7), Some(15));
+        assert_eq!(map.len(), 99);
+
+        map.reserve(1000);
+        assert_eq!(map.chains()[0].len(), 99);
+        assert_eq!(map.raw_entry().from_key(&41), Some((&41, &82)));
     }
 
-    pub fn or_insert_default(self) -> &'a mut V
-    where
-        V: Default,
-    {
-        self.or_insert_with(Default::default)
-        // match self {
-        //     Entry::Occupied(e) => e.entry.1,
-        //     Entry::Vacant(e) => e.insert(V::default())
-        // }
+    #[test]
+    fn custom_hasher_collect_and_clone() {
+        let map: HashMap<i32, i32, Colliding> = (0..10).map(|i| (i, i)).collect();
+        assert_eq!(map.chains()[0].len(), 10);
+        let mut copy: HashMap<i32, i32, Colliding> = HashMap::default();
+        copy.clone_from(&map);
+        assert_eq!(copy, map);
+        assert_eq!(copy, (0..10).map(|i| (i, i)).collect::<HashMap<_, _>>().into_std());
     }
 
-}
+    #[test]
+    fn with_capacity_and_hasher() {
+        let mut map = HashMap::with_capacity_and_hasher(100, SeededState(7));
+        assert_eq!(map.hasher(), &SeededState(7));
+        let nbuckets = map.table.buckets.len();
+        assert!(map.capacity() >= 100);
+        for i in 0..100 {
+            map.insert(i, i);
+            assert_eq!(map.table.buckets.len(), nbuckets);
+        }
 
-impl<K, V> HashMap<K ,V> 
-where 
-    K: Hash + Eq
-{
-    fn bucket<Q>(&self, key: &Q) -> usize 
-    where 
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
-    {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        (hasher.finish() % self.buckets.len() as u64) as usize
+        let sibling: HashMap<i32, i32, _> = HashMap::with_hasher(map.hasher().clone());
+        assert_eq!(sibling.hasher(), &SeededState(7));
+        assert_eq!(HashMap::<i32, i32, _>::with_capacity_and_hasher(0, SeededState(1)).capacity(), 0);
     }
 
-    pub fn entry(&mut self, key: K) -> Entry<K, V> {
-        if self.buckets.is_empty() || self.items > 3 * self.buckets.len() / 4 {
-            self.resize();
+    #[test]
+    fn random_state_per_map() {
+        let first: HashMap<u32, u32> = (0..64).map(|i| (i, i)).collect();
+        // Two fresh maps sharing a layout is possible by chance, but all of
+        // them matching the first one would mean the seed isn't per map.
+        let mut differs = false;
+        for _ in 0..8 {
+            let other: HashMap<u32, u32> = (0..64).map(|i| (i, i)).collect();
+            assert_eq!(other, first);
+            differs |= layout(&other) != layout(&first);
         }
+        assert!(differs);
+    }
 
-        let bucket = self.bucket(&key);
-        
-        for entry in &mut self.buckets[bucket] {
-            if entry.0 == key {
-                return Entry::Occupied(OccupiedEntry {
-                    entry: unsafe {
-                        &mut *(entry as *mut _)
-                    }
-                })
-            }
-        }
+    #[test]
+    fn rehash_with_new_hasher() {
+        // everything starts out in one bucket
+        let map: HashMap<u32, u32, Colliding> = (0..500).map(|i| (i, i * 2)).collect();
+        let before = layout(&map);
+        let moved = map.rehash_with(SeededState(99));
+        assert_eq!(moved.hasher(), &SeededState(99));
+        assert_eq!(moved.len(), 500);
+        assert!((0..500).all(|i| moved.get(&i) == Some(&(i * 2))));
+        assert_ne!(layout(&moved), before);
 
-        Entry::Vacant(VacantEntry {
-            key,
-            map: self,
-            bucket
-        })
+        let empty: HashMap<u32, u32> = HashMap::new();
+        assert!(empty.rehash_with(SeededState(1)).is_empty());
     }
 
-    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        if self.buckets.is_empty() || self.items > 3 * self.buckets.len() / 4 {
-            self.resize();
+    #[test]
+    fn set_hasher_in_place() {
+        let mut map = HashMap::with_hasher(SeededState(1));
+        for i in 0..500 {
+            map.insert(i, i * 2);
         }
+        let before = layout(&map);
+        let nbuckets = map.table.buckets.len();
+        map.set_hasher(SeededState(2));
+        assert_eq!(map.hasher(), &SeededState(2));
+        assert_eq!(map.table.buckets.len(), nbuckets);
+        assert_eq!(map.len(), 500);
+        assert!((0..500).all(|i| map.get(&i) == Some(&(i * 2))));
+        assert_ne!(layout(&map), before);
+        map.insert(500, 1000);
+        assert_eq!(map.remove(&7), Some(14));
 
-        let bucket = self.bucket(&key);
-        let bucket = &mut self.buckets[bucket];
-        
-        self.items += 1;
-        for &mut (ref ekey, ref mut evalue) in bucket.iter_mut() {
-            if ekey == &key {
-                return Some(mem::replace(evalue, value));
+        let mut empty: HashMap<u32, u32, _> = HashMap::with_hasher(SeededState(1));
+        empty.set_hasher(SeededState(3));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn bucket_for_matches_storage() {
+        let mut map = HashMap::new();
+        assert_eq!(map.bucket_count(), 0);
+        assert_eq!(map.bucket_for("a"), None);
+        for i in 0..300 {
+            map.insert(i.to_string(), i);
+        }
+        assert_eq!(map.bucket_count(), map.table.buckets.len());
+        for (bucket, chain) in map.chains().into_iter().enumerate() {
+            for (hash, key, _) in chain {
+                assert_eq!(map.bucket_for(key.as_str()), Some(bucket));
+                assert_eq!(map.hash_one(key.as_str()), *hash);
             }
         }
-        bucket.push((key,value));
-        None
+        assert!(map.bucket_for("missing").unwrap() < map.bucket_count());
+        assert_eq!(map.hash_one("7"), map.hasher().hash_one("7"));
     }
 
-    pub fn get<Q>(&self, key: &Q) -> Option<&V> 
-    where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized,
-    {
-        let bucket = self.bucket(key);
-        self.buckets[bucket]
-            .iter()
-            .find(|&(ref ekey, _)| ekey.borrow() == key)
-            .map(|&(_, ref evalue)| evalue)
+    #[test]
+    fn sequential_and_strided_keys_spread() {
+        // keys that only differ in high bits would share a bucket if the
+        // mask were applied to the raw hash
+        let mut ints: HashMap<u64, (), crate::BuildNoHashHasher<u64>> = HashMap::default();
+        for i in 0..4096 {
+            ints.insert(i << 20, ());
+        }
+        let longest = ints.chains().iter().map(Vec::len).max().unwrap();
+        assert!(longest < 16, "longest chain {}", longest);
+
+        let mut map = HashMap::new();
+        for i in 0..100_000u64 {
+            map.insert(i, i);
+        }
+        map.finish_rehash();
+        let longest = map.chains().iter().map(Vec::len).max().unwrap();
+        assert!(longest < 16, "longest chain {}", longest);
     }
 
-    pub fn contains_key<Q>(&self, key: &Q) -> bool 
-    where 
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized
-    {
-        self.get(key).is_some()
+    #[test]
+    fn cached_hashes_with_colliding_keys() {
+        // every key hashes to 0, so probes always fall through to `Eq`
+        let mut map = HashMap::with_hasher(Colliding::default());
+        for i in 0..50 {
+            assert_eq!(map.insert(i, i), None);
+        }
+        assert_eq!(map.insert(7, 70), Some(7));
+        assert_eq!(map.get(&7), Some(&70));
+        assert_eq!(map.get(&50), None);
+        assert_eq!(map.remove(&8), Some(8));
+        assert!(!map.contains_key(&8));
+        match map.entry(9) {
+            Entry::Occupied(e) => assert_eq!(e.get(), &9),
+            Entry::Vacant(_) => panic!("9 should be present"),
+        }
+        *map.entry(8).or_insert(0) += 80;
+        assert_eq!(map[&8], 80);
+        assert!(map.entries.iter().all(|&(hash, _, _)| hash == 0));
+        assert_eq!(map.len(), 50);
     }
 
-    pub fn remove<Q>(&mut self, key: &Q) -> Option<V> 
-    where
-        K: Borrow<Q>,
-        Q: Hash + Eq + ?Sized
-    {
-        let bucket = self.bucket(key);
-        let bucket = &mut self.buckets[bucket];
-        let index = bucket
-            .iter()
-            .position(|&(ref ekey, _)| ekey.borrow() == key)?;
-        self.items -= 1;
-        Some(bucket.swap_remove(index).1) // (key, value).1
+    #[derive(Clone, PartialEq, Eq)]
+    struct Counted(u32);
+
+    thread_local! {
+        static HASHED: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
     }
 
-    pub fn len(&self) -> usize {
-        self.items
+    impl Hash for Counted {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            HASHED.with(|n| n.set(n.get() + 1));
+            self.0.hash(state);
+        }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.items == 0
+    #[test]
+    fn resize_reuses_cached_hashes() {
+        let mut map = HashMap::new();
+        HASHED.with(|n| n.set(0));
+        for i in 0..1000 {
+            map.insert(Counted(i), i);
+        }
+        // one hash per insert, none for the resizes along the way
+        assert_eq!(HASHED.with(|n| n.get()), 1000);
+        map.reserve(10_000);
+        map.shrink_to_fit();
+        assert_eq!(HASHED.with(|n| n.get()), 1000);
+        assert!((0..1000).all(|i| map[&Counted(i)] == i));
     }
 
-    fn resize(&mut self) {
-        let target_size = match self.buckets.len() {
-            0 => INITIAL_NBUCKETS,
-            n => 2*n
-        };
+    #[test]
+    fn prehashed_insert_get_remove() {
+        let mut map: HashMap<String, u32> = HashMap::new();
+        for i in 0..200 {
+            let key = i.to_string();
+            let hash = map.hasher().hash_one(&key);
+            assert_eq!(map.insert_hashed(hash, key, i), None);
+        }
+        let hash = map.hasher().hash_one("7");
+        assert_eq!(map.insert_hashed(hash, "7".to_string(), 70), Some(7));
+        assert_eq!(map.get_hashed(hash, "7"), Some(&70));
+        assert_eq!(map.get_hashed(map.hasher().hash_one("200"), "200"), None);
 
-        let mut new_buckets = Vec::with_capacity(target_size);
-        new_buckets.extend((0..target_size).map(|_| Vec::new()));
+        // hashes agree with the map's hasher, so both APIs see the same entries
+        assert_eq!(map.get("7"), Some(&70));
+        map.insert("300".to_string(), 300);
+        assert_eq!(map.get_hashed(map.hasher().hash_one("300"), "300"), Some(&300));
+        assert_eq!(map.remove_hashed(map.hasher().hash_one("5"), "5"), Some(5));
+        assert!(!map.contains_key("5"));
+        assert_eq!(map.remove("6"), Some(6));
+        assert_eq!(map.get_hashed(map.hasher().hash_one("6"), "6"), None);
+        assert_eq!(map.len(), 199);
+    }
 
-        for (key, value) in self.buckets.iter_mut().flat_map(|bucket| bucket.drain(..)) {
-            let mut hasher = DefaultHasher::new();
-            key.hash(&mut hasher);
-            let bucket = (hasher.finish() % new_buckets.len() as u64) as usize;
-            new_buckets[bucket].push((key, value));
+    #[test]
+    fn prehashed_with_inconsistent_hash() {
+        let mut map = HashMap::new();
+        map.insert_hashed(12345, 1u64, "bogus");
+        map.insert(2, "plain");
+        assert_eq!(map.get_hashed(12345, &1), Some(&"bogus"));
+        // the wrong hash still has to agree with `Eq`
+        assert_eq!(map.get_hashed(12345, &2), None);
+        for i in 3..100 {
+            map.insert(i, "filler");
         }
+        assert_eq!(map.get_hashed(12345, &1), Some(&"bogus"));
+        assert_eq!(map.get(&2), Some(&"plain"));
+        assert_eq!(map.remove_hashed(12345, &1), Some("bogus"));
+        assert_eq!(map.len(), 98);
+    }
 
-        mem::replace(&mut self.buckets, new_buckets);
+    #[test]
+    fn many_gets() {
+        let map: HashMap<u64, u64> = (0..200_000).map(|i| (i, i ^ 0x5555)).collect();
+        let mut sum = 0u64;
+        for round in 0..5 {
+            for i in 0..200_000 {
+                sum = sum.wrapping_add(map[&i] + round);
+            }
+        }
+        let expected = (0..5u64)
+            .flat_map(|round| (0..200_000u64).map(move |i| (i ^ 0x5555) + round))
+            .fold(0u64, u64::wrapping_add);
+        assert_eq!(sum, expected);
     }
-}
 
-pub struct Iter<'a, K, V> {
-    map: &'a HashMap<K, V>,
-    bucket: usize,
-    at: usize
-}
+    /// Every entry is reachable exactly once, from a bucket its hash
+    /// selects, including old buckets not migrated yet mid-rehash.
+    fn check_arena<K, V, S>(map: &HashMap<K, V, S>) {
+        assert_eq!(map.table.next.len(), map.entries.len());
+        let mut seen = vec![false; map.entries.len()];
+        for bucket in 0..map.table.buckets.len() {
+            for index in map.chain(bucket) {
+                assert!(!mem::replace(&mut seen[index], true), "entry {} linked twice", index);
+                assert!(map.hash_buckets(map.entries[index].0).contains(&bucket));
+            }
+        }
+        for (bucket, &head) in map.table.old_buckets.iter().enumerate() {
+            assert!(bucket >= map.table.migrated || head == NONE);
+            for index in (Chain { next: &map.table.next, at: head }) {
+                assert!(!mem::replace(&mut seen[index], true), "entry {} linked twice", index);
+                let hash = map.entries[index].0;
+                assert!(candidate_buckets(hash, map.table.old_buckets.len(), map.table.two_choice).contains(&bucket));
+            }
+        }
+        assert!(seen.into_iter().all(|linked| linked));
+    }
 
-impl<'a, K, V> Iterator for Iter<'a, K, V> {
-    type Item = (&'a K, &'a V);
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            match self.map.buckets.get(self.bucket) {
-                Some(bucket) => {
-                    match bucket.get(self.at) {
-                        Some(&(ref ekey, ref evalue)) => {
-                            self.at += 1;
-                            break Some((ekey, evalue))
-                        },
-                        None => {
-                            self.bucket += 1;
-                            self.at = 0;
-                            // return self.next();
-                            continue;
-                        }
-                    }
-                },
-                None => break None,
+    #[test]
+    fn arena_remove_during_collisions() {
+        let mut map = HashMap::with_hasher(Colliding::default());
+        for i in 0..64 {
+            map.insert(i, i * 10);
+        }
+        // remove from the head, the tail and the middle of the one chain
+        for i in [63, 0, 31, 32, 1, 62] {
+            assert_eq!(map.remove(&i), Some(i * 10));
+            check_arena(&map);
+            assert_eq!(map.get(&i), None);
+        }
+        for i in (2..62).filter(|&i| i != 31 && i != 32) {
+            assert_eq!(map.get(&i), Some(&(i * 10)));
+        }
+        map.set_auto_shrink(false);
+        for i in 2..62 {
+            map.remove(&i);
+            check_arena(&map);
+        }
+        assert!(map.is_empty());
+        assert!(map.chains().iter().all(Vec::is_empty));
+    }
+
+    #[test]
+    fn arena_swap_remove_fixes_links() {
+        let mut map = HashMap::with_hasher(Colliding::default());
+        for i in 0..8 {
+            map.insert(i, i);
+        }
+        // 7 sits last in the arena, so removing 0 moves it into slot 0
+        assert_eq!(map.entries.last().map(|e| e.1), Some(7));
+        assert_eq!(map.remove(&0), Some(0));
+        assert_eq!(map.entries[0].1, 7);
+        check_arena(&map);
+        assert_eq!(map.get(&7), Some(&7));
+        *map.get_mut(&7).unwrap() += 100;
+        assert_eq!(map[&7], 107);
+
+        // removing the last entry moves nothing
+        let last = map.entries.last().unwrap().1;
+        assert_eq!(map.remove(&last), Some(last));
+        check_arena(&map);
+        assert_eq!(map.len(), 6);
+    }
+
+    #[test]
+    fn arena_growth() {
+        let mut map = HashMap::new();
+        for i in 0..5000u32 {
+            map.insert(i, i);
+            if i.is_power_of_two() {
+                check_arena(&map);
             }
         }
+        check_arena(&map);
+        map.shrink_to_fit();
+        check_arena(&map);
+        map.reserve(10_000);
+        check_arena(&map);
+        assert!((0..5000).all(|i| map[&i] == i));
+
+        let mut colliding = HashMap::with_hasher(Colliding::default());
+        colliding.extend((0..500).map(|i| (i, i)));
+        check_arena(&colliding);
+        assert_eq!(colliding.chains()[0].len(), 500);
     }
-}
 
-impl<'a, K, V> IntoIterator for &'a HashMap<K, V> {
-    type Item = (&'a K, &'a V);
-    type IntoIter = Iter<'a, K, V>;
-    fn into_iter(self) -> Self::IntoIter {
-        Iter{
-            map: self,
-            bucket: 0,
-            at: 0
+    #[test]
+    fn arena_entry_api() {
+        let mut map = HashMap::with_hasher(Colliding::default());
+        for i in 0..20 {
+            *map.entry(i % 10).or_insert(0) += 1;
+        }
+        check_arena(&map);
+        assert!((0..10).all(|i| map[&i] == 2));
+
+        match map.entry(3) {
+            Entry::Occupied(e) => assert_eq!(e.remove_entry(), (3, 2)),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        check_arena(&map);
+        match map.entry(3) {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(e) => *e.insert(30) += 1,
         }
+        check_arena(&map);
+        assert_eq!(map[&3], 31);
+        // the moved entry is still reachable through an occupied entry
+        match map.entry(9) {
+            Entry::Occupied(mut e) => *e.get_mut() = 90,
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert_eq!(map[&9], 90);
+
+        let mut strings: HashMap<String, u32> = HashMap::new();
+        *strings.entry_ref("a").or_insert(0) += 1;
+        *strings.entry_ref("a").or_insert(0) += 1;
+        assert_eq!(strings["a"], 2);
+        check_arena(&strings);
     }
-}
 
-use std::iter::FromIterator;
-impl<K, V> FromIterator<(K, V)> for HashMap<K, V>
-where 
-    K: Hash + Eq
-{
-    fn from_iter<T>(iter: T) -> Self 
-    where
-        T: IntoIterator<Item = (K, V)>
-    {
+    #[test]
+    fn arena_iteration_order() {
         let mut map = HashMap::new();
-        for (k, v) in iter {
-            map.insert(k ,v);
+        for i in 0..100 {
+            map.insert(i, i);
         }
-        map
+        map.remove(&10);
+        map.retain(|&k, _| k % 7 != 0);
+        check_arena(&map);
+        let arena: Vec<_> = map.entries.iter().map(|&(_, k, v)| (k, v)).collect();
+        assert_eq!(map.iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>(), arena);
+        assert_eq!(map.clone().into_iter().collect::<Vec<_>>(), arena);
+        let extracted: Vec<_> = map.extract_if(|&k, _| k % 2 == 0).collect();
+        assert!(extracted.iter().all(|&(k, _)| k % 2 == 0));
+        check_arena(&map);
+        assert!(map.keys().all(|k| k % 2 == 1));
+        assert_eq!(map.drain().count(), arena.len() - extracted.len());
+        check_arena(&map);
+        assert!(map.is_empty());
     }
-}
 
-impl<K, V, const N: usize> From<[(K, V); N]> for HashMap<K, V>
-where 
-    K: Hash + Eq
-{
-    fn from(arr: [(K, V); N]) -> Self {
-        // arr.into_iter().collect()
-        std::array::IntoIter::new(arr).collect()
+    fn growth_points(load_factor: f32) -> Vec<usize> {
+        let mut map = HashMap::with_capacity_and_load_factor(0, load_factor);
+        let mut points = Vec::new();
+        for i in 0..5000 {
+            let nbuckets = map.table.buckets.len();
+            map.insert(i, i);
+            if map.table.buckets.len() != nbuckets {
+                points.push(i);
+            }
+            assert!(map.len() <= map.table.buckets.len().max(1) * 95 / 100 + 1);
+        }
+        points
     }
-}
 
+    #[test]
+    fn load_factor_controls_growth() {
+        let sparse = growth_points(0.5);
+        let dense = growth_points(0.9);
+        assert!(sparse.len() > dense.len());
+        // the sparse map reaches every bucket count earlier
+        assert!(sparse.iter().zip(&dense).skip(3).all(|(s, d)| s < d));
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let sparse: HashMap<u32, u32> = HashMap::with_capacity_and_load_factor(1500, 0.5);
+        let dense: HashMap<u32, u32> = HashMap::with_capacity_and_load_factor(1500, 0.9);
+        assert!(sparse.capacity() >= 1500 && dense.capacity() >= 1500);
+        assert!(sparse.table.buckets.len() > dense.table.buckets.len());
+        assert_eq!(dense.load_factor(), 0.9);
+    }
 
     #[test]
-    fn insert_get() {
+    fn set_load_factor() {
         let mut map = HashMap::new();
-        map.insert("foo", 42);
-        assert_eq!(map.get(&"foo"), Some(&42));
+        assert_eq!(map.load_factor(), DEFAULT_LOAD_FACTOR);
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        let nbuckets = map.table.buckets.len();
+        map.set_load_factor(0.2);
+        assert!(map.table.buckets.len() > nbuckets);
+        assert!(map.capacity() >= 1000);
+        assert!((0..1000).all(|i| map[&i] == i));
+
+        map.reserve(500);
+        assert!(map.capacity() >= 1500);
+        assert!(map.table.buckets.len() as f64 * 0.2 >= 1499.0);
+        let clone = map.clone();
+        assert_eq!(clone.load_factor(), 0.2);
     }
 
     #[test]
-    fn remove() {
-        let mut map = HashMap::new();
-        map.insert("foo", "bar");
-        assert_eq!(map.get(&"foo"), Some(&"bar"));
-        assert_eq!(map.remove(&"foo"), Some("bar"));
+    #[should_panic(expected = "load factor must be in (0.1, 0.95]")]
+    fn load_factor_out_of_range() {
+        HashMap::<u32, u32>::new().set_load_factor(0.99);
+    }
+
+    fn longest_chain<K, V, S>(map: &HashMap<K, V, S>) -> usize {
+        map.chains().iter().map(Vec::len).max().unwrap_or(0)
     }
 
     #[test]
-    fn len_0() {
-        let mut map = HashMap::new();
-        assert_eq!(map.len(), 0);
-        map.insert("foo", "bar");
+    fn two_choice_shortens_longest_chain() {
+        // keys bunched into a few dense runs far apart, as ids often are
+        let keys: Vec<u64> = (0..64u64)
+            .flat_map(|run| (0..1024).map(move |i| (run << 40) | (i * 3)))
+            .collect();
+        let mut single: HashMap<u64, (), crate::BuildNoHashHasher<u64>> = HashMap::default();
+        let mut double: HashMap<u64, (), crate::BuildNoHashHasher<u64>> = HashMap::default();
+        double.set_two_choice(true);
+        for &k in &keys {
+            single.insert(k, ());
+            double.insert(k, ());
+        }
+        assert_eq!(double.bucket_count(), single.bucket_count());
+        let (one, two) = (longest_chain(&single), longest_chain(&double));
+        assert!(two * 2 <= one, "two-choice longest chain {} vs {}", two, one);
+        check_arena(&double);
+        assert!(keys.iter().all(|k| double.contains_key(k)));
+
+        // switching modes relinks the same entries
+        single.set_two_choice(true);
+        assert_eq!(longest_chain(&single), two);
+        double.set_two_choice(false);
+        assert_eq!(longest_chain(&double), one);
+        check_arena(&single);
+        check_arena(&double);
     }
 
     #[test]
-    fn len_1() {
+    fn two_choice_agrees_with_std() {
         let mut map = HashMap::new();
-        map.insert("foo", "bar");
-        assert_eq!(map.len(), 1);
+        map.set_two_choice(true);
+        let mut model = std::collections::HashMap::new();
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        for step in 0..20_000 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let key = (state % 3000) as u32;
+            match state >> 60 {
+                0..=5 => assert_eq!(map.insert(key, step), model.insert(key, step)),
+                6..=9 => assert_eq!(map.remove(&key), model.remove(&key)),
+                10 => {
+                    *map.entry(key).or_insert(0) += 1;
+                    *model.entry(key).or_insert(0) += 1;
+                }
+                11 => assert_eq!(map.remove_entry(&key), model.remove_entry(&key)),
+                12 if step % 500 == 0 => {
+                    map.retain(|&k, _| k % 4 != 0);
+                    model.retain(|&k, _| k % 4 != 0);
+                }
+                _ => assert_eq!(map.get(&key), model.get(&key)),
+            }
+            assert_eq!(map.len(), model.len());
+            if step % 1000 == 0 {
+                check_arena(&map);
+                assert!(map == model);
+            }
+        }
+        // resizes in both directions keep every entry in a candidate bucket
+        map.reserve(50_000);
+        check_arena(&map);
+        map.shrink_to_fit();
+        check_arena(&map);
+        assert!(map == model);
+
+        let clone = map.clone();
+        assert!(clone.two_choice());
+        let chains = clone.chains();
+        for key in model.keys() {
+            let [first, second] = clone.candidate_buckets(key).unwrap();
+            assert_eq!(clone.bucket_for(key), Some(first));
+            let home = chains.iter().position(|chain| chain.iter().any(|(_, k, _)| k == key));
+            assert!(home == Some(first) || home == Some(second));
+        }
+    }
+
+    /// Inserts fresh keys until `map` starts an incremental rehash.
+    fn grow_until_rehashing<S: BuildHasher>(
+        map: &mut HashMap<u32, u32, S>,
+        model: &mut std::collections::HashMap<u32, u32>,
+    ) {
+        let mut key = model.keys().max().map_or(0, |k| k + 1);
+        while !map.is_rehashing() {
+            map.insert(key, key);
+            model.insert(key, key);
+            key += 1;
+        }
     }
 
     #[test]
-    fn is_empty() {
+    fn growing_rehashes_incrementally() {
         let mut map = HashMap::new();
-        assert_eq!(map.is_empty(), true);
-        map.insert("foo", "bar");
+        let mut model = std::collections::HashMap::new();
+        grow_until_rehashing(&mut map, &mut model);
+        // the insert that grew the map only moved one step's worth
+        let old = map.table.old_buckets.len();
+        assert_eq!(old, INCREMENTAL_REHASH_MIN);
+        assert_eq!(map.bucket_count(), 2 * old);
+        assert_eq!(map.table.migrated, REHASH_STEP);
+        check_arena(&map);
+
+        // plain lookups move nothing; each insert or removal moves a step
+        assert!(model.keys().all(|k| map.contains_key(k)));
+        assert_eq!(map.table.migrated, REHASH_STEP);
+        map.remove(&0);
+        model.remove(&0);
+        assert_eq!(map.table.migrated, 2 * REHASH_STEP);
+        let mut steps = 2;
+        while map.is_rehashing() {
+            let key = 1_000_000 + steps;
+            map.insert(key, key);
+            model.insert(key, key);
+            steps += 1;
+        }
+        assert_eq!(steps as usize, old / REHASH_STEP);
+        check_arena(&map);
+        assert!(map == model);
     }
 
     #[test]
-    fn contains_key() {
+    fn operations_during_rehash() {
         let mut map = HashMap::new();
-        map.insert("foo", "bar");
-        assert_eq!(map.contains_key(&"foo"), true);
-        assert_eq!(map.contains_key(&"bar"), false);
+        let mut model = std::collections::HashMap::new();
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        for round in 0..3 {
+            // the last round also has to respect two candidate buckets
+            map.set_two_choice(round == 2);
+            grow_until_rehashing(&mut map, &mut model);
+            let mut step = 0;
+            while map.is_rehashing() {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let key = (state % (2 * model.len() as u64)) as u32;
+                match state >> 61 {
+                    0 | 1 => assert_eq!(map.insert(key, step), model.insert(key, step)),
+                    2 | 3 => assert_eq!(map.remove(&key), model.remove(&key)),
+                    4 => {
+                        *map.entry(key).or_insert(0) += 1;
+                        *model.entry(key).or_insert(0) += 1;
+                    }
+                    5 => match map.entry(key) {
+                        Entry::Occupied(e) => assert_eq!(Some(e.remove()), model.remove(&key)),
+                        Entry::Vacant(_) => assert!(!model.contains_key(&key)),
+                    },
+                    _ => assert_eq!(map.get(&key), model.get(&key)),
+                }
+                // nothing lost or duplicated, for lookups or iteration
+                assert_eq!(map.len(), model.len());
+                assert_eq!(map.iter().count(), model.len());
+                assert!(model.iter().all(|(k, v)| map.get(k) == Some(v)));
+                check_arena(&map);
+                step += 1;
+            }
+            assert!(map == model, "round {}", round);
+        }
     }
 
     #[test]
-    fn iter() {
+    fn bulk_operations_during_rehash() {
         let mut map = HashMap::new();
-        map.insert("foo", 41);
-        map.insert("bar", 42);
-        map.insert("baz", 413);
-        map.insert("quox", 4);
+        let mut model = std::collections::HashMap::new();
+        grow_until_rehashing(&mut map, &mut model);
+        let clone = map.clone();
+        assert!(clone.is_rehashing());
+        assert!(clone == model);
 
-        for (&key, &value) in &map {
-            match key {
-                "foo" => assert_eq!(value, 41),
-                "bar" => assert_eq!(value, 42),
-                "baz" => assert_eq!(value, 413),
-                "quox" => assert_eq!(value, 4),
-                _ => unreachable!(),
+        map.retain(|&k, _| k % 2 == 0);
+        model.retain(|&k, _| k % 2 == 0);
+        assert!(!map.is_rehashing());
+        check_arena(&map);
+        assert!(map == model);
+
+        grow_until_rehashing(&mut map, &mut model);
+        map.reserve(10_000);
+        assert!(!map.is_rehashing());
+        check_arena(&map);
+        assert!(map == model);
+
+        let mut other = clone.clone();
+        other.finish_rehash();
+        check_arena(&other);
+        assert!(other == clone);
+        other.clear();
+        assert!(!other.is_rehashing());
+        assert!(other.is_empty());
+        other.insert(1, 1);
+        check_arena(&other);
+    }
+
+    #[test]
+    fn miss_filter_has_no_false_negatives_under_churn() {
+        let mut map = HashMap::with_miss_filter(8);
+        let mut model = std::collections::HashMap::new();
+        let mut rng = 0x2545_f491_4f6c_dd1d_u64;
+        for step in 0..40_000u32 {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            let key = (rng % 5000) as u32;
+            match rng >> 60 {
+                0..=6 => assert_eq!(map.insert(key, step), model.insert(key, step)),
+                7..=13 => assert_eq!(map.remove(&key), model.remove(&key)),
+                14 => assert_eq!(map.entry(key).or_insert(step), model.entry(key).or_insert(step)),
+                _ if step % 1000 == 0 => {
+                    map.retain(|&k, _| k % 4 != 0);
+                    model.retain(|&k, _| k % 4 != 0);
+                }
+                _ => assert_eq!(map.get(&key), model.get(&key)),
+            }
+            if step % 2000 == 0 {
+                assert!(model.iter().all(|(k, v)| map.get(k) == Some(v)));
             }
         }
+        assert_eq!(map.len(), model.len());
+        assert!(model.iter().all(|(k, v)| map.get(k) == Some(v)));
+        check_arena(&map);
 
-        assert_eq!((&map).into_iter().count(), 4);
+        // and likewise across an incremental rehash
+        let mut model = std::collections::HashMap::new();
+        map.clear();
+        grow_until_rehashing(&mut map, &mut model);
+        assert!(model.keys().all(|k| map.contains_key(k)));
+        while map.is_rehashing() {
+            let &key = model.keys().next().unwrap();
+            assert_eq!(map.remove(&key), model.remove(&key));
+            assert!(model.keys().all(|k| map.contains_key(k)));
+        }
+    }
+
+    #[test]
+    fn miss_filter_turns_away_most_misses() {
+        let mut filtered = HashMap::with_hasher(DeterministicState::new(5));
+        filtered.set_miss_filter(Some(10));
+        let mut plain = HashMap::with_hasher(DeterministicState::new(5));
+        for i in 0..10_000u32 {
+            filtered.insert(i, i);
+            plain.insert(i, i);
+        }
+        for i in 0..20_000u32 {
+            assert_eq!(filtered.get(&i), plain.get(&i));
+        }
+        let filter = filtered.table.miss_filter.as_ref().unwrap();
+        let let_through = (10_000..110_000u32)
+            .filter(|i| filter.may_contain(filtered.hasher().hash_one(i)))
+            .count();
+        assert!(let_through < 5_000, "{} misses reached the buckets", let_through);
+        assert_eq!(filtered.clone().miss_filter(), Some(10));
+
+        // mass removal rebuilds it rather than leaving the dead bits set
+        filtered.set_auto_shrink(false);
+        for i in 0..9_000u32 {
+            filtered.remove(&i);
+        }
+        let filter = filtered.table.miss_filter.as_ref().unwrap();
+        let stale = (0..9_000u32).filter(|i| filter.may_contain(filtered.hasher().hash_one(i))).count();
+        assert!(stale < 2_000, "{} removed keys still pass the filter", stale);
+        assert!((9_000..10_000u32).all(|i| filtered[&i] == i));
+
+        filtered.set_miss_filter(None);
+        assert_eq!(filtered.miss_filter(), None);
+        assert!((9_000..10_000u32).all(|i| filtered[&i] == i));
+        assert_eq!(filtered.get(&1), None);
+    }
+
+    #[test]
+    fn memory_usage_follows_resizes() {
+        let mut map: HashMap<u64, String> = HashMap::new();
+        assert_eq!(map.memory_usage(), MapMemoryUsage::default());
+        assert_eq!(map.memory_usage().total(), 0);
+
+        let entry = mem::size_of::<(u64, u64, String)>();
+        let mut last = map.memory_usage();
+        for i in 0..5000 {
+            let nbuckets = map.bucket_count();
+            let was_rehashing = map.is_rehashing();
+            map.insert(i, i.to_string());
+            let usage = map.memory_usage();
+            // the strings' own bytes are not counted
+            assert_eq!(usage.entries_bytes, map.len() * entry);
+            if map.bucket_count() > nbuckets {
+                assert!(usage.table_bytes > last.table_bytes);
+                assert!(usage.total() > last.total());
+            }
+            // only finishing an incremental rehash gives memory back
+            if !was_rehashing || map.is_rehashing() {
+                assert!(usage.total() >= last.total());
+            }
+            last = usage;
+        }
+        let usage = map.memory_usage();
+        assert_eq!(
+            usage.total(),
+            map.entries.capacity() * entry
+                + (map.table.next.capacity() + map.table.buckets.capacity() + map.table.old_buckets.capacity())
+                    * mem::size_of::<usize>(),
+        );
+
+        map.set_auto_shrink(false);
+        map.retain(|&k, _| k < 100);
+        let before = map.memory_usage();
+        assert!(before.wasted_capacity_bytes > before.entries_bytes);
+        map.shrink_to_fit();
+        let after = map.memory_usage();
+        assert!(after.total() < before.total());
+        assert!(after.table_bytes < before.table_bytes);
+        assert_eq!(after.entries_bytes, before.entries_bytes);
+
+        // a miss filter counts as part of the table
+        map.set_miss_filter(Some(8));
+        assert!(map.memory_usage().table_bytes > after.table_bytes);
+        map.clear();
+        map.shrink_to_fit();
+        map.set_miss_filter(None);
+        assert_eq!(map.memory_usage().total(), 0);
     }
 
 }
\ No newline at end of file
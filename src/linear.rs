@@ -0,0 +1,526 @@
+//! Open addressing with linear probing, as a [`Table`] for [`HashMap`].
+//!
+//! A map on a [`LinearTable`] keeps the same dense entry arena as one on
+//! the default chained table, so its iterators and entry API are the same,
+//! but it finds entries through a flat array of slots instead of index
+//! chains. Every full slot caches its entry's hash, so a probe only looks
+//! at the arena when the hashes match, and a collision costs a step to the
+//! neighbouring slot rather than a jump through a `next` link.
+//!
+//! A removed entry leaves a tombstone behind, so probes for the keys past
+//! it keep walking. Tombstones count against the load factor like live
+//! entries do, and are swept out whenever the table is rebuilt.
+
+use alloc::vec::Vec;
+
+use crate::{bucket_index, sealed, DefaultState, HashMap, Table, TryReserveError};
+
+/// Slots in the smallest allocated table.
+pub(crate) const MIN_SLOTS: usize = 4;
+
+/// Live entries plus tombstones that `nslots` slots hold before the table
+/// is rebuilt. Always less than `nslots`, so every probe that misses ends
+/// at an empty slot.
+pub(crate) fn capacity_of(nslots: usize) -> usize {
+    nslots / 4 * 3
+}
+
+/// Smallest power-of-two slot count with room for `capacity` entries, or
+/// `None` if that count does not fit in a `usize`.
+pub(crate) fn slots_for(capacity: usize) -> Option<usize> {
+    if capacity == 0 {
+        return Some(0);
+    }
+    let mut nslots = MIN_SLOTS;
+    while capacity_of(nslots) < capacity {
+        nslots = nslots.checked_mul(2)?;
+    }
+    Some(nslots)
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Slot {
+    Empty,
+    /// Held an entry that was removed; probes continue past it.
+    Tombstone,
+    Full { hash: u64, index: usize },
+}
+
+/// A [`Table`] that resolves collisions by linear probing.
+///
+/// A map on it has the same API as any [`HashMap`], entry API included;
+/// the chained table's own settings, like the load factor, don't apply:
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::{HashMap, LinearTable};
+///
+/// let mut map = HashMap::with_table(LinearTable::new());
+/// map.insert("a", 1);
+/// *map.entry("b").or_insert(0) += 2;
+/// assert_eq!(map.get("a"), Some(&1));
+/// assert_eq!(map.remove("b"), Some(2));
+/// assert_eq!(map.len(), 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LinearTable {
+    slots: Vec<Slot>,
+    tombstones: usize,
+}
+
+impl LinearTable {
+    /// An empty table; it allocates its slots on the first insert.
+    pub fn new() -> Self {
+        LinearTable {
+            slots: Vec::new(),
+            tombstones: 0,
+        }
+    }
+}
+
+/// A [`HashMap`] on a [`LinearTable`].
+pub type LinearMap<K, V, S = DefaultState> = HashMap<K, V, S, LinearTable>;
+
+impl sealed::Sealed for LinearTable {}
+
+impl Table for LinearTable {
+    /// Number of entries the map can hold before its table is rebuilt,
+    /// assuming no tombstones are left behind by removals in between.
+    fn capacity(&self) -> usize {
+        capacity_of(self.slots.len())
+    }
+
+    fn find<K, V, S>(map: &HashMap<K, V, S, Self>, hash: u64, is_match: impl FnMut(&K) -> bool) -> Option<usize> {
+        map.find(hash, is_match).map(|(_, index)| index)
+    }
+
+    fn reserve_one<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.reserve_one();
+    }
+
+    fn push<K, V, S>(map: &mut HashMap<K, V, S, Self>, hash: u64, key: K, value: V) -> usize {
+        // no key matches, so the probe ends at the slot the new one goes in
+        let slot = map.find_slot(hash, |_| false).unwrap_err();
+        map.push_entry(slot, hash, key, value)
+    }
+
+    fn remove<K, V, S>(map: &mut HashMap<K, V, S, Self>, index: usize) -> (u64, K, V) {
+        let slot = map.slot_of(index);
+        map.remove_at(slot, index)
+    }
+
+    fn unlink_all<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.table.slots.fill(Slot::Empty);
+        map.table.tombstones = 0;
+    }
+
+    fn retained<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.rebuild(map.table.slots.len());
+    }
+
+    /// Makes room for at least `additional` more entries, sweeping out any
+    /// tombstones if the table has to be rebuilt for it.
+    fn reserve<K, V, S>(map: &mut HashMap<K, V, S, Self>, additional: usize) {
+        let needed = map.len().checked_add(additional).expect("capacity overflow");
+        map.entries.reserve(additional);
+        if needed + map.table.tombstones > map.capacity() {
+            let nslots = slots_for(needed).expect("capacity overflow");
+            map.rebuild(nslots.max(map.table.slots.len()));
+        }
+    }
+
+    fn try_reserve<K, V, S>(map: &mut HashMap<K, V, S, Self>, additional: usize) -> Result<(), TryReserveError> {
+        let needed = map.len().checked_add(additional).ok_or(TryReserveError::CapacityOverflow)?;
+        map.entries.try_reserve(additional).map_err(|_| TryReserveError::AllocError)?;
+        if needed.saturating_add(map.table.tombstones) > map.capacity() {
+            let nslots = slots_for(needed).ok_or(TryReserveError::CapacityOverflow)?;
+            let nslots = nslots.max(map.table.slots.len());
+            // `rebuild` refills the slot array where it stands
+            map.table.slots
+                .try_reserve_exact(nslots - map.table.slots.len())
+                .map_err(|_| TryReserveError::AllocError)?;
+            map.rebuild(nslots);
+        }
+        Ok(())
+    }
+
+    /// Shrinks the table as far as the current entries allow, dropping every
+    /// tombstone.
+    fn shrink_to_fit<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.entries.shrink_to_fit();
+        map.rebuild(slots_for(map.len()).expect("capacity overflow"));
+        map.table.slots.shrink_to_fit();
+    }
+
+    // only `shrink_to_fit` gives slots back
+    fn maybe_shrink<K, V, S>(_map: &mut HashMap<K, V, S, Self>) {}
+}
+
+impl<K, V, S> HashMap<K, V, S, LinearTable> {
+    /// Slots probed for `hash`: its home slot, then each following one,
+    /// wrapping around the end of the table. The table must be allocated.
+    fn probe(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let nslots = self.table.slots.len();
+        let home = bucket_index(hash, nslots);
+        (0..nslots).map(move |step| (home + step) & (nslots - 1))
+    }
+
+    /// Slot and arena index of the entry with this `hash` whose key
+    /// `is_match` accepts, or else the slot a new entry for it should go
+    /// in: the first tombstone on the way, or the empty slot that ended the
+    /// probe. The table must be allocated and have room for one more entry.
+    fn find_slot<F>(&self, hash: u64, mut is_match: F) -> Result<(usize, usize), usize>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let mut vacant = None;
+        for slot in self.probe(hash) {
+            match self.table.slots[slot] {
+                Slot::Empty => return Err(vacant.unwrap_or(slot)),
+                Slot::Tombstone => {
+                    vacant.get_or_insert(slot);
+                }
+                Slot::Full { hash: shash, index } => {
+                    if shash == hash && is_match(&self.entries[index].1) {
+                        return Ok((slot, index));
+                    }
+                }
+            }
+        }
+        Err(vacant.expect("linear probe found no free slot"))
+    }
+
+    /// Like `find_slot`, but for lookups, which also work on an empty map.
+    fn find<F>(&self, hash: u64, is_match: F) -> Option<(usize, usize)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        self.find_slot(hash, is_match).ok()
+    }
+
+    /// Furthest any entry sits from its home slot.
+    #[cfg(all(test, feature = "std"))]
+    pub(crate) fn max_probe_length(&self) -> usize {
+        let nslots = self.table.slots.len();
+        (0..nslots)
+            .filter_map(|slot| match self.table.slots[slot] {
+                Slot::Full { hash, .. } => Some((slot + nslots - bucket_index(hash, nslots)) & (nslots - 1)),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Slot that points at `entries[index]`.
+    fn slot_of(&self, index: usize) -> usize {
+        self.probe(self.entries[index].0)
+            .find(|&slot| matches!(self.table.slots[slot], Slot::Full { index: i, .. } if i == index))
+            .expect("entry missing from the slot table")
+    }
+
+    /// Fills the vacant `slot` with a new entry and returns its arena index.
+    fn push_entry(&mut self, slot: usize, hash: u64, key: K, value: V) -> usize {
+        if let Slot::Tombstone = self.table.slots[slot] {
+            self.table.tombstones -= 1;
+        }
+        let index = self.entries.len();
+        self.table.slots[slot] = Slot::Full { hash, index };
+        self.entries.push((hash, key, value));
+        index
+    }
+
+    /// Removes the entry at arena `index`, which `slot` points at. The last
+    /// entry is swapped into its place in the arena, and its slot updated.
+    fn remove_at(&mut self, slot: usize, index: usize) -> (u64, K, V) {
+        // a probe that reaches an empty slot stops there anyway, so a slot
+        // right before one needn't keep the probes going
+        let after = (slot + 1) & (self.table.slots.len() - 1);
+        self.table.slots[slot] = match self.table.slots[after] {
+            Slot::Empty => Slot::Empty,
+            _ => {
+                self.table.tombstones += 1;
+                Slot::Tombstone
+            }
+        };
+        let last = self.entries.len() - 1;
+        if index != last {
+            let moved = self.slot_of(last);
+            self.table.slots[moved] = Slot::Full { hash: self.entries[last].0, index };
+        }
+        self.entries.swap_remove(index)
+    }
+
+    /// Makes sure one more entry fits without the probe running out of
+    /// empty slots. A table that is mostly tombstones is swept at the same
+    /// size; otherwise it doubles.
+    fn reserve_one(&mut self) {
+        let capacity = self.capacity();
+        if self.len() + self.table.tombstones < capacity {
+            return;
+        }
+        let nslots = match self.len() < capacity / 2 {
+            true => self.table.slots.len(),
+            false => (self.table.slots.len() * 2).max(MIN_SLOTS),
+        };
+        self.rebuild(nslots);
+    }
+
+    /// Lays the arena out over `nslots` fresh slots, dropping every
+    /// tombstone. Hashes are cached, so no key is hashed again.
+    fn rebuild(&mut self, nslots: usize) {
+        debug_assert!(capacity_of(nslots) >= self.len());
+        self.table.slots.clear();
+        self.table.slots.resize(nslots, Slot::Empty);
+        self.table.tombstones = 0;
+        for (index, &(hash, _, _)) in self.entries.iter().enumerate() {
+            let mut slot = bucket_index(hash, nslots);
+            while !matches!(self.table.slots[slot], Slot::Empty) {
+                slot = (slot + 1) & (nslots - 1);
+            }
+            self.table.slots[slot] = Slot::Full { hash, index };
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::hash::BuildHasher;
+    use core::mem;
+
+    use super::*;
+    use crate::test_util::{differential, Colliding};
+    use crate::{BuildNoHashHasher, Entry};
+
+    /// Every entry is pointed at by exactly one slot, the tombstone count
+    /// is right and the table always keeps an empty slot.
+    fn check_table<K, V, S>(map: &LinearMap<K, V, S>) {
+        let mut seen = vec![false; map.len()];
+        let mut tombstones = 0;
+        for slot in &map.table.slots {
+            match *slot {
+                Slot::Empty => {}
+                Slot::Tombstone => tombstones += 1,
+                Slot::Full { hash, index } => {
+                    assert_eq!(map.entries[index].0, hash);
+                    assert!(!mem::replace(&mut seen[index], true));
+                }
+            }
+        }
+        assert!(seen.into_iter().all(|pointed_at| pointed_at));
+        assert_eq!(tombstones, map.table.tombstones);
+        assert!(map.table.slots.is_empty() || map.len() + map.table.tombstones < map.table.slots.len());
+    }
+
+    #[test]
+    fn differential_against_chaining() {
+        for seed in 0..8 {
+            // few keys, so inserts and removals keep hitting the same ones
+            differential(seed, 64, HashMap::with_table(LinearTable::new()), check_table);
+            differential(seed, 4096, HashMap::with_table(LinearTable::new()), check_table);
+        }
+    }
+
+    #[test]
+    fn differential_with_collisions() {
+        for seed in 0..4 {
+            differential(seed, 200, HashMap::with_table_and_hasher(LinearTable::new(), Colliding::default()), check_table);
+        }
+    }
+
+    #[test]
+    fn insert_get_remove() {
+        let mut map = HashMap::with_table(LinearTable::new());
+        for i in 0..1000 {
+            assert_eq!(map.insert(i, i * 2), None);
+        }
+        assert_eq!(map.insert(7, 0), Some(14));
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map[&7], 0);
+        for i in (0..1000).step_by(2) {
+            assert_eq!(map.remove(&i), Some(if i == 7 { 0 } else { i * 2 }));
+        }
+        assert_eq!(map.len(), 500);
+        assert!((1..1000).step_by(2).all(|i| map.contains_key(&i)));
+        assert!((0..1000).step_by(2).all(|i| map.get(&i).is_none()));
+        check_table(&map);
+    }
+
+    #[test]
+    fn probes_wrap_around() {
+        let mut map: LinearMap<u64, u64, BuildNoHashHasher<u64>> = LinearMap::default();
+        map.reserve(12);
+        let nslots = map.table.slots.len();
+        let keys: Vec<u64> = (0..)
+            .filter(|&k| bucket_index(map.hash_builder.hash_one(k), nslots) == nslots - 1)
+            .take(3)
+            .collect();
+        for &key in &keys {
+            map.insert(key, key);
+        }
+        assert_eq!(map.table.slots.len(), nslots);
+        let full: Vec<_> = (0..nslots).filter(|&s| matches!(map.table.slots[s], Slot::Full { .. })).collect();
+        assert_eq!(full, [0, 1, nslots - 1]);
+
+        // removing the one in the last slot must not cut the others off
+        assert_eq!(map.remove(&keys[0]), Some(keys[0]));
+        assert!(matches!(map.table.slots[nslots - 1], Slot::Tombstone));
+        assert_eq!(map.get(&keys[1]), Some(&keys[1]));
+        assert_eq!(map.get(&keys[2]), Some(&keys[2]));
+        map.insert(keys[0], 0);
+        assert!(matches!(map.table.slots[nslots - 1], Slot::Full { .. }));
+        assert_eq!(map.table.tombstones, 0);
+        check_table(&map);
+    }
+
+    #[test]
+    fn tombstones_trigger_sweep_not_growth() {
+        let mut map = HashMap::with_table(LinearTable::new());
+        map.reserve(100);
+        let nslots = map.table.slots.len();
+        for round in 0..100_000u32 {
+            map.insert(round, round);
+            if round >= 10 {
+                assert_eq!(map.remove(&(round - 10)), Some(round - 10));
+            }
+        }
+        // ten live keys never need more than the table that was asked for
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.table.slots.len(), nslots);
+        check_table(&map);
+        assert!((99_990..100_000).all(|k| map[&k] == k));
+    }
+
+    #[test]
+    fn growth_counts_tombstones() {
+        let mut map = HashMap::with_table_and_hasher(LinearTable::new(), Colliding::default());
+        for i in 0..5 {
+            map.insert(i, i);
+        }
+        let nslots = map.table.slots.len();
+        // removals in the middle of the run leave tombstones behind
+        map.remove(&1);
+        map.remove(&2);
+        assert_eq!(map.table.tombstones, 2);
+        map.insert(10, 10);
+        assert_eq!(map.table.tombstones, 1, "the new key reuses the first tombstone");
+        while map.len() + map.table.tombstones < map.capacity() {
+            map.insert(map.len() as u32 + 100, 0);
+        }
+        map.insert(1000, 0);
+        assert_eq!(map.table.tombstones, 0);
+        assert_eq!(map.table.slots.len(), nslots * 2);
+        check_table(&map);
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut map = HashMap::with_table(LinearTable::new());
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(map["the"], 3);
+        assert_eq!(map["fox"], 1);
+
+        match map.entry("fox") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.key(), &"fox");
+                assert_eq!(e.insert(10), 1);
+                assert_eq!(e.remove_entry(), ("fox", 10));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry("fox") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(e) => {
+                assert_eq!(e.key(), &"fox");
+                let e = e.insert_entry(5);
+                assert_eq!(e.get(), &5);
+            }
+        }
+        map.entry("end").and_modify(|v| *v += 1).or_insert(0);
+        assert_eq!(map["end"], 2);
+        assert_eq!(*map.entry("new").or_insert_with_key(|k| k.len()), 3);
+        assert_eq!(*map.entry("zero").or_insert_default(), 0);
+        check_table(&map);
+    }
+
+    #[test]
+    fn iterators_match_hash_map() {
+        let mut map: LinearMap<u32, u32> = (0..500).map(|i| (i, i)).collect();
+        for (_, v) in &mut map {
+            *v += 1;
+        }
+        for v in map.values_mut() {
+            *v *= 2;
+        }
+        assert!(map.iter().all(|(&k, &v)| v == (k + 1) * 2));
+        assert_eq!(map.keys().len(), 500);
+        assert_eq!(map.values().copied().max(), Some(1000));
+        let model: HashMap<u32, u32> = map.clone().into_iter().collect();
+        assert!(map == model);
+
+        let mut keys: Vec<_> = map.clone().into_keys().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, (0..500).collect::<Vec<_>>());
+        assert_eq!(map.clone().into_values().count(), 500);
+
+        let capacity = map.capacity();
+        assert_eq!(map.drain().count(), 500);
+        assert!(map.is_empty());
+        assert_eq!(map.capacity(), capacity);
+        map.insert(1, 1);
+        assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &1)]);
+    }
+
+    #[test]
+    fn reserve_and_shrink() {
+        let mut map = HashMap::with_table(LinearTable::new());
+        map.reserve(1000);
+        let nslots = map.table.slots.len();
+        assert!(map.capacity() >= 1000);
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.table.slots.len(), nslots);
+        map.retain(|&k, _| k < 10);
+        map.shrink_to_fit();
+        assert!(map.table.slots.len() < nslots);
+        assert!((0..10).all(|k| map[&k] == k));
+        check_table(&map);
+
+        map.clear();
+        map.shrink_to_fit();
+        assert_eq!(map.capacity(), 0);
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.remove(&1), None);
+    }
+
+    #[test]
+    fn try_reserve() {
+        let mut map = HashMap::with_table(LinearTable::new());
+        map.insert(1u32, 1u32);
+        assert_eq!(map.try_reserve(usize::MAX), Err(TryReserveError::CapacityOverflow));
+        assert_eq!(map.try_reserve(1000), Ok(()));
+        let nslots = map.table.slots.len();
+        assert!(map.capacity() >= 1001);
+        for i in 2..1002 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.table.slots.len(), nslots);
+        check_table(&map);
+    }
+
+    #[test]
+    fn borrowed_lookups_and_debug() {
+        let map: LinearMap<_, _> = [("a".to_string(), 1)].into_iter().collect();
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.get_key_value("a"), Some((&"a".to_string(), &1)));
+        assert_eq!(format!("{:?}", map), r#"{"a": 1}"#);
+        let mut other = HashMap::with_table(LinearTable::new());
+        other.extend(map.iter().map(|(k, &v)| (k.clone(), v)));
+        assert_eq!(map, other);
+    }
+}
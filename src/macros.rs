@@ -0,0 +1,155 @@
+/// Builds a [`HashMap`](crate::HashMap) from `key => value` pairs.
+///
+/// The number of pairs is counted when the macro expands, so the map is
+/// created with room for all of them and never resizes while being filled.
+/// A key that appears twice keeps its last value, as with `insert`. The
+/// map uses the default hasher, so the macro needs the `std` feature.
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::hashmap;
+///
+/// let map = hashmap! {
+///     "a" => 1,
+///     "b" => 2,
+/// };
+/// assert_eq!(map["b"], 2);
+///
+/// let empty: hashmap::HashMap<&str, i32> = hashmap! {};
+/// assert!(empty.is_empty());
+/// ```
+///
+/// Unlike `vec!`, there is no `key => value; count` form:
+///
+/// ```compile_fail
+/// let map = hashmap::hashmap! { "a" => 1; 3 };
+/// ```
+#[macro_export]
+macro_rules! hashmap {
+    (@unit $($x:tt)*) => { () };
+    (@count $($key:expr),*) => {
+        <[()]>::len(&[$($crate::hashmap!(@unit $key)),*])
+    };
+
+    ($key:expr => $value:expr; $count:expr) => {
+        ::core::compile_error!(
+            "hashmap! does not support `key => value; count`, since every key must be distinct"
+        )
+    };
+    () => {
+        $crate::HashMap::new()
+    };
+    ($($key:expr => $value:expr),+ $(,)?) => {{
+        let mut map = $crate::HashMap::with_capacity($crate::hashmap!(@count $($key),+));
+        $(
+            map.insert($key, $value);
+        )+
+        map
+    }};
+}
+
+/// Declares `static` [`StaticMap`](crate::StaticMap)s, laid out while
+/// compiling, from `key => value` pairs.
+///
+/// Keys are string or byte string literals, `char`s or integers, or any
+/// constant expression of those types, and values any constant
+/// expression. A repeated key is a compile error.
+///
+/// ```
+/// use hashmap::static_map;
+///
+/// static_map! {
+///     static KEYWORDS: &'static str => u8 = {
+///         "fn" => 1,
+///         "let" => 2,
+///         "match" => 3,
+///     };
+///     pub(crate) static SQUARES: u32 => u32 = { 2 => 4, 3 => 9 };
+/// }
+///
+/// assert_eq!(KEYWORDS.get("let"), Some(&2));
+/// assert!(!KEYWORDS.contains_key("var"));
+/// assert_eq!(SQUARES.get(&3), Some(&9));
+/// ```
+///
+/// ```compile_fail
+/// hashmap::static_map! {
+///     static TWICE: &'static str => u8 = { "a" => 1, "a" => 2 };
+/// }
+/// ```
+#[macro_export]
+macro_rules! static_map {
+    ($(
+        $(#[$attr:meta])*
+        $vis:vis static $name:ident : $key_ty:ty => $value_ty:ty = {
+            $($key:expr => $value:expr),* $(,)?
+        };
+    )*) => {$(
+        $(#[$attr])*
+        $vis static $name: $crate::StaticMap<$key_ty, $value_ty> = {
+            use $crate::static_map::{home, slots_for, Key, EMPTY};
+
+            const ENTRIES: &[($key_ty, $value_ty)] = &[$(($key, $value)),*];
+            const NSLOTS: usize = slots_for(ENTRIES.len());
+            const SLOTS: [usize; NSLOTS] = {
+                let mut slots = [EMPTY; NSLOTS];
+                let mut index = 0;
+                while index < ENTRIES.len() {
+                    let key = Key::<$key_ty>(ENTRIES[index].0);
+                    let mut slot = home(key.hash(), NSLOTS);
+                    while slots[slot] != EMPTY {
+                        // an equal key hashes the same, so it is somewhere
+                        // on this probe
+                        if key.same(&Key(ENTRIES[slots[slot]].0)) {
+                            panic!(concat!("static_map!: duplicate key in ", stringify!($name)));
+                        }
+                        slot = (slot + 1) & (NSLOTS - 1);
+                    }
+                    slots[slot] = index;
+                    index += 1;
+                }
+                slots
+            };
+            $crate::StaticMap::from_parts(ENTRIES, &SLOTS)
+        };
+    )*};
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    // Nothing is imported here: the expansion has to name the map type
+    // through `$crate`.
+
+    #[test]
+    fn literal() {
+        let map = hashmap! { "a" => 1, "b" => 2 };
+        assert_eq!(map.len(), 2);
+        assert_eq!(map["a"], 1);
+        assert_eq!(map["b"], 2);
+    }
+
+    #[test]
+    fn trailing_comma_and_duplicates() {
+        let map = hashmap! {
+            1 => "one",
+            2 => "two",
+            1 => "uno",
+        };
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[&1], "uno");
+    }
+
+    #[test]
+    fn empty() {
+        let map: crate::HashMap<u8, u8> = hashmap! {};
+        assert!(map.is_empty());
+        assert_eq!(map.capacity(), 0);
+    }
+
+    #[test]
+    fn presized() {
+        let map = hashmap! { 1 => 1, 2 => 2, 3 => 3, 4 => 4, 5 => 5 };
+        assert_eq!(map.table.buckets.len(), crate::buckets_for(5, crate::DEFAULT_LOAD_FACTOR).unwrap());
+        assert!(map.capacity() >= 5);
+    }
+}
@@ -0,0 +1,287 @@
+//! Impls that every map type besides [`HashMap`](crate::HashMap) would
+//! otherwise spell out word for word.
+//!
+//! The maps differ in how they store and find entries, not in how
+//! `Extend` reserves or how an `Entry` falls back to inserting, so each
+//! module writes its own constructors, `Clone`, by-value iterator and
+//! entry accessors and gets the rest from these macros. Names the macros
+//! don't qualify, such as `Iter`, `HashMap` or `OccupiedEntry`, are the
+//! invoking module's own.
+
+/// The std traits of a map type `$map`, which must have the usual
+/// `with_hasher`, `with_capacity`, `reserve`, `insert`, `get`, `iter` and
+/// `iter_mut`.
+macro_rules! map_impls {
+    ($map:ident) => {
+        impl<K, V, S: Default> Default for $map<K, V, S> {
+            fn default() -> Self {
+                $map::with_hasher(S::default())
+            }
+        }
+
+        impl<K: core::fmt::Debug, V: core::fmt::Debug, S> core::fmt::Debug for $map<K, V, S> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.debug_map().entries(self.iter()).finish()
+            }
+        }
+
+        impl<K, V, S> PartialEq for $map<K, V, S>
+        where
+            K: core::hash::Hash + Eq,
+            V: PartialEq,
+            S: core::hash::BuildHasher,
+        {
+            fn eq(&self, other: &Self) -> bool {
+                self.len() == other.len()
+                    && self.iter().all(|(key, value)| other.get(key) == Some(value))
+            }
+        }
+
+        impl<K, V, S> Eq for $map<K, V, S>
+        where
+            K: core::hash::Hash + Eq,
+            V: Eq,
+            S: core::hash::BuildHasher,
+        {}
+
+        impl<K, V, S> PartialEq<HashMap<K, V, S>> for $map<K, V, S>
+        where
+            K: core::hash::Hash + Eq,
+            V: PartialEq,
+            S: core::hash::BuildHasher,
+        {
+            fn eq(&self, other: &HashMap<K, V, S>) -> bool {
+                self.len() == other.len()
+                    && self.iter().all(|(key, value)| other.get(key) == Some(value))
+            }
+        }
+
+        impl<K, V, S, Q> core::ops::Index<&Q> for $map<K, V, S>
+        where
+            K: core::hash::Hash + Eq,
+            Q: core::hash::Hash + Equivalent<K> + ?Sized,
+            S: core::hash::BuildHasher,
+        {
+            type Output = V;
+
+            /// Panics if `key` is not in the map.
+            fn index(&self, key: &Q) -> &V {
+                self.get(key).expect(concat!(stringify!($map), "::index: key not found"))
+            }
+        }
+
+        impl<K, V, S> FromIterator<(K, V)> for $map<K, V, S>
+        where
+            K: core::hash::Hash + Eq,
+            S: core::hash::BuildHasher + Default,
+        {
+            fn from_iter<T>(iter: T) -> Self
+            where
+                T: IntoIterator<Item = (K, V)>,
+            {
+                let mut map = $map::with_hasher(S::default());
+                map.extend(iter);
+                map
+            }
+        }
+
+        impl<K, V, S> Extend<(K, V)> for $map<K, V, S>
+        where
+            K: core::hash::Hash + Eq,
+            S: core::hash::BuildHasher,
+        {
+            fn extend<T>(&mut self, iter: T)
+            where
+                T: IntoIterator<Item = (K, V)>,
+            {
+                let iter = iter.into_iter();
+                let additional = match self.is_empty() {
+                    true => iter.size_hint().0,
+                    false => iter.size_hint().0.div_ceil(2),
+                };
+                self.reserve(additional);
+                for (k, v) in iter {
+                    self.insert(k, v);
+                }
+            }
+        }
+
+        impl<'a, K, V, S> Extend<(&'a K, &'a V)> for $map<K, V, S>
+        where
+            K: core::hash::Hash + Eq + Copy,
+            V: Copy,
+            S: core::hash::BuildHasher,
+        {
+            fn extend<T>(&mut self, iter: T)
+            where
+                T: IntoIterator<Item = (&'a K, &'a V)>,
+            {
+                self.extend(iter.into_iter().map(|(&k, &v)| (k, v)));
+            }
+        }
+
+        #[cfg(feature = "std")]
+        impl<K, V, const N: usize> From<[(K, V); N]> for $map<K, V>
+        where
+            K: core::hash::Hash + Eq,
+        {
+            fn from(arr: [(K, V); N]) -> Self {
+                let mut map = $map::with_capacity(N);
+                map.extend(arr);
+                map
+            }
+        }
+
+        impl<'a, K, V, S> IntoIterator for &'a $map<K, V, S> {
+            type Item = (&'a K, &'a V);
+            type IntoIter = Iter<'a, K, V>;
+            fn into_iter(self) -> Self::IntoIter {
+                self.iter()
+            }
+        }
+
+        impl<'a, K, V, S> IntoIterator for &'a mut $map<K, V, S> {
+            type Item = (&'a K, &'a mut V);
+            type IntoIter = IterMut<'a, K, V>;
+            fn into_iter(self) -> Self::IntoIter {
+                self.iter_mut()
+            }
+        }
+    };
+}
+
+/// The parts of a map's `Entry`, `OccupiedEntry` and `VacantEntry` that
+/// only go through their other methods. The module writes the accessors
+/// (`key`, `get`, `get_mut`, `into_mut` and `remove_entry` on an occupied
+/// entry, `insert_entry` on a vacant one), and a vacant entry keeps its
+/// key in a `key` field. Bounds that `insert_entry` needs go after
+/// `where`.
+macro_rules! entry_impls {
+    ($(where $($bound:tt)+)?) => {
+        impl<'a, K: core::fmt::Debug, V: core::fmt::Debug, S> core::fmt::Debug for Entry<'a, K, V, S> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                match self {
+                    Entry::Occupied(e) => f.debug_tuple("Entry").field(e).finish(),
+                    Entry::Vacant(e) => f.debug_tuple("Entry").field(e).finish(),
+                }
+            }
+        }
+
+        impl<'a, K: core::fmt::Debug, V: core::fmt::Debug, S> core::fmt::Debug for OccupiedEntry<'a, K, V, S> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.debug_struct("OccupiedEntry")
+                    .field("key", self.key())
+                    .field("value", self.get())
+                    .finish()
+            }
+        }
+
+        impl<'a, K: core::fmt::Debug, V, S> core::fmt::Debug for VacantEntry<'a, K, V, S> {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                f.debug_tuple("VacantEntry").field(self.key()).finish()
+            }
+        }
+
+        impl<'a, K: 'a, V: 'a, S> OccupiedEntry<'a, K, V, S> {
+            /// Replaces the value, returning the old one.
+            pub fn insert(&mut self, value: V) -> V {
+                core::mem::replace(self.get_mut(), value)
+            }
+
+            pub fn remove(self) -> V {
+                self.remove_entry().1
+            }
+        }
+
+        impl<'a, K: 'a, V: 'a, S> VacantEntry<'a, K, V, S> {
+            pub fn key(&self) -> &K {
+                &self.key
+            }
+
+            pub fn into_key(self) -> K {
+                self.key
+            }
+        }
+
+        impl<'a, K: 'a, V: 'a, S> VacantEntry<'a, K, V, S>
+        $(where $($bound)+)?
+        {
+            pub fn insert(self, value: V) -> &'a mut V {
+                self.insert_entry(value).into_mut()
+            }
+        }
+
+        impl<'a, K: 'a, V: 'a, S> Entry<'a, K, V, S>
+        $(where $($bound)+)?
+        {
+            pub fn or_insert(self, value: V) -> &'a mut V {
+                match self {
+                    Entry::Occupied(e) => e.into_mut(),
+                    Entry::Vacant(e) => e.insert(value),
+                }
+            }
+
+            pub fn or_insert_with<F>(self, maker: F) -> &'a mut V
+            where
+                F: FnOnce() -> V,
+            {
+                match self {
+                    Entry::Occupied(e) => e.into_mut(),
+                    Entry::Vacant(e) => e.insert(maker()),
+                }
+            }
+
+            pub fn or_insert_with_key<F>(self, maker: F) -> &'a mut V
+            where
+                F: FnOnce(&K) -> V,
+            {
+                match self {
+                    Entry::Occupied(e) => e.into_mut(),
+                    Entry::Vacant(e) => {
+                        let value = maker(&e.key);
+                        e.insert(value)
+                    }
+                }
+            }
+
+            pub fn or_insert_default(self) -> &'a mut V
+            where
+                V: Default,
+            {
+                self.or_insert_with(Default::default)
+            }
+
+            pub fn key(&self) -> &K {
+                match self {
+                    Entry::Occupied(e) => e.key(),
+                    Entry::Vacant(e) => e.key(),
+                }
+            }
+
+            /// Sets the value of the entry, overwriting it if occupied, and
+            /// returns the now occupied entry.
+            pub fn insert(self, value: V) -> OccupiedEntry<'a, K, V, S> {
+                match self {
+                    Entry::Occupied(mut e) => {
+                        e.insert(value);
+                        e
+                    }
+                    Entry::Vacant(e) => e.insert_entry(value),
+                }
+            }
+
+            pub fn and_modify<F>(self, f: F) -> Self
+            where
+                F: FnOnce(&mut V),
+            {
+                match self {
+                    Entry::Occupied(mut e) => {
+                        f(e.get_mut());
+                        Entry::Occupied(e)
+                    }
+                    Entry::Vacant(e) => Entry::Vacant(e),
+                }
+            }
+        }
+    };
+}
@@ -0,0 +1,279 @@
+//! A small self-describing binary format for saving a map without serde.
+//!
+//! The layout is the magic bytes `HMAP`, a version byte, the entry count as
+//! a little-endian `u64`, and then every key followed by its value, each
+//! encoded by its [`ToBytes`] impl.
+
+use std::error::Error;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Read, Write};
+
+use crate::HashMap;
+
+const MAGIC: &[u8; 4] = b"HMAP";
+const VERSION: u8 = 1;
+
+// Length prefixes come from the input, so never trust them for allocation
+// sizes beyond this; larger payloads still load, just incrementally.
+const MAX_PREALLOC: usize = 4096;
+
+/// Types that can be written in the persistence format.
+pub trait ToBytes {
+    fn write_bytes<W: Write>(&self, w: &mut W) -> io::Result<()>;
+}
+
+/// Types that can be read back from the persistence format.
+pub trait FromBytes: Sized {
+    fn read_bytes<R: Read>(r: &mut R) -> Result<Self, ReadError>;
+}
+
+/// The error returned by [`HashMap::read_from`].
+#[derive(Debug)]
+pub enum ReadError {
+    /// The input ended in the middle of the data.
+    Truncated,
+    /// The input does not start with the expected magic bytes.
+    BadMagic,
+    /// The input was written by an unknown version of the format.
+    UnsupportedVersion(u8),
+    /// A string was not valid UTF-8.
+    InvalidUtf8,
+    /// A length does not fit in memory on this platform.
+    LengthOverflow(u64),
+    /// Reading failed for some other reason.
+    Io(io::Error),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadError::Truncated => f.write_str("input ended unexpectedly"),
+            ReadError::BadMagic => f.write_str("input is not a serialized HashMap (bad magic bytes)"),
+            ReadError::UnsupportedVersion(version) => {
+                write!(f, "unsupported format version {} (expected {})", version, VERSION)
+            }
+            ReadError::InvalidUtf8 => f.write_str("string is not valid UTF-8"),
+            ReadError::LengthOverflow(len) => write!(f, "length {} is too large", len),
+            ReadError::Io(err) => write!(f, "read failed: {}", err),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ReadError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => ReadError::Truncated,
+            _ => ReadError::Io(err),
+        }
+    }
+}
+
+macro_rules! int_bytes {
+    ($($ty:ty)*) => {$(
+        impl ToBytes for $ty {
+            fn write_bytes<W: Write>(&self, w: &mut W) -> io::Result<()> {
+                w.write_all(&self.to_le_bytes())
+            }
+        }
+
+        impl FromBytes for $ty {
+            fn read_bytes<R: Read>(r: &mut R) -> Result<Self, ReadError> {
+                let mut buf = [0; std::mem::size_of::<$ty>()];
+                r.read_exact(&mut buf)?;
+                Ok(<$ty>::from_le_bytes(buf))
+            }
+        }
+    )*};
+}
+
+int_bytes!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128);
+
+// `usize`/`isize` are always written as 64 bits so files move between
+// platforms.
+impl ToBytes for usize {
+    fn write_bytes<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as u64).write_bytes(w)
+    }
+}
+
+impl FromBytes for usize {
+    fn read_bytes<R: Read>(r: &mut R) -> Result<Self, ReadError> {
+        let n = u64::read_bytes(r)?;
+        usize::try_from(n).map_err(|_| ReadError::LengthOverflow(n))
+    }
+}
+
+impl ToBytes for isize {
+    fn write_bytes<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        (*self as i64).write_bytes(w)
+    }
+}
+
+impl FromBytes for isize {
+    fn read_bytes<R: Read>(r: &mut R) -> Result<Self, ReadError> {
+        let n = i64::read_bytes(r)?;
+        isize::try_from(n).map_err(|_| ReadError::LengthOverflow(n as u64))
+    }
+}
+
+impl ToBytes for Vec<u8> {
+    fn write_bytes<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.len().write_bytes(w)?;
+        w.write_all(self)
+    }
+}
+
+impl FromBytes for Vec<u8> {
+    fn read_bytes<R: Read>(r: &mut R) -> Result<Self, ReadError> {
+        let len = usize::read_bytes(r)?;
+        let mut buf = Vec::with_capacity(len.min(MAX_PREALLOC));
+        r.take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            return Err(ReadError::Truncated);
+        }
+        Ok(buf)
+    }
+}
+
+impl ToBytes for String {
+    fn write_bytes<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        self.len().write_bytes(w)?;
+        w.write_all(self.as_bytes())
+    }
+}
+
+impl FromBytes for String {
+    fn read_bytes<R: Read>(r: &mut R) -> Result<Self, ReadError> {
+        String::from_utf8(Vec::read_bytes(r)?).map_err(|_| ReadError::InvalidUtf8)
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: ToBytes,
+    V: ToBytes,
+{
+    /// Writes the map in the persistence format described in the module docs.
+    pub fn write_to<W: Write>(&self, mut w: W) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        self.len().write_bytes(&mut w)?;
+        for (key, value) in self {
+            key.write_bytes(&mut w)?;
+            value.write_bytes(&mut w)?;
+        }
+        w.flush()
+    }
+}
+
+impl<K, V, S> HashMap<K, V, S>
+where
+    K: FromBytes + Hash + Eq,
+    V: FromBytes,
+    S: BuildHasher + Default,
+{
+    /// Reads a map written by [`write_to`](HashMap::write_to). Malformed
+    /// input of any kind is reported as a [`ReadError`].
+    pub fn read_from<R: Read>(mut r: R) -> Result<Self, ReadError> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(ReadError::BadMagic);
+        }
+        let version = u8::read_bytes(&mut r)?;
+        if version != VERSION {
+            return Err(ReadError::UnsupportedVersion(version));
+        }
+
+        let len = usize::read_bytes(&mut r)?;
+        let mut map = HashMap::with_capacity_and_hasher(len.min(MAX_PREALLOC), S::default());
+        for _ in 0..len {
+            let key = K::read_bytes(&mut r)?;
+            let value = V::read_bytes(&mut r)?;
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> HashMap<String, Vec<u8>> {
+        (0..100).map(|i| (i.to_string(), vec![i as u8; i])).collect()
+    }
+
+    fn bytes_of<K: ToBytes, V: ToBytes>(map: &HashMap<K, V>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        map.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let map = sample();
+        let back = HashMap::<String, Vec<u8>>::read_from(&bytes_of(&map)[..]).unwrap();
+        assert_eq!(back, map);
+
+        let map: HashMap<u64, i32> = (0..1000).map(|i| (i, -(i as i32))).collect();
+        let back = HashMap::<u64, i32>::read_from(&bytes_of(&map)[..]).unwrap();
+        assert_eq!(back, map);
+    }
+
+    #[test]
+    fn round_trip_empty() {
+        let map: HashMap<u8, u8> = HashMap::new();
+        let bytes = bytes_of(&map);
+        assert_eq!(bytes.len(), 4 + 1 + 8);
+        assert!(HashMap::<u8, u8>::read_from(&bytes[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = bytes_of(&sample());
+        for len in [0, 3, 5, 12, bytes.len() / 2, bytes.len() - 1] {
+            let err = HashMap::<String, Vec<u8>>::read_from(&bytes[..len]).unwrap_err();
+            assert!(matches!(err, ReadError::Truncated), "{}: {}", len, err);
+        }
+    }
+
+    #[test]
+    fn bad_header() {
+        let mut bytes = bytes_of(&sample());
+        bytes[4] = VERSION + 1;
+        let err = HashMap::<String, Vec<u8>>::read_from(&bytes[..]).unwrap_err();
+        assert!(matches!(err, ReadError::UnsupportedVersion(2)));
+        assert!(err.to_string().contains("version 2"));
+
+        bytes[0] = b'X';
+        let err = HashMap::<String, Vec<u8>>::read_from(&bytes[..]).unwrap_err();
+        assert!(matches!(err, ReadError::BadMagic));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let map = HashMap::from([(vec![0xff, 0xfe], 1u8)]);
+        let err = HashMap::<String, u8>::read_from(&bytes_of(&map)[..]).unwrap_err();
+        assert!(matches!(err, ReadError::InvalidUtf8));
+    }
+
+    #[test]
+    fn huge_length_prefix() {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        let err = HashMap::<u8, u8>::read_from(&bytes[..]).unwrap_err();
+        assert!(matches!(err, ReadError::Truncated | ReadError::LengthOverflow(_)));
+    }
+}
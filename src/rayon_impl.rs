@@ -0,0 +1,240 @@
+//! Parallel iteration with rayon.
+//!
+//! Work is split over the entry arena: rayon divides the entry vector
+//! directly, so splitting costs nothing beyond slicing and the chains never
+//! need to be walked.
+
+use std::collections::LinkedList;
+use std::fmt;
+use std::hash::{BuildHasher, Hash};
+
+use rayon::iter::plumbing::UnindexedConsumer;
+use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator};
+use rayon::prelude::*;
+
+use crate::HashMap;
+
+/// A parallel iterator over the entries of a `HashMap`.
+///
+/// This `struct` is created by `par_iter` on a `HashMap`.
+pub struct ParIter<'a, K, V> {
+    entries: &'a [(u64, K, V)],
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for ParIter<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.entries.iter().map(|(_, k, v)| (k, v))).finish()
+    }
+}
+
+impl<'a, K: Sync, V: Sync> ParallelIterator for ParIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.entries
+            .par_iter()
+            .map(|(_, k, v)| (k, v))
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'a, K: Sync, V: Sync, S> IntoParallelIterator for &'a HashMap<K, V, S> {
+    type Iter = ParIter<'a, K, V>;
+    type Item = (&'a K, &'a V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        ParIter { entries: &self.entries }
+    }
+}
+
+/// A parallel iterator over the entries of a `HashMap`, with mutable
+/// references to the values.
+///
+/// This `struct` is created by `par_iter_mut` on a `HashMap`.
+pub struct ParIterMut<'a, K, V> {
+    entries: &'a mut [(u64, K, V)],
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for ParIterMut<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.entries.iter().map(|(_, k, v)| (k, v))).finish()
+    }
+}
+
+impl<'a, K: Send + Sync, V: Send> ParallelIterator for ParIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.entries
+            .par_iter_mut()
+            .map(|(_, k, v)| (&*k, v))
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<'a, K: Send + Sync, V: Send, S> IntoParallelIterator for &'a mut HashMap<K, V, S> {
+    type Iter = ParIterMut<'a, K, V>;
+    type Item = (&'a K, &'a mut V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        ParIterMut { entries: &mut self.entries }
+    }
+}
+
+/// An owning parallel iterator over the entries of a `HashMap`.
+///
+/// This `struct` is created by `into_par_iter` on a `HashMap`.
+pub struct IntoParIter<K, V> {
+    entries: Vec<(u64, K, V)>,
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for IntoParIter<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.entries.iter().map(|(_, k, v)| (k, v))).finish()
+    }
+}
+
+impl<K: Send, V: Send> ParallelIterator for IntoParIter<K, V> {
+    type Item = (K, V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.entries
+            .into_par_iter()
+            .map(|(_, k, v)| (k, v))
+            .drive_unindexed(consumer)
+    }
+}
+
+impl<K: Send, V: Send, S> IntoParallelIterator for HashMap<K, V, S> {
+    type Iter = IntoParIter<K, V>;
+    type Item = (K, V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        IntoParIter { entries: self.entries }
+    }
+}
+
+impl<K, V, S> FromParallelIterator<(K, V)> for HashMap<K, V, S>
+where
+    K: Hash + Eq + Send,
+    V: Send,
+    S: BuildHasher + Default,
+{
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+    {
+        let mut map = HashMap::with_hasher(S::default());
+        map.par_extend(par_iter);
+        map
+    }
+}
+
+impl<K, V, S> ParallelExtend<(K, V)> for HashMap<K, V, S>
+where
+    K: Hash + Eq + Send,
+    V: Send,
+    S: BuildHasher,
+{
+    /// Each rayon task buffers its pairs in a `Vec` and the buffers are
+    /// inserted in iteration order, so a key that appears more than once
+    /// ends up with its last value, just like the sequential `extend`.
+    fn par_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+    {
+        let chunks: LinkedList<Vec<(K, V)>> = par_iter
+            .into_par_iter()
+            .fold(Vec::new, |mut chunk, pair| {
+                chunk.push(pair);
+                chunk
+            })
+            .map(|chunk| LinkedList::from([chunk]))
+            .reduce(LinkedList::new, |mut left, mut right| {
+                left.append(&mut right);
+                left
+            });
+
+        self.reserve(chunks.iter().map(Vec::len).sum());
+        for chunk in chunks {
+            self.extend(chunk);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn large() -> HashMap<u64, u64> {
+        (0..100_000).map(|i| (i, i * 3)).collect()
+    }
+
+    fn sorted<T: Ord>(mut v: Vec<T>) -> Vec<T> {
+        v.sort_unstable();
+        v
+    }
+
+    #[test]
+    fn par_sum_matches_sequential() {
+        let map = large();
+        let sequential: u64 = map.values().sum();
+        let parallel: u64 = map.par_iter().map(|(_, v)| *v).sum();
+        assert_eq!(parallel, sequential);
+        assert_eq!(map.par_iter().count(), map.len());
+    }
+
+    #[test]
+    fn par_iter_same_pairs() {
+        let map = large();
+        let sequential = sorted(map.iter().collect());
+        let parallel = sorted(map.par_iter().collect());
+        assert_eq!(parallel, sequential);
+
+        let empty: HashMap<u64, u64> = HashMap::new();
+        assert_eq!(empty.par_iter().count(), 0);
+    }
+
+    #[test]
+    fn par_iter_mut() {
+        let mut map = large();
+        map.par_iter_mut().for_each(|(k, v)| *v += k);
+        assert!(map.iter().all(|(k, v)| *v == k * 4));
+    }
+
+    #[test]
+    fn into_par_iter() {
+        let map = large();
+        let sequential = sorted(map.clone().into_iter().collect());
+        let parallel = sorted(map.into_par_iter().collect());
+        assert_eq!(parallel, sequential);
+    }
+
+    #[test]
+    fn par_collect_matches_sequential() {
+        let pairs: Vec<(u64, u64)> = (0..300_000).map(|i| (i % 250_000, i)).collect();
+        let sequential: HashMap<u64, u64> = pairs.iter().copied().collect();
+        let parallel: HashMap<u64, u64> = pairs.par_iter().copied().collect();
+        assert_eq!(parallel.len(), 250_000);
+        assert_eq!(parallel, sequential);
+        // The later duplicate wins, as in the sequential build.
+        assert_eq!(parallel[&0], 250_000);
+    }
+
+    #[test]
+    fn par_extend_overwrites() {
+        let mut map: HashMap<u64, &str> = (0..1000).map(|i| (i, "old")).collect();
+        map.par_extend((500..1500u64).into_par_iter().map(|i| (i, "new")));
+        assert_eq!(map.len(), 1500);
+        assert!(map.iter().all(|(&k, &v)| v == if k < 500 { "old" } else { "new" }));
+    }
+}
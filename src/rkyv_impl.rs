@@ -0,0 +1,106 @@
+//! Archives a `HashMap` as rkyv's own `ArchivedHashMap`, so an archived map
+//! can be validated and queried in place, without deserializing it first.
+
+use core::hash::{BuildHasher, Hash};
+
+use rkyv::collections::swiss_table::map::{ArchivedHashMap, HashMapResolver};
+use rkyv::rancor::{Fallible, Source};
+use rkyv::ser::{Allocator, Writer};
+use rkyv::{Archive, Deserialize, Place, Serialize};
+
+use crate::HashMap;
+
+// load factor of the archived table, as a fraction
+const LOAD_FACTOR: (usize, usize) = (7, 8);
+
+impl<K, V, H> Archive for HashMap<K, V, H>
+where
+    K: Archive + Hash + Eq,
+    K::Archived: Hash + Eq,
+    V: Archive,
+{
+    type Archived = ArchivedHashMap<K::Archived, V::Archived>;
+    type Resolver = HashMapResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        ArchivedHashMap::resolve_from_len(self.len(), LOAD_FACTOR, resolver, out);
+    }
+}
+
+impl<K, V, H, S> Serialize<S> for HashMap<K, V, H>
+where
+    K: Serialize<S> + Hash + Eq,
+    K::Archived: Hash + Eq,
+    V: Serialize<S>,
+    S: Fallible + Writer + Allocator + ?Sized,
+    S::Error: Source,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ArchivedHashMap::<K::Archived, V::Archived>::serialize_from_iter::<_, _, _, K, V, _>(
+            self.iter(),
+            LOAD_FACTOR,
+            serializer,
+        )
+    }
+}
+
+impl<K, V, H, D> Deserialize<HashMap<K, V, H>, D> for ArchivedHashMap<K::Archived, V::Archived>
+where
+    K: Archive + Hash + Eq,
+    K::Archived: Deserialize<K, D> + Hash + Eq,
+    V: Archive,
+    V::Archived: Deserialize<V, D>,
+    H: BuildHasher + Default,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<HashMap<K, V, H>, D::Error> {
+        let mut map = HashMap::with_capacity_and_hasher(self.len(), H::default());
+        for (key, value) in self.iter() {
+            map.insert(key.deserialize(deserializer)?, value.deserialize(deserializer)?);
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use rkyv::rancor::Error;
+    use rkyv::string::ArchivedString;
+
+    use super::*;
+
+    type Archived = ArchivedHashMap<ArchivedString, rkyv::Archived<u64>>;
+
+    #[test]
+    fn round_trip() {
+        let map: HashMap<String, u64> = (0..10_000).map(|i| (i.to_string(), i)).collect();
+        let bytes = rkyv::to_bytes::<Error>(&map).unwrap();
+
+        let archived = rkyv::access::<Archived, Error>(&bytes).unwrap();
+        assert_eq!(archived.len(), map.len());
+        assert_eq!(archived.get("1234").map(|v| v.to_native()), Some(1234));
+        assert!(archived.get("10000").is_none());
+
+        let back: HashMap<String, u64> = rkyv::deserialize::<_, Error>(archived).unwrap();
+        assert_eq!(back, map);
+    }
+
+    #[test]
+    fn empty() {
+        let map: HashMap<String, u64> = HashMap::new();
+        let bytes = rkyv::to_bytes::<Error>(&map).unwrap();
+        let archived = rkyv::access::<Archived, Error>(&bytes).unwrap();
+        assert_eq!(archived.len(), 0);
+        let back: HashMap<String, u64> = rkyv::deserialize::<_, Error>(archived).unwrap();
+        assert!(back.is_empty());
+    }
+
+    #[test]
+    fn corrupt_bytes_rejected() {
+        let map: HashMap<String, u64> = (0..100).map(|i| (i.to_string(), i)).collect();
+        let mut bytes = rkyv::to_bytes::<Error>(&map).unwrap().to_vec();
+        let len = bytes.len();
+        bytes[len - 4..].copy_from_slice(&[0xff; 4]);
+        assert!(rkyv::access::<Archived, Error>(&bytes).is_err());
+    }
+}
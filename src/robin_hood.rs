@@ -0,0 +1,631 @@
+//! An open-addressing map with Robin Hood probing.
+//!
+//! [`RobinHoodMap`] is laid out like a map on a
+//! [`LinearTable`](crate::LinearTable): a dense entry arena, found through
+//! a table of slots that cache each entry's hash. It differs in where
+//! entries end up. Every slot also records its entry's probe-sequence
+//! length (how far past its home slot it sits), and an insertion that
+//! meets an entry closer to home than itself takes that slot, pushing the
+//! rest of the run one step along. No entry can then sit much further from
+//! home than any other, however the keys cluster.
+//!
+//! The same order lets lookups stop early, at the first entry closer to
+//! home than the probe, and lets removal shift the run after it back by
+//! one instead of leaving a tombstone.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::linear::{capacity_of, slots_for, MIN_SLOTS};
+use crate::{
+    bucket_index, make_hash, DefaultState, Drain, Equivalent, HashMap, IntoIter, IntoKeys,
+    IntoValues, Iter, IterMut, Keys, Values, ValuesMut,
+};
+
+#[derive(Clone, Copy, Debug)]
+enum Slot {
+    Empty,
+    /// `psl` is the number of slots between the entry and its home slot.
+    Full { hash: u64, index: usize, psl: usize },
+}
+
+/// A hash map that resolves collisions by Robin Hood probing.
+///
+/// It offers the core of [`HashMap`]'s API, with the
+/// crate's iterators and an entry API of its own in this module:
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::RobinHoodMap;
+///
+/// let mut map = RobinHoodMap::new();
+/// map.insert("a", 1);
+/// *map.entry("b").or_insert(0) += 2;
+/// assert_eq!(map.get("a"), Some(&1));
+/// assert_eq!(map.remove("b"), Some(2));
+/// assert_eq!(map.len(), 1);
+/// ```
+pub struct RobinHoodMap<K, V, S = DefaultState> {
+    entries: Vec<(u64, K, V)>,
+    slots: Vec<Slot>,
+    hash_builder: S,
+}
+
+#[cfg(feature = "std")]
+impl<K, V> RobinHoodMap<K, V, DefaultState> {
+    pub fn new() -> Self {
+        RobinHoodMap::with_hasher(DefaultState::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        RobinHoodMap::with_capacity_and_hasher(capacity, DefaultState::default())
+    }
+}
+
+impl<K, V, S> RobinHoodMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        RobinHoodMap {
+            entries: Vec::new(),
+            slots: Vec::new(),
+            hash_builder,
+        }
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let nslots = slots_for(capacity).expect("capacity overflow");
+        RobinHoodMap {
+            entries: Vec::with_capacity(capacity),
+            slots: alloc::vec![Slot::Empty; nslots],
+            hash_builder,
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Number of entries the map can hold before its table grows.
+    pub fn capacity(&self) -> usize {
+        capacity_of(self.slots.len())
+    }
+
+    /// Furthest any entry sits from its home slot.
+    pub fn max_probe_length(&self) -> usize {
+        self.slots
+            .iter()
+            .map(|slot| match *slot {
+                Slot::Empty => 0,
+                Slot::Full { psl, .. } => psl,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.slots.fill(Slot::Empty);
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let len = self.len();
+        self.entries.retain_mut(|(_, ekey, evalue)| f(ekey, evalue));
+        if self.len() != len {
+            self.rebuild(self.slots.len());
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { inner: self.entries.iter() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut { inner: self.entries.iter_mut() }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { inner: self.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { inner: self.iter_mut() }
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        IntoKeys { inner: self.into_iter() }
+    }
+
+    pub fn into_values(self) -> IntoValues<K, V> {
+        IntoValues { inner: self.into_iter() }
+    }
+
+    /// Removes every entry, handing them out by value. The table keeps its
+    /// size, as with [`clear`](RobinHoodMap::clear).
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        self.slots.fill(Slot::Empty);
+        Drain {
+            inner: self.entries.drain(..),
+            marker: PhantomData,
+        }
+    }
+
+    /// Slots probed for `hash`, each with the probe length an entry there
+    /// would have: its home slot, then each following one, wrapping around
+    /// the end of the table. The table must be allocated.
+    fn probe(&self, hash: u64) -> impl Iterator<Item = (usize, usize)> {
+        let nslots = self.slots.len();
+        let home = bucket_index(hash, nslots);
+        (0..nslots).map(move |psl| (psl, (home + psl) & (nslots - 1)))
+    }
+
+    /// Slot and arena index of the entry with this `hash` whose key
+    /// `is_match` accepts, or else the slot a new entry for it belongs in,
+    /// along with its probe length there. The table must be allocated and
+    /// have room for one more entry.
+    fn find_slot<F>(&self, hash: u64, mut is_match: F) -> Result<(usize, usize), (usize, usize)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        for (psl, slot) in self.probe(hash) {
+            match self.slots[slot] {
+                Slot::Empty => return Err((slot, psl)),
+                // had the key been inserted, it would have taken this slot
+                Slot::Full { psl: spsl, .. } if spsl < psl => return Err((slot, psl)),
+                Slot::Full { hash: shash, index, .. } => {
+                    if shash == hash && is_match(&self.entries[index].1) {
+                        return Ok((slot, index));
+                    }
+                }
+            }
+        }
+        unreachable!("robin hood probe found no free slot")
+    }
+
+    /// Like `find_slot`, but for lookups, which also work on an empty map.
+    fn find<F>(&self, hash: u64, is_match: F) -> Option<(usize, usize)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        self.find_slot(hash, is_match).ok()
+    }
+
+    /// Slot that points at `entries[index]`.
+    fn slot_of(&self, index: usize) -> usize {
+        self.probe(self.entries[index].0)
+            .map(|(_, slot)| slot)
+            .find(|&slot| matches!(self.slots[slot], Slot::Full { index: i, .. } if i == index))
+            .expect("entry missing from the slot table")
+    }
+
+    /// Puts `carry` in `slot`. Whatever was there moves on to the next
+    /// slot, one step further from home, and so on until an entry lands in
+    /// an empty slot.
+    fn displace(&mut self, mut slot: usize, mut carry: Slot) {
+        let mask = self.slots.len() - 1;
+        loop {
+            carry = mem::replace(&mut self.slots[slot], carry);
+            match &mut carry {
+                Slot::Empty => return,
+                Slot::Full { psl, .. } => *psl += 1,
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+
+    /// Adds an entry in `slot`, where `find_slot` said it belongs, and
+    /// returns its arena index.
+    fn push_entry(&mut self, slot: usize, psl: usize, hash: u64, key: K, value: V) -> usize {
+        let index = self.entries.len();
+        self.displace(slot, Slot::Full { hash, index, psl });
+        self.entries.push((hash, key, value));
+        index
+    }
+
+    /// Removes the entry at arena `index`, which `slot` points at, shifting
+    /// the entries after it that aren't in their home slot back by one. The
+    /// last entry is swapped into its place in the arena, and its slot
+    /// updated.
+    fn remove_at(&mut self, slot: usize, index: usize) -> (u64, K, V) {
+        let mask = self.slots.len() - 1;
+        let mut hole = slot;
+        loop {
+            let next = (hole + 1) & mask;
+            match self.slots[next] {
+                Slot::Full { hash, index, psl } if psl > 0 => {
+                    self.slots[hole] = Slot::Full { hash, index, psl: psl - 1 };
+                    hole = next;
+                }
+                _ => break,
+            }
+        }
+        self.slots[hole] = Slot::Empty;
+
+        let last = self.entries.len() - 1;
+        if index != last {
+            let moved = self.slot_of(last);
+            if let Slot::Full { index: i, .. } = &mut self.slots[moved] {
+                *i = index;
+            }
+        }
+        self.entries.swap_remove(index)
+    }
+
+    fn reserve_one(&mut self) {
+        if self.len() >= self.capacity() {
+            self.rebuild((self.slots.len() * 2).max(MIN_SLOTS));
+        }
+    }
+
+    /// Lays the arena out over `nslots` fresh slots. Hashes are cached, so
+    /// no key is hashed again.
+    fn rebuild(&mut self, nslots: usize) {
+        debug_assert!(capacity_of(nslots) >= self.len());
+        self.slots.clear();
+        self.slots.resize(nslots, Slot::Empty);
+        for index in 0..self.entries.len() {
+            let hash = self.entries[index].0;
+            let (slot, psl) = self
+                .probe(hash)
+                .find(|&(psl, slot)| match self.slots[slot] {
+                    Slot::Empty => true,
+                    Slot::Full { psl: spsl, .. } => spsl < psl,
+                })
+                .map(|(psl, slot)| (slot, psl))
+                .expect("robin hood probe found no free slot");
+            self.displace(slot, Slot::Full { hash, index, psl });
+        }
+    }
+}
+
+impl<K, V, S> RobinHoodMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn lookup<Q>(&self, key: &Q) -> Option<(usize, usize)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.find(make_hash(&self.hash_builder, key), |ekey| key.equivalent(ekey))
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        self.reserve_one();
+        let hash = make_hash(&self.hash_builder, &key);
+        match self.find_slot(hash, |ekey| ekey == &key) {
+            Ok((slot, index)) => Entry::Occupied(OccupiedEntry { map: self, slot, index }),
+            Err((slot, psl)) => Entry::Vacant(VacantEntry { key, hash, map: self, slot, psl }),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => Some(e.insert(value)),
+            Entry::Vacant(e) => {
+                e.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let (_, index) = self.lookup(key)?;
+        let (_, ekey, evalue) = &self.entries[index];
+        Some((ekey, evalue))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let (_, index) = self.lookup(key)?;
+        Some(&mut self.entries[index].2)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let (slot, index) = self.lookup(key)?;
+        let (_, ekey, evalue) = self.remove_at(slot, index);
+        Some((ekey, evalue))
+    }
+
+    /// Makes room for at least `additional` more entries.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len().checked_add(additional).expect("capacity overflow");
+        self.entries.reserve(additional);
+        if needed > self.capacity() {
+            let nslots = slots_for(needed).expect("capacity overflow");
+            self.rebuild(nslots.max(self.slots.len()));
+        }
+    }
+
+    /// Shrinks the table as far as the current entries allow.
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+        self.rebuild(slots_for(self.len()).expect("capacity overflow"));
+        self.slots.shrink_to_fit();
+    }
+}
+
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    map: &'a mut RobinHoodMap<K, V, S>,
+    slot: usize,
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    key: K,
+    hash: u64,
+    map: &'a mut RobinHoodMap<K, V, S>,
+    slot: usize,
+    psl: usize,
+}
+
+/// A view into a single entry of a [`RobinHoodMap`], from
+/// [`RobinHoodMap::entry`].
+pub enum Entry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+impl<'a, K: 'a, V: 'a, S> OccupiedEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.map.entries[self.index].1
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.entries[self.index].2
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.entries[self.index].2
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.entries[self.index].2
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        let (_, ekey, evalue) = self.map.remove_at(self.slot, self.index);
+        (ekey, evalue)
+    }
+}
+
+impl<'a, K: 'a, V: 'a, S> VacantEntry<'a, K, V, S> {
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S> {
+        let index = self.map.push_entry(self.slot, self.psl, self.hash, self.key, value);
+        OccupiedEntry {
+            map: self.map,
+            slot: self.slot,
+            index,
+        }
+    }
+}
+
+entry_impls!();
+
+impl<K: Clone, V: Clone, S: Clone> Clone for RobinHoodMap<K, V, S> {
+    fn clone(&self) -> Self {
+        RobinHoodMap {
+            entries: self.entries.clone(),
+            slots: self.slots.clone(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+map_impls!(RobinHoodMap);
+
+impl<K, V, S> IntoIterator for RobinHoodMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.entries.into_iter(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::test_util::{differential, sorted, Colliding};
+    use crate::{BuildNoHashHasher, LinearMap, LinearTable};
+
+    // Keys hash in pairs to the same value.
+    type Clustering = crate::test_util::Clustering<2>;
+
+    /// Every entry is pointed at by exactly one slot, holding its true
+    /// probe length, and no run is out of Robin Hood order.
+    fn check_table<K, V, S>(map: &RobinHoodMap<K, V, S>) {
+        let nslots = map.slots.len();
+        let mut seen = vec![false; map.len()];
+        for slot in 0..nslots {
+            if let Slot::Full { hash, index, psl } = map.slots[slot] {
+                assert_eq!(map.entries[index].0, hash);
+                assert!(!mem::replace(&mut seen[index], true));
+                assert_eq!((bucket_index(hash, nslots) + psl) & (nslots - 1), slot);
+                // the entry before is at most one step closer to home, or
+                // the entry couldn't have been pushed this far
+                let before = map.slots[(slot + nslots - 1) & (nslots - 1)];
+                if psl > 0 {
+                    assert!(matches!(before, Slot::Full { psl: b, .. } if b + 1 >= psl));
+                }
+            }
+        }
+        assert!(seen.into_iter().all(|pointed_at| pointed_at));
+    }
+
+    #[test]
+    fn differential_against_chaining() {
+        for seed in 0..8 {
+            differential(seed, 64, RobinHoodMap::new(), check_table);
+            differential(seed, 4096, RobinHoodMap::new(), check_table);
+        }
+    }
+
+    #[test]
+    fn differential_with_poor_hashers() {
+        for seed in 0..4 {
+            differential(seed, 200, RobinHoodMap::with_hasher(Colliding::default()), check_table);
+            differential(seed, 2000, RobinHoodMap::with_hasher(Clustering::default()), check_table);
+        }
+    }
+
+    #[test]
+    fn clustered_keys_keep_probes_short() {
+        let mut robin_hood = RobinHoodMap::with_hasher(Clustering::default());
+        let mut linear = LinearMap::with_table_and_hasher(LinearTable::new(), Clustering::default());
+        // close to the point where both tables would grow
+        for i in 0..24_000u32 {
+            robin_hood.insert(i, i);
+            linear.insert(i, i);
+        }
+        check_table(&robin_hood);
+        let (rh, lp) = (robin_hood.max_probe_length(), linear.max_probe_length());
+        assert!(rh * 3 < lp, "robin hood {} vs linear {}", rh, lp);
+
+        // removals shift runs back, so the bound holds through churn too
+        for i in (0..24_000).step_by(3) {
+            robin_hood.remove(&i);
+        }
+        for i in 24_000..32_000 {
+            robin_hood.insert(i, i);
+        }
+        check_table(&robin_hood);
+        assert!(robin_hood.max_probe_length() <= rh + 16);
+    }
+
+    #[test]
+    fn backward_shift_on_remove() {
+        let mut map = RobinHoodMap::with_hasher(Colliding::default());
+        for i in 0..6 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.max_probe_length(), 5);
+        assert_eq!(map.remove(&0), Some(0));
+        // no tombstone: the run moved up and the last slot emptied
+        assert_eq!(map.max_probe_length(), 4);
+        check_table(&map);
+        assert!((1..6).all(|i| map[&i] == i));
+        for i in 1..6 {
+            map.remove(&i);
+            check_table(&map);
+        }
+        assert!(map.slots.iter().all(|slot| matches!(slot, Slot::Empty)));
+    }
+
+    #[test]
+    fn insert_displaces_richer_entries() {
+        let mut map: RobinHoodMap<u64, u64, BuildNoHashHasher<u64>> =
+            RobinHoodMap::with_capacity_and_hasher(40, Default::default());
+        let nslots = map.slots.len();
+        let home = |key: u64| bucket_index(key, nslots);
+        let h = home(0);
+        let first: Vec<u64> = (0..).filter(|&k| home(k) == h).take(8).collect();
+        let second: Vec<u64> = (0..).filter(|&k| home(k) == (h + 1) & (nslots - 1)).take(8).collect();
+
+        // the later run settles in first; the earlier one, homed a slot
+        // before it, still ends up in front by displacing it
+        for &key in second.iter().chain(&first) {
+            map.insert(key, key);
+        }
+        check_table(&map);
+        let keys: Vec<u64> = (0..16)
+            .map(|step| match map.slots[(h + step) & (nslots - 1)] {
+                Slot::Full { index, .. } => map.entries[index].1,
+                Slot::Empty => panic!("run broken at step {}", step),
+            })
+            .collect();
+        assert!(keys[..8].iter().all(|k| first.contains(k)));
+        assert!(keys[8..].iter().all(|k| second.contains(k)));
+        // linear probing would have left the last of `first` 15 slots out
+        assert_eq!(map.max_probe_length(), 14);
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut map = RobinHoodMap::new();
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(map["the"], 3);
+        match map.entry("fox") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.insert(10), 1);
+                assert_eq!(e.remove_entry(), ("fox", 10));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry("fox") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(e) => assert_eq!(e.insert_entry(5).get(), &5),
+        }
+        map.entry("end").and_modify(|v| *v += 1).or_insert(0);
+        assert_eq!(map["end"], 2);
+        assert_eq!(*map.entry("zero").or_insert_default(), 0);
+        check_table(&map);
+    }
+
+    #[test]
+    fn parity_with_linear_map() {
+        let linear: LinearMap<u32, u32> = (0..1000).map(|i| (i, i * 3)).collect();
+        let mut map: RobinHoodMap<u32, u32> = linear.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(sorted(map.iter()), sorted(linear.iter()));
+        map.retain(|&k, _| k % 2 == 0);
+        map.reserve(5000);
+        check_table(&map);
+        map.shrink_to_fit();
+        check_table(&map);
+        assert_eq!(map.len(), 500);
+        assert_eq!(map.clone().into_keys().count(), 500);
+        assert_eq!(map.drain().map(|(_, v)| v).sum::<u32>(), (0..1000).step_by(2).map(|k| k * 3).sum::<u32>());
+        assert!(map.is_empty());
+        assert_eq!(format!("{:?}", RobinHoodMap::from([(1, 2)])), "{1: 2}");
+    }
+}
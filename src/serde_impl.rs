@@ -0,0 +1,335 @@
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+use crate::{DefaultState, Entry, HashMap};
+
+// Don't trust the length a (possibly hostile) input claims up front; beyond
+// this the map grows as usual while entries actually arrive.
+const MAX_PREALLOC: usize = 4096;
+
+impl<K, V, H> Serialize for HashMap<K, V, H>
+where
+    K: Serialize,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer
+    {
+        serializer.collect_map(self.iter())
+    }
+}
+
+struct HashMapVisitor<K, V, H> {
+    marker: PhantomData<HashMap<K, V, H>>,
+}
+
+impl<'de, K, V, H> Visitor<'de> for HashMapVisitor<K, V, H>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    H: BuildHasher + Default,
+{
+    type Value = HashMap<K, V, H>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>
+    {
+        let capacity = access.size_hint().unwrap_or(0).min(MAX_PREALLOC);
+        let mut map = HashMap::with_capacity_and_hasher(capacity, H::default());
+        // later duplicates overwrite earlier ones, like std
+        while let Some((key, value)) = access.next_entry()? {
+            map.insert(key, value);
+        }
+        Ok(map)
+    }
+}
+
+impl<'de, K, V, H> Deserialize<'de> for HashMap<K, V, H>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    H: BuildHasher + Default,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_map(HashMapVisitor { marker: PhantomData })
+    }
+}
+
+/// What to do when a serialized map contains the same key more than once.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DuplicatePolicy {
+    /// Keep the value that appears last, like plain deserialization does.
+    #[default]
+    LastWins,
+    /// Keep the value that appears first and ignore later ones.
+    FirstWins,
+    /// Fail with an error naming the duplicated key.
+    Error,
+}
+
+/// A [`DeserializeSeed`] that builds a [`HashMap`] under a chosen
+/// [`DuplicatePolicy`], e.g.
+/// `DeserializeWithPolicy::new(DuplicatePolicy::Error).deserialize(&mut de)`.
+pub struct DeserializeWithPolicy<K, V, H = DefaultState> {
+    policy: DuplicatePolicy,
+    marker: PhantomData<HashMap<K, V, H>>,
+}
+
+impl<K, V, H> DeserializeWithPolicy<K, V, H> {
+    pub fn new(policy: DuplicatePolicy) -> Self {
+        DeserializeWithPolicy {
+            policy,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, K, V, H> DeserializeSeed<'de> for DeserializeWithPolicy<K, V, H>
+where
+    K: Deserialize<'de> + Hash + Eq + fmt::Debug,
+    V: Deserialize<'de>,
+    H: BuildHasher + Default,
+{
+    type Value = HashMap<K, V, H>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, K, V, H> Visitor<'de> for DeserializeWithPolicy<K, V, H>
+where
+    K: Deserialize<'de> + Hash + Eq + fmt::Debug,
+    V: Deserialize<'de>,
+    H: BuildHasher + Default,
+{
+    type Value = HashMap<K, V, H>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map")
+    }
+
+    fn visit_map<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>
+    {
+        let capacity = access.size_hint().unwrap_or(0).min(MAX_PREALLOC);
+        let mut map = HashMap::with_capacity_and_hasher(capacity, H::default());
+        while let Some((key, value)) = access.next_entry()? {
+            match map.entry(key) {
+                Entry::Vacant(e) => {
+                    e.insert(value);
+                },
+                Entry::Occupied(mut e) => match self.policy {
+                    DuplicatePolicy::LastWins => {
+                        e.insert(value);
+                    },
+                    DuplicatePolicy::FirstWins => {},
+                    DuplicatePolicy::Error => {
+                        return Err(de::Error::custom(format_args!("duplicate map key {:?}", e.key())));
+                    },
+                },
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// Alternative representation as a sequence of `(key, value)` pairs, for
+/// formats like JSON that only allow string map keys. Use it with
+/// `#[serde(with = "hashmap::serde_seq")]` on a `HashMap` field.
+pub mod serde_seq {
+    use core::fmt;
+    use core::hash::{BuildHasher, Hash};
+    use core::marker::PhantomData;
+
+    use serde::de::{Deserialize, Deserializer, SeqAccess, Visitor};
+    use serde::ser::{Serialize, Serializer};
+
+    use super::MAX_PREALLOC;
+    use crate::HashMap;
+
+    pub fn serialize<K, V, H, S>(map: &HashMap<K, V, H>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, H, D>(deserializer: D) -> Result<HashMap<K, V, H>, D::Error>
+    where
+        K: Deserialize<'de> + Hash + Eq,
+        V: Deserialize<'de>,
+        H: BuildHasher + Default,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(SeqVisitor { marker: PhantomData })
+    }
+
+    struct SeqVisitor<K, V, H> {
+        marker: PhantomData<HashMap<K, V, H>>,
+    }
+
+    impl<'de, K, V, H> Visitor<'de> for SeqVisitor<K, V, H>
+    where
+        K: Deserialize<'de> + Hash + Eq,
+        V: Deserialize<'de>,
+        H: BuildHasher + Default,
+    {
+        type Value = HashMap<K, V, H>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a sequence of key-value pairs")
+        }
+
+        fn visit_seq<A>(self, mut access: A) -> Result<Self::Value, A::Error>
+        where
+            A: SeqAccess<'de>
+        {
+            let capacity = access.size_hint().unwrap_or(0).min(MAX_PREALLOC);
+            let mut map = HashMap::with_capacity_and_hasher(capacity, H::default());
+            while let Some((key, value)) = access.next_element()? {
+                map.insert(key, value);
+            }
+            Ok(map)
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let map: HashMap<String, u32> = (0..100).map(|i| (i.to_string(), i)).collect();
+        let json = serde_json::to_string(&map).unwrap();
+        let back: HashMap<String, u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, map);
+    }
+
+    #[test]
+    fn json_empty() {
+        let map: HashMap<String, u32> = HashMap::new();
+        assert_eq!(serde_json::to_string(&map).unwrap(), "{}");
+        let back: HashMap<String, u32> = serde_json::from_str("{}").unwrap();
+        assert!(back.is_empty());
+    }
+
+    #[test]
+    fn json_duplicates_last_wins() {
+        let map: HashMap<String, u32> = serde_json::from_str(r#"{"a": 1, "b": 2, "a": 3}"#).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("a"), Some(&3));
+    }
+
+    #[test]
+    fn json_integer_keys() {
+        let map: HashMap<u64, String> = (0..100).map(|i| (i, i.to_string())).collect();
+        let json = serde_json::to_string(&map).unwrap();
+        let back: HashMap<u64, String> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, map);
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let map: HashMap<(u32, u32), Vec<u8>> = (0..100).map(|i| ((i, i * 2), vec![i as u8; 3])).collect();
+        let bytes = bincode::serialize(&map).unwrap();
+        let back: HashMap<(u32, u32), Vec<u8>> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back, map);
+
+        let empty: HashMap<u32, u32> = HashMap::new();
+        let bytes = bincode::serialize(&empty).unwrap();
+        let back: HashMap<u32, u32> = bincode::deserialize(&bytes).unwrap();
+        assert!(back.is_empty());
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Eq, Hash, Debug)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Grid {
+        #[serde(with = "serde_seq")]
+        cells: HashMap<(u32, u32), char>,
+        #[serde(with = "serde_seq")]
+        points: HashMap<Point, String>,
+        labels: HashMap<String, u32>,
+    }
+
+    #[test]
+    fn seq_round_trip() {
+        let grid = Grid {
+            cells: (0..10).map(|i| ((i, i + 1), 'x')).collect(),
+            points: (0..10).map(|i| (Point { x: i, y: -i }, i.to_string())).collect(),
+            labels: (0..10).map(|i| (i.to_string(), i)).collect(),
+        };
+        let json = serde_json::to_string(&grid).unwrap();
+        assert!(json.contains(r#""labels":{"#));
+        assert!(json.contains(r#""cells":[["#));
+        let back: Grid = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, grid);
+    }
+
+    #[test]
+    fn seq_format() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Wrapper(#[serde(with = "serde_seq")] HashMap<(u32, u32), u32>);
+
+        let json = serde_json::to_string(&Wrapper(HashMap::from([((1, 2), 3)]))).unwrap();
+        assert_eq!(json, "[[[1,2],3]]");
+        let back: Wrapper = serde_json::from_str("[[[1,2],3],[[1,2],4]]").unwrap();
+        assert_eq!(back.0.len(), 1);
+        assert_eq!(back.0.get(&(1, 2)), Some(&4));
+    }
+
+    fn with_policy(json: &str, policy: DuplicatePolicy) -> serde_json::Result<HashMap<String, u32>> {
+        let mut de = serde_json::Deserializer::from_str(json);
+        DeserializeWithPolicy::new(policy).deserialize(&mut de)
+    }
+
+    const DUPLICATED: &str = r#"{"a": 1, "b": 2, "a": 3}"#;
+
+    #[test]
+    fn policy_last_wins() {
+        let map = with_policy(DUPLICATED, DuplicatePolicy::LastWins).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("a"), Some(&3));
+    }
+
+    #[test]
+    fn policy_first_wins() {
+        let map = with_policy(DUPLICATED, DuplicatePolicy::FirstWins).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("a"), Some(&1));
+    }
+
+    #[test]
+    fn policy_error() {
+        let err = with_policy(DUPLICATED, DuplicatePolicy::Error).unwrap_err();
+        assert!(err.to_string().contains(r#"duplicate map key "a""#), "{}", err);
+
+        let map = with_policy(r#"{"a": 1, "b": 2}"#, DuplicatePolicy::Error).unwrap();
+        assert_eq!(map.len(), 2);
+    }
+}
@@ -0,0 +1,703 @@
+//! A map that skips hashing while it is small.
+//!
+//! [`SmallMap`] wraps a [`HashMap`]. Up to a threshold of entries it leaves
+//! the bucket array unallocated and finds keys by comparing them one by one
+//! against the arena, which for a handful of entries beats hashing the key
+//! at all. The insert that would cross the threshold hashes every entry
+//! once and links them into buckets; from then on it is an ordinary chained
+//! map. Removals that take it below half the threshold drop the buckets
+//! again, so a map hovering around the threshold doesn't flip back and
+//! forth.
+//!
+//! Both forms share one arena, so the crate's iterators serve either, and
+//! switching never reorders entries.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+
+use crate::{
+    buckets_for, make_hash, DefaultState, Drain, Equivalent, HashMap, IntoIter, IntoKeys,
+    IntoValues, Iter, IterMut, Keys, Values, ValuesMut, NONE,
+};
+
+/// Entries a [`SmallMap`] scans through before it switches to hashing,
+/// unless built with a threshold of its own.
+pub(crate) const DEFAULT_THRESHOLD: usize = 8;
+
+/// A hash map that linear-scans its entries until there are more than a
+/// threshold of them, eight unless set with
+/// [`with_threshold`](SmallMap::with_threshold).
+///
+/// It offers the core of [`HashMap`]'s API, with the same iterators and an
+/// entry API of its own in this module:
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::SmallMap;
+///
+/// let mut headers = SmallMap::new();
+/// headers.insert("content-type", "text/plain");
+/// headers.insert("content-length", "12");
+/// assert!(!headers.is_hashed());
+/// assert_eq!(headers.get("content-type"), Some(&"text/plain"));
+///
+/// for name in ["accept", "host", "user-agent", "cookie", "referer", "origin", "etag"] {
+///     headers.insert(name, "");
+/// }
+/// assert_eq!(headers.len(), 9);
+/// assert!(headers.is_hashed());
+/// assert_eq!(headers.get("content-length"), Some(&"12"));
+/// ```
+pub struct SmallMap<K, V, S = DefaultState> {
+    // while `hashed` is false the bucket arrays stay empty and the cached
+    // hashes in the arena mean nothing; `next` is kept as long as the arena
+    map: HashMap<K, V, S>,
+    threshold: usize,
+    hashed: bool,
+}
+
+#[cfg(feature = "std")]
+impl<K, V> SmallMap<K, V, DefaultState> {
+    pub fn new() -> Self {
+        SmallMap::with_hasher(DefaultState::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        SmallMap::with_capacity_and_hasher(capacity, DefaultState::default())
+    }
+
+    /// An empty map that starts hashing once it holds more than
+    /// `threshold` entries. A threshold of zero hashes from the start.
+    pub fn with_threshold(threshold: usize) -> Self {
+        SmallMap::with_threshold_and_hasher(threshold, DefaultState::default())
+    }
+}
+
+impl<K, V, S> SmallMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        SmallMap::with_threshold_and_hasher(DEFAULT_THRESHOLD, hash_builder)
+    }
+
+    /// Starts out hashed if `capacity` is past the default threshold, since
+    /// the map would switch before it ran out of room anyway.
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        if capacity <= DEFAULT_THRESHOLD {
+            let mut map = SmallMap::with_hasher(hash_builder);
+            map.map.entries.reserve(capacity);
+            map.map.table.next.reserve(capacity);
+            return map;
+        }
+        SmallMap {
+            map: HashMap::with_capacity_and_hasher(capacity, hash_builder),
+            threshold: DEFAULT_THRESHOLD,
+            hashed: true,
+        }
+    }
+
+    pub fn with_threshold_and_hasher(threshold: usize, hash_builder: S) -> Self {
+        SmallMap {
+            map: HashMap::with_hasher(hash_builder),
+            threshold,
+            hashed: false,
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        self.map.hasher()
+    }
+
+    /// Most entries the map holds before it starts hashing.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Whether the map is currently linked into buckets rather than
+    /// scanned.
+    pub fn is_hashed(&self) -> bool {
+        self.hashed
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Number of entries the map can hold before it either switches to
+    /// hashing or, once hashed, grows its buckets.
+    pub fn capacity(&self) -> usize {
+        match self.hashed {
+            true => self.map.capacity(),
+            false => self.threshold,
+        }
+    }
+
+    /// Removes every entry, which also drops the buckets of a hashed map.
+    pub fn clear(&mut self) {
+        self.unhash();
+        self.map.clear();
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        if self.hashed {
+            self.map.retain(f);
+            self.unhash_if_small();
+        } else {
+            self.map.entries.retain_mut(|(_, ekey, evalue)| f(ekey, evalue));
+            self.map.table.next.truncate(self.map.entries.len());
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        self.map.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        self.map.iter_mut()
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        self.map.keys()
+    }
+
+    pub fn values(&self) -> Values<'_, K, V> {
+        self.map.values()
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        self.map.values_mut()
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K, V> {
+        self.map.into_keys()
+    }
+
+    pub fn into_values(self) -> IntoValues<K, V> {
+        self.map.into_values()
+    }
+
+    /// Removes every entry, handing them out by value. Like
+    /// [`clear`](SmallMap::clear), it leaves the map unhashed.
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        self.unhash();
+        self.map.drain()
+    }
+
+    /// Arena index of the entry whose key `is_match` accepts, by scanning.
+    fn scan<F>(&self, mut is_match: F) -> Option<usize>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        self.map.entries.iter().position(|(_, ekey, _)| is_match(ekey))
+    }
+
+    /// Appends an entry to an unhashed map and returns its arena index.
+    fn push_unhashed(&mut self, key: K, value: V) -> usize {
+        let index = self.map.entries.len();
+        self.map.entries.push((0, key, value));
+        self.map.table.next.push(NONE);
+        index
+    }
+
+    /// Removes the entry at arena `index`, dropping the buckets if that
+    /// leaves the map small enough to scan again.
+    fn remove_at(&mut self, index: usize) -> (K, V) {
+        if !self.hashed {
+            self.map.table.next.swap_remove(index);
+            let (_, ekey, evalue) = self.map.entries.swap_remove(index);
+            return (ekey, evalue);
+        }
+        let (_, ekey, evalue) = self.map.remove_index(index);
+        self.unhash_if_small();
+        if self.hashed {
+            self.map.maybe_shrink();
+        }
+        (ekey, evalue)
+    }
+
+    fn unhash_if_small(&mut self) {
+        if self.len() < self.threshold / 2 {
+            self.unhash();
+        }
+    }
+
+    /// Gives up the buckets. The arena and its order are left alone.
+    fn unhash(&mut self) {
+        self.map.end_rehash();
+        self.map.table.buckets = Vec::new();
+        self.hashed = false;
+    }
+}
+
+impl<K, V, S> SmallMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Hashes every entry once and links them into buckets with room for
+    /// at least `capacity` entries.
+    fn hash_all(&mut self, capacity: usize) {
+        let map = &mut self.map;
+        for entry in &mut map.entries {
+            entry.0 = make_hash(&map.hash_builder, &entry.1);
+        }
+        let nbuckets = buckets_for(capacity.max(map.len()), map.table.load_factor).expect("capacity overflow");
+        map.rehash(nbuckets);
+        self.hashed = true;
+    }
+
+    fn lookup<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        match self.hashed {
+            true => self.map.lookup(key),
+            false => self.scan(|ekey| key.equivalent(ekey)),
+        }
+    }
+
+    /// Finds the entry for `key`. A vacant entry in a map already at its
+    /// threshold switches the map to hashing first, so inserting into it
+    /// lands in a bucket.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        if !self.hashed {
+            if let Some(index) = self.scan(|ekey| ekey == &key) {
+                return Entry::Occupied(OccupiedEntry { map: self, index });
+            }
+            if self.len() < self.threshold {
+                return Entry::Vacant(VacantEntry { key, hash: 0, map: self });
+            }
+            self.hash_all(self.len() + 1);
+        }
+        if self.map.len() >= self.map.capacity() {
+            self.map.resize();
+        }
+        let hash = make_hash(&self.map.hash_builder, &key);
+        match self.map.find(hash, |ekey| ekey == &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry { key, hash, map: self }),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => Some(e.insert(value)),
+            Entry::Vacant(e) => {
+                e.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        let (_, ekey, evalue) = &self.map.entries[index];
+        Some((ekey, evalue))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.map.entries[index].2)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        Some(self.remove_at(index))
+    }
+
+    /// Makes room for at least `additional` more entries, switching to
+    /// hashing if they would take the map past its threshold.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len().checked_add(additional).expect("capacity overflow");
+        if self.hashed {
+            self.map.reserve(additional);
+        } else if needed > self.threshold {
+            self.hash_all(needed);
+        } else {
+            self.map.entries.reserve(additional);
+            self.map.table.next.reserve(additional);
+        }
+    }
+
+    /// Shrinks the arena, and the buckets of a hashed map, as far as the
+    /// current entries allow.
+    pub fn shrink_to_fit(&mut self) {
+        if self.hashed {
+            self.map.shrink_to_fit();
+        } else {
+            self.map.entries.shrink_to_fit();
+            self.map.table.next.shrink_to_fit();
+        }
+    }
+}
+
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    map: &'a mut SmallMap<K, V, S>,
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    key: K,
+    /// Only computed, and only used, once the map is hashed.
+    hash: u64,
+    map: &'a mut SmallMap<K, V, S>,
+}
+
+/// A view into a single entry of a [`SmallMap`], from
+/// [`SmallMap::entry`].
+pub enum Entry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+impl<'a, K: 'a, V: 'a, S> OccupiedEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.map.map.entries[self.index].1
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.map.entries[self.index].2
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.map.entries[self.index].2
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.map.entries[self.index].2
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove_at(self.index)
+    }
+}
+
+impl<'a, K: 'a, V: 'a, S> VacantEntry<'a, K, V, S> {
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S> {
+        let index = match self.map.hashed {
+            true => self.map.map.push_entry(self.hash, self.key, value),
+            false => self.map.push_unhashed(self.key, value),
+        };
+        OccupiedEntry { map: self.map, index }
+    }
+}
+
+entry_impls!();
+
+impl<K: Clone, V: Clone, S: Clone> Clone for SmallMap<K, V, S> {
+    fn clone(&self) -> Self {
+        SmallMap {
+            map: self.map.clone(),
+            threshold: self.threshold,
+            hashed: self.hashed,
+        }
+    }
+}
+
+map_impls!(SmallMap);
+
+impl<K, V, S> IntoIterator for SmallMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.map.into_iter()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::hash::Hasher;
+    use std::cell::Cell;
+
+    use super::*;
+    use crate::test_util::{differential, sorted, Colliding};
+
+    /// An unhashed map has no buckets; a hashed one reaches every entry
+    /// from its bucket under its real hash.
+    fn check_table<K: Hash + Eq, V, S: BuildHasher>(map: &SmallMap<K, V, S>) {
+        let inner = &map.map;
+        assert_eq!(inner.table.next.len(), inner.entries.len());
+        if !map.hashed {
+            assert!(inner.table.buckets.is_empty());
+            assert!(map.len() <= map.threshold);
+            return;
+        }
+        for (index, (hash, key, _)) in inner.entries.iter().enumerate() {
+            assert_eq!(*hash, make_hash(&inner.hash_builder, key));
+            assert_eq!(inner.find(*hash, |ekey| ekey == key), Some(index));
+        }
+    }
+
+    /// The shared differential suite, which must also see the map switch
+    /// to hashing whenever there are keys enough for it to.
+    fn switching_differential<S: BuildHasher>(seed: u64, keys: u64, map: SmallMap<u32, u32, S>) {
+        let threshold = map.threshold();
+        let mut hashed = map.is_hashed();
+        let mut switches = 0;
+        differential(seed, keys, map, |map| {
+            if map.is_hashed() != hashed {
+                hashed = map.is_hashed();
+                switches += 1;
+            }
+            check_table(map);
+        });
+        if keys > threshold as u64 * 4 {
+            assert!(switches > 0, "never switched to hashing");
+        }
+    }
+
+    #[test]
+    fn differential_against_chaining() {
+        for seed in 0..8 {
+            for threshold in [0, 1, 4, 8, 32] {
+                switching_differential(seed, 12, SmallMap::with_threshold(threshold));
+                switching_differential(seed, 1000, SmallMap::with_threshold(threshold));
+            }
+        }
+    }
+
+    #[test]
+    fn differential_with_collisions() {
+        for seed in 0..4 {
+            switching_differential(seed, 40, SmallMap::with_threshold_and_hasher(8, Colliding::default()));
+        }
+    }
+
+    thread_local! {
+        static HASHED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(PartialEq, Eq, Debug)]
+    struct Counted(u32);
+
+    impl Hash for Counted {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            HASHED.with(|n| n.set(n.get() + 1));
+            self.0.hash(state);
+        }
+    }
+
+    #[test]
+    fn small_maps_never_hash() {
+        let mut map = SmallMap::with_threshold(4);
+        for i in 0..4 {
+            map.insert(Counted(i), i);
+        }
+        assert_eq!(map.get(&Counted(2)), Some(&2));
+        assert_eq!(map.remove(&Counted(0)), Some(0));
+        *map.entry(Counted(3)).or_insert(0) += 1;
+        assert_eq!(HASHED.with(Cell::get), 0);
+        assert!(!map.is_hashed());
+
+        // crossing the threshold hashes everything once, new key included
+        map.insert(Counted(10), 10);
+        map.insert(Counted(11), 11);
+        assert!(map.is_hashed());
+        assert_eq!(HASHED.with(Cell::get), 5);
+        check_table(&map);
+    }
+
+    #[test]
+    fn crossing_the_threshold_both_ways() {
+        let mut map = SmallMap::new();
+        assert_eq!(map.threshold(), DEFAULT_THRESHOLD);
+        for i in 0..DEFAULT_THRESHOLD as u32 {
+            map.insert(i, i);
+            assert!(!map.is_hashed());
+        }
+        let order: Vec<_> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        map.insert(100, 100);
+        assert!(map.is_hashed());
+        check_table(&map);
+        // the switch kept the arena as it was
+        let after: Vec<_> = map.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(after[..DEFAULT_THRESHOLD], order[..]);
+        assert_eq!(after[DEFAULT_THRESHOLD], (100, 100));
+
+        // removing down to half the threshold stays hashed, below it doesn't
+        let half = DEFAULT_THRESHOLD / 2;
+        while map.len() > half {
+            let &key = map.keys().next().unwrap();
+            map.remove(&key);
+            assert!(map.is_hashed());
+        }
+        let &key = map.keys().next().unwrap();
+        let order: Vec<_> = map.iter().map(|(&k, &v)| (k, v)).skip(1).collect();
+        match map.entry(key) {
+            Entry::Occupied(e) => assert_eq!(e.remove(), key),
+            Entry::Vacant(_) => unreachable!(),
+        }
+        assert!(!map.is_hashed());
+        check_table(&map);
+        let mut expected = order;
+        expected.sort_unstable();
+        assert_eq!(sorted(map.iter()), expected);
+        for &(k, v) in &expected {
+            assert_eq!(map[&k], v);
+        }
+    }
+
+    #[test]
+    fn threshold_zero_hashes_from_the_start() {
+        let mut map = SmallMap::with_threshold(0);
+        assert_eq!(map.capacity(), 0);
+        map.insert(1, 1);
+        assert!(map.is_hashed());
+        map.remove(&1);
+        assert!(map.is_hashed());
+        check_table(&map);
+    }
+
+    #[test]
+    fn reserve_and_capacity() {
+        let mut map: SmallMap<u32, u32> = SmallMap::with_threshold(16);
+        map.reserve(10);
+        assert!(!map.is_hashed());
+        assert_eq!(map.capacity(), 16);
+        map.reserve(100);
+        assert!(map.is_hashed());
+        assert!(map.capacity() >= 100);
+        check_table(&map);
+
+        let map: SmallMap<u32, u32> = SmallMap::with_capacity(4);
+        assert!(!map.is_hashed());
+        let mut map: SmallMap<u32, u32> = SmallMap::with_capacity(1000);
+        assert!(map.is_hashed());
+        let nbuckets = map.map.bucket_count();
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.map.bucket_count(), nbuckets);
+        map.retain(|&k, _| k < 100);
+        map.shrink_to_fit();
+        assert!(map.map.bucket_count() < nbuckets);
+        check_table(&map);
+
+        map.retain(|&k, _| k < 2);
+        assert!(!map.is_hashed());
+        map.shrink_to_fit();
+        assert_eq!(sorted(map.iter()), [(0, 0), (1, 1)]);
+        check_table(&map);
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut map = SmallMap::new();
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert!(map.is_hashed());
+        assert_eq!(map["the"], 3);
+        assert_eq!(map["fox"], 1);
+
+        match map.entry("fox") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.key(), &"fox");
+                assert_eq!(e.insert(10), 1);
+                assert_eq!(e.remove_entry(), ("fox", 10));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry("fox") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(e) => {
+                assert_eq!(e.key(), &"fox");
+                let e = e.insert_entry(5);
+                assert_eq!(e.get(), &5);
+            }
+        }
+        map.entry("end").and_modify(|v| *v += 1).or_insert(0);
+        assert_eq!(map["end"], 2);
+        assert_eq!(*map.entry("new").or_insert_with_key(|k| k.len()), 3);
+        assert_eq!(*map.entry("zero").or_insert_default(), 0);
+        check_table(&map);
+
+        // a vacant entry at the threshold already points into the buckets
+        let mut map = SmallMap::with_threshold(2);
+        map.insert(1, 1);
+        map.insert(2, 2);
+        let Entry::Vacant(e) = map.entry(3) else { unreachable!() };
+        assert_eq!(*e.insert(3), 3);
+        assert!(map.is_hashed());
+        check_table(&map);
+    }
+
+    #[test]
+    fn iterators_match_hash_map() {
+        for threshold in [4, 1000] {
+            let mut map: SmallMap<u32, u32> = SmallMap::with_threshold(threshold);
+            map.extend((0..500).map(|i| (i, i)));
+            assert_eq!(map.is_hashed(), threshold < 500);
+            for (_, v) in &mut map {
+                *v += 1;
+            }
+            for v in map.values_mut() {
+                *v *= 2;
+            }
+            assert!(map.iter().all(|(&k, &v)| v == (k + 1) * 2));
+            assert_eq!(map.keys().len(), 500);
+            assert_eq!(map.values().copied().max(), Some(1000));
+            let model: HashMap<u32, u32> = map.clone().into_iter().collect();
+            assert!(map == model);
+
+            let mut keys: Vec<_> = map.clone().into_keys().collect();
+            keys.sort_unstable();
+            assert_eq!(keys, (0..500).collect::<Vec<_>>());
+            assert_eq!(map.clone().into_values().count(), 500);
+
+            assert_eq!(map.drain().count(), 500);
+            assert!(map.is_empty());
+            assert!(!map.is_hashed());
+            map.insert(1, 1);
+            assert_eq!(map.iter().collect::<Vec<_>>(), [(&1, &1)]);
+            check_table(&map);
+        }
+    }
+
+    #[test]
+    fn borrowed_lookups_and_debug() {
+        let map = SmallMap::from([("a".to_string(), 1)]);
+        assert_eq!(map.get("a"), Some(&1));
+        assert_eq!(map.get_key_value("a"), Some((&"a".to_string(), &1)));
+        assert_eq!(format!("{:?}", map), r#"{"a": 1}"#);
+        let mut other = SmallMap::new();
+        other.extend(map.iter().map(|(k, &v)| (k.clone(), v)));
+        assert_eq!(map, other);
+        assert_eq!(SmallMap::<u32, u32>::default().threshold(), DEFAULT_THRESHOLD);
+    }
+}
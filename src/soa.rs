@@ -0,0 +1,757 @@
+//! A chaining map that stores keys and values in separate arrays.
+//!
+//! [`SoaHashMap`] chains entries through buckets like [`HashMap`], but
+//! splits its arena into parallel `hashes`, `keys` and `values` vectors:
+//! entry `i` is `keys[i]` with `values[i]`. Walking a chain touches only
+//! hashes and keys, and [`values`](SoaHashMap::values) or
+//! [`values_mut`](SoaHashMap::values_mut) run straight down the dense value
+//! array without dragging keys through the cache. Removal swap-removes the
+//! same index from every array, so they never fall out of step.
+
+use alloc::vec::{self, Vec};
+use core::hash::{BuildHasher, Hash};
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+use core::slice;
+
+use crate::{
+    bucket_index, buckets_for, capacity_of, make_hash, Chain, DefaultState, Equivalent, HashMap,
+    DEFAULT_LOAD_FACTOR, INITIAL_NBUCKETS, NONE,
+};
+
+/// A hash map laid out as a struct of arrays, for loops that only read or
+/// update values.
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::SoaHashMap;
+///
+/// let mut map = SoaHashMap::new();
+/// map.insert("a", [0u64; 32]);
+/// map.insert("b", [1u64; 32]);
+/// for value in map.values_mut() {
+///     value[0] += 1;
+/// }
+/// assert_eq!(map["b"][0], 2);
+/// assert_eq!(map.values_slice().len(), 2);
+/// ```
+pub struct SoaHashMap<K, V, S = DefaultState> {
+    // Entry `i` is `(hashes[i], keys[i], values[i])`, chained through
+    // `next[i]` from `buckets` exactly as in `HashMap`.
+    hashes: Vec<u64>,
+    keys: Vec<K>,
+    values: Vec<V>,
+    next: Vec<usize>,
+    buckets: Vec<usize>,
+    hash_builder: S,
+}
+
+#[cfg(feature = "std")]
+impl<K, V> SoaHashMap<K, V, DefaultState> {
+    pub fn new() -> Self {
+        SoaHashMap::with_hasher(DefaultState::default())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        SoaHashMap::with_capacity_and_hasher(capacity, DefaultState::default())
+    }
+}
+
+impl<K, V, S> SoaHashMap<K, V, S> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        SoaHashMap::with_capacity_and_hasher(0, hash_builder)
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        let nbuckets = buckets_for(capacity, DEFAULT_LOAD_FACTOR).expect("capacity overflow");
+        SoaHashMap {
+            hashes: Vec::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
+            values: Vec::with_capacity(capacity),
+            next: Vec::with_capacity(capacity),
+            buckets: alloc::vec![NONE; nbuckets],
+            hash_builder,
+        }
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Number of entries the map can hold before its buckets grow.
+    pub fn capacity(&self) -> usize {
+        capacity_of(self.buckets.len(), DEFAULT_LOAD_FACTOR)
+    }
+
+    pub fn clear(&mut self) {
+        self.hashes.clear();
+        self.keys.clear();
+        self.values.clear();
+        self.next.clear();
+        self.buckets.fill(NONE);
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        // swap-removing keeps every array in step without a second pass
+        let mut index = 0;
+        while index < self.len() {
+            if f(&self.keys[index], &mut self.values[index]) {
+                index += 1;
+            } else {
+                self.remove_index(index);
+            }
+        }
+    }
+
+    /// The keys, in the same order as [`values_slice`](Self::values_slice).
+    pub fn keys_slice(&self) -> &[K] {
+        &self.keys
+    }
+
+    /// The values as one dense slice, in the order every iterator uses.
+    pub fn values_slice(&self) -> &[V] {
+        &self.values
+    }
+
+    /// Mutable access to the dense value array. Keys can't be reached
+    /// this way, so entries stay where their hashes put them.
+    pub fn values_mut_slice(&mut self) -> &mut [V] {
+        &mut self.values
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            keys: self.keys.iter(),
+            values: self.values.iter(),
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            keys: self.keys.iter(),
+            values: self.values.iter_mut(),
+        }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K> {
+        Keys { inner: self.keys.iter() }
+    }
+
+    pub fn values(&self) -> Values<'_, V> {
+        Values { inner: self.values.iter() }
+    }
+
+    pub fn values_mut(&mut self) -> ValuesMut<'_, V> {
+        ValuesMut { inner: self.values.iter_mut() }
+    }
+
+    pub fn into_keys(self) -> IntoKeys<K> {
+        IntoKeys { inner: self.keys.into_iter() }
+    }
+
+    pub fn into_values(self) -> IntoValues<V> {
+        IntoValues { inner: self.values.into_iter() }
+    }
+
+    /// Removes every entry, handing them out by value. The buckets are
+    /// kept, as with [`clear`](SoaHashMap::clear).
+    pub fn drain(&mut self) -> Drain<'_, K, V, S> {
+        self.hashes.clear();
+        self.next.clear();
+        self.buckets.fill(NONE);
+        Drain {
+            keys: self.keys.drain(..),
+            values: self.values.drain(..),
+            marker: PhantomData,
+        }
+    }
+
+    fn chain(&self, bucket: usize) -> Chain<'_> {
+        Chain {
+            next: &self.next,
+            at: self.buckets[bucket],
+        }
+    }
+
+    /// Index of the entry with this `hash` whose key `is_match` accepts.
+    fn find<F>(&self, hash: u64, mut is_match: F) -> Option<usize>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        if self.buckets.is_empty() {
+            return None;
+        }
+        self.chain(bucket_index(hash, self.buckets.len()))
+            .find(|&index| self.hashes[index] == hash && is_match(&self.keys[index]))
+    }
+
+    /// Appends an entry to every array and links it into its bucket,
+    /// growing the buckets first if the map is full. Returns its index.
+    fn push_entry(&mut self, hash: u64, key: K, value: V) -> usize {
+        if self.len() >= self.capacity() {
+            let nbuckets = match self.buckets.len() {
+                0 => INITIAL_NBUCKETS,
+                n => 2 * n,
+            };
+            self.rehash(nbuckets);
+        }
+        let bucket = bucket_index(hash, self.buckets.len());
+        let index = self.len();
+        self.hashes.push(hash);
+        self.keys.push(key);
+        self.values.push(value);
+        self.next.push(self.buckets[bucket]);
+        self.buckets[bucket] = index;
+        index
+    }
+
+    /// The link (a bucket head or another entry's `next`) that points at
+    /// entry `index`.
+    fn link_to(&mut self, index: usize) -> &mut usize {
+        let bucket = bucket_index(self.hashes[index], self.buckets.len());
+        if self.buckets[bucket] == index {
+            return &mut self.buckets[bucket];
+        }
+        let mut at = self.buckets[bucket];
+        while self.next[at] != index {
+            at = self.next[at];
+        }
+        &mut self.next[at]
+    }
+
+    /// Unlinks and removes entry `index` from every array. The last entry
+    /// is swapped into its place in each of them, and whatever linked to
+    /// it is pointed at the new index.
+    fn remove_index(&mut self, index: usize) -> (K, V) {
+        let after = self.next[index];
+        *self.link_to(index) = after;
+        let last = self.len() - 1;
+        if index != last {
+            *self.link_to(last) = index;
+        }
+        self.next.swap_remove(index);
+        self.hashes.swap_remove(index);
+        (self.keys.swap_remove(index), self.values.swap_remove(index))
+    }
+
+    /// Relinks every entry over `nbuckets` fresh buckets.
+    fn rehash(&mut self, nbuckets: usize) {
+        self.buckets.clear();
+        self.buckets.resize(nbuckets, NONE);
+        for (index, &hash) in self.hashes.iter().enumerate() {
+            let bucket = bucket_index(hash, nbuckets);
+            self.next[index] = self.buckets[bucket];
+            self.buckets[bucket] = index;
+        }
+    }
+}
+
+impl<K, V, S> SoaHashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn lookup<Q>(&self, key: &Q) -> Option<usize>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.find(make_hash(&self.hash_builder, key), |ekey| key.equivalent(ekey))
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, S> {
+        let hash = make_hash(&self.hash_builder, &key);
+        match self.find(hash, |ekey| ekey == &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            None => Entry::Vacant(VacantEntry { key, hash, map: self }),
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut e) => Some(e.insert(value)),
+            Entry::Vacant(e) => {
+                e.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        Some(&self.values[self.lookup(key)?])
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        Some((&self.keys[index], &self.values[index]))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        Some(&mut self.values[index])
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, evalue)| evalue)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let index = self.lookup(key)?;
+        Some(self.remove_index(index))
+    }
+
+    /// Makes room for at least `additional` more entries.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len().checked_add(additional).expect("capacity overflow");
+        self.hashes.reserve(additional);
+        self.keys.reserve(additional);
+        self.values.reserve(additional);
+        self.next.reserve(additional);
+        if needed > self.capacity() {
+            self.rehash(buckets_for(needed, DEFAULT_LOAD_FACTOR).expect("capacity overflow"));
+        }
+    }
+
+    /// Shrinks the buckets and every array as far as the current entries
+    /// allow.
+    pub fn shrink_to_fit(&mut self) {
+        self.hashes.shrink_to_fit();
+        self.keys.shrink_to_fit();
+        self.values.shrink_to_fit();
+        self.next.shrink_to_fit();
+        self.rehash(buckets_for(self.len(), DEFAULT_LOAD_FACTOR).expect("capacity overflow"));
+        self.buckets.shrink_to_fit();
+    }
+}
+
+pub struct OccupiedEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    map: &'a mut SoaHashMap<K, V, S>,
+    index: usize,
+}
+
+pub struct VacantEntry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    key: K,
+    hash: u64,
+    map: &'a mut SoaHashMap<K, V, S>,
+}
+
+/// A view into a single entry of a [`SoaHashMap`], from
+/// [`SoaHashMap::entry`].
+pub enum Entry<'a, K: 'a, V: 'a, S: 'a = DefaultState> {
+    Occupied(OccupiedEntry<'a, K, V, S>),
+    Vacant(VacantEntry<'a, K, V, S>),
+}
+
+impl<'a, K: 'a, V: 'a, S> OccupiedEntry<'a, K, V, S> {
+    pub fn key(&self) -> &K {
+        &self.map.keys[self.index]
+    }
+
+    pub fn get(&self) -> &V {
+        &self.map.values[self.index]
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.map.values[self.index]
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.map.values[self.index]
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove_index(self.index)
+    }
+}
+
+impl<'a, K: 'a, V: 'a, S> VacantEntry<'a, K, V, S> {
+    pub fn insert_entry(self, value: V) -> OccupiedEntry<'a, K, V, S> {
+        let index = self.map.push_entry(self.hash, self.key, value);
+        OccupiedEntry { map: self.map, index }
+    }
+}
+
+entry_impls!();
+
+impl<K: Clone, V: Clone, S: Clone> Clone for SoaHashMap<K, V, S> {
+    fn clone(&self) -> Self {
+        SoaHashMap {
+            hashes: self.hashes.clone(),
+            keys: self.keys.clone(),
+            values: self.values.clone(),
+            next: self.next.clone(),
+            buckets: self.buckets.clone(),
+            hash_builder: self.hash_builder.clone(),
+        }
+    }
+}
+
+map_impls!(SoaHashMap);
+
+impl<K, V, S> IntoIterator for SoaHashMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            keys: self.keys.into_iter(),
+            values: self.values.into_iter(),
+        }
+    }
+}
+
+pub struct Iter<'a, K, V> {
+    keys: slice::Iter<'a, K>,
+    values: slice::Iter<'a, V>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<Self::Item> {
+        Some((self.keys.next()?, self.values.next()?))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K, V> Clone for Iter<'a, K, V> {
+    fn clone(&self) -> Self {
+        Iter {
+            keys: self.keys.clone(),
+            values: self.values.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> FusedIterator for Iter<'a, K, V> {}
+
+pub struct IterMut<'a, K, V> {
+    keys: slice::Iter<'a, K>,
+    values: slice::IterMut<'a, V>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<Self::Item> {
+        Some((self.keys.next()?, self.values.next()?))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+impl<'a, K, V> FusedIterator for IterMut<'a, K, V> {}
+
+pub struct IntoIter<K, V> {
+    keys: vec::IntoIter<K>,
+    values: vec::IntoIter<V>,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        Some((self.keys.next()?, self.values.next()?))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> FusedIterator for IntoIter<K, V> {}
+
+pub struct Drain<'a, K, V, S = DefaultState> {
+    keys: vec::Drain<'a, K>,
+    values: vec::Drain<'a, V>,
+    marker: PhantomData<&'a mut S>,
+}
+
+impl<'a, K, V, S> Iterator for Drain<'a, K, V, S> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<Self::Item> {
+        Some((self.keys.next()?, self.values.next()?))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.keys.size_hint()
+    }
+}
+
+impl<'a, K, V, S> ExactSizeIterator for Drain<'a, K, V, S> {}
+
+impl<'a, K, V, S> FusedIterator for Drain<'a, K, V, S> {}
+
+pub struct Keys<'a, K> {
+    inner: slice::Iter<'a, K>,
+}
+
+impl<'a, K> Iterator for Keys<'a, K> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K> ExactSizeIterator for Keys<'a, K> {}
+
+impl<'a, K> FusedIterator for Keys<'a, K> {}
+
+pub struct Values<'a, V> {
+    inner: slice::Iter<'a, V>,
+}
+
+impl<'a, V> Iterator for Values<'a, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, V> ExactSizeIterator for Values<'a, V> {}
+
+impl<'a, V> FusedIterator for Values<'a, V> {}
+
+pub struct ValuesMut<'a, V> {
+    inner: slice::IterMut<'a, V>,
+}
+
+impl<'a, V> Iterator for ValuesMut<'a, V> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, V> ExactSizeIterator for ValuesMut<'a, V> {}
+
+impl<'a, V> FusedIterator for ValuesMut<'a, V> {}
+
+pub struct IntoKeys<K> {
+    inner: vec::IntoIter<K>,
+}
+
+impl<K> Iterator for IntoKeys<K> {
+    type Item = K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K> ExactSizeIterator for IntoKeys<K> {}
+
+impl<K> FusedIterator for IntoKeys<K> {}
+
+pub struct IntoValues<V> {
+    inner: vec::IntoIter<V>,
+}
+
+impl<V> Iterator for IntoValues<V> {
+    type Item = V;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<V> ExactSizeIterator for IntoValues<V> {}
+
+impl<V> FusedIterator for IntoValues<V> {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::mem;
+
+    use super::*;
+    use crate::test_util::{differential, Colliding};
+
+    /// The arrays have one element per entry, each hash belongs to its key,
+    /// and every entry is linked exactly once from its bucket.
+    fn check_arrays<K: Hash, V, S: BuildHasher>(map: &SoaHashMap<K, V, S>) {
+        let len = map.len();
+        assert_eq!(map.hashes.len(), len);
+        assert_eq!(map.values.len(), len);
+        assert_eq!(map.next.len(), len);
+        let mut seen = vec![false; len];
+        for bucket in 0..map.buckets.len() {
+            for index in map.chain(bucket) {
+                assert!(!mem::replace(&mut seen[index], true));
+                assert_eq!(bucket_index(map.hashes[index], map.buckets.len()), bucket);
+            }
+        }
+        assert!(seen.into_iter().all(|linked| linked));
+        for (key, &hash) in map.keys.iter().zip(&map.hashes) {
+            assert_eq!(make_hash(&map.hash_builder, key), hash);
+        }
+    }
+
+    #[test]
+    fn differential_against_interleaved() {
+        for seed in 0..8 {
+            // few keys, so inserts and removals keep hitting the same ones
+            differential(seed, 64, SoaHashMap::new(), check_arrays);
+            differential(seed, 4096, SoaHashMap::new(), check_arrays);
+        }
+        for seed in 0..2 {
+            differential(seed, 200, SoaHashMap::with_hasher(Colliding::default()), check_arrays);
+        }
+    }
+
+    #[test]
+    fn swap_remove_keeps_arrays_in_step() {
+        // values name their key, so any slip between the arrays shows up
+        let mut map = SoaHashMap::with_hasher(Colliding::default());
+        for i in 0..10u32 {
+            map.insert(i, i.to_string());
+        }
+        // the last entry moves into the removed one's place in every array
+        assert_eq!(map.remove(&3), Some("3".to_string()));
+        assert_eq!(map.keys_slice()[3], 9);
+        assert_eq!(map.values_slice()[3], "9");
+        assert_eq!(map.remove(&9), Some("9".to_string()));
+        assert_eq!(map.keys_slice()[3], 8);
+        // removing the last entry itself moves nothing
+        assert_eq!(map.remove(&7), Some("7".to_string()));
+        assert_eq!(map.keys_slice()[..], [0, 1, 2, 8, 4, 5, 6]);
+        check_arrays(&map);
+        for (key, value) in map.keys_slice().iter().zip(map.values_slice()) {
+            assert_eq!(&key.to_string(), value);
+        }
+        assert!(map.iter().all(|(k, v)| map[k] == *v && k.to_string() == *v));
+    }
+
+    #[test]
+    fn retain_and_drain_keep_arrays_in_step() {
+        let mut map: SoaHashMap<u32, String> = (0..500).map(|i| (i, i.to_string())).collect();
+        map.retain(|&k, v| {
+            v.push('!');
+            k % 3 == 0
+        });
+        check_arrays(&map);
+        assert_eq!(map.len(), 167);
+        for (key, value) in map.keys_slice().iter().zip(map.values_slice()) {
+            assert_eq!(format!("{}!", key), *value);
+        }
+        let mut drained: Vec<_> = map.drain().collect();
+        assert!(map.is_empty());
+        check_arrays(&map);
+        drained.sort_unstable();
+        assert!(drained.iter().all(|(k, v)| format!("{}!", k) == *v));
+        assert_eq!(drained.len(), 167);
+    }
+
+    #[test]
+    fn values_come_from_the_dense_array() {
+        let mut map: SoaHashMap<u32, [u64; 16]> = (0..100).map(|i| (i, [u64::from(i); 16])).collect();
+        let slice = map.values_slice().as_ptr_range();
+        assert!(map.values().all(|v| slice.contains(&(v as *const _))));
+        assert!(map.values().zip(map.values_slice()).all(|(a, b)| core::ptr::eq(a, b)));
+        for value in map.values_mut() {
+            value[0] += 1000;
+        }
+        map.values_mut_slice()[0][1] = 7;
+        let first = map.keys_slice()[0];
+        assert_eq!(map[&first][1], 7);
+        assert!((0..100).all(|i| map[&i][0] == u64::from(i) + 1000));
+        assert_eq!(map.clone().into_values().map(|v| v[2]).sum::<u64>(), (0..100).sum::<u64>());
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut map = SoaHashMap::new();
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(map["the"], 3);
+        match map.entry("fox") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.insert(10), 1);
+                assert_eq!(e.remove_entry(), ("fox", 10));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry("fox") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(e) => assert_eq!(e.insert_entry(5).get(), &5),
+        }
+        map.entry("end").and_modify(|v| *v += 1).or_insert(0);
+        assert_eq!(map["end"], 2);
+        assert_eq!(*map.entry("zero").or_insert_default(), 0);
+        check_arrays(&map);
+    }
+
+    #[test]
+    fn parity_with_hash_map() {
+        let chained: HashMap<u32, u32> = (0..1000).map(|i| (i, i * 3)).collect();
+        let mut map: SoaHashMap<u32, u32> = chained.iter().map(|(&k, &v)| (k, v)).collect();
+        assert!(map == chained);
+        map.retain(|&k, _| k % 2 == 0);
+        map.reserve(5000);
+        check_arrays(&map);
+        map.shrink_to_fit();
+        check_arrays(&map);
+        assert_eq!(map.len(), 500);
+        assert_eq!(map.clone().into_keys().count(), 500);
+        assert_eq!(map.drain().map(|(_, v)| v).sum::<u32>(), (0..1000).step_by(2).map(|k| k * 3).sum::<u32>());
+        assert!(map.is_empty());
+        assert_eq!(format!("{:?}", SoaHashMap::from([(1, 2)])), "{1: 2}");
+    }
+}
@@ -0,0 +1,363 @@
+//! Maps laid out at compile time, for [`static_map!`](macro@crate::static_map).
+//!
+//! The macro hashes every literal key in a const context and places it in
+//! an open-addressed table of indices into the pairs as written, probing
+//! linearly past collisions, so a `static` holds the finished table and
+//! nothing runs at startup. Keys hash through [`StaticKey`] with a fixed
+//! FNV-1a over their bytes, which a lookup at run time repeats.
+//!
+//! A `const fn` can't call trait methods on stable Rust, so the compile
+//! time half lives in an inherent impl of [`Key`] per supported key type:
+//! string and byte string slices, `char` and the primitive integers.
+
+use core::fmt;
+use core::iter::FusedIterator;
+use core::ops::Index;
+use core::slice;
+
+use crate::{bucket_index, Equivalent};
+
+/// Marks an empty slot in a [`StaticMap`]'s table.
+#[doc(hidden)]
+pub const EMPTY: usize = usize::MAX;
+
+/// FNV-1a, which is short enough to write as a `const fn`.
+const fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+        i += 1;
+    }
+    hash
+}
+
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// Slots for `len` keys: at least twice as many, so probes stay short and
+/// always reach an empty slot.
+#[doc(hidden)]
+pub const fn slots_for(len: usize) -> usize {
+    (len * 2).next_power_of_two()
+}
+
+/// Slot a key with this hash is placed in first.
+#[doc(hidden)]
+pub const fn home(hash: u64, nslots: usize) -> usize {
+    bucket_index(hash, nslots)
+}
+
+/// A key as the macro sees it at compile time. Used by `static_map!`'s
+/// expansion only.
+#[doc(hidden)]
+pub struct Key<T>(pub T);
+
+impl Key<&str> {
+    pub const fn hash(&self) -> u64 {
+        fnv1a(self.0.as_bytes())
+    }
+
+    pub const fn same(&self, other: &Self) -> bool {
+        bytes_eq(self.0.as_bytes(), other.0.as_bytes())
+    }
+}
+
+impl Key<&[u8]> {
+    pub const fn hash(&self) -> u64 {
+        fnv1a(self.0)
+    }
+
+    pub const fn same(&self, other: &Self) -> bool {
+        bytes_eq(self.0, other.0)
+    }
+}
+
+impl Key<char> {
+    pub const fn hash(&self) -> u64 {
+        fnv1a(&(self.0 as u32).to_le_bytes())
+    }
+
+    pub const fn same(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+macro_rules! int_keys {
+    ($($int:ty),*) => {
+        $(
+            impl Key<$int> {
+                pub const fn hash(&self) -> u64 {
+                    fnv1a(&self.0.to_le_bytes())
+                }
+
+                pub const fn same(&self, other: &Self) -> bool {
+                    self.0 == other.0
+                }
+            }
+
+            impl StaticKey for $int {
+                fn static_hash(&self) -> u64 {
+                    Key(*self).hash()
+                }
+            }
+        )*
+    };
+}
+
+int_keys!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// Keys a [`StaticMap`] can be looked up by, hashed the way
+/// [`static_map!`](macro@crate::static_map) hashed its keys at compile time.
+pub trait StaticKey {
+    fn static_hash(&self) -> u64;
+}
+
+impl StaticKey for str {
+    fn static_hash(&self) -> u64 {
+        Key(self).hash()
+    }
+}
+
+impl StaticKey for [u8] {
+    fn static_hash(&self) -> u64 {
+        Key(self).hash()
+    }
+}
+
+impl StaticKey for char {
+    fn static_hash(&self) -> u64 {
+        Key(*self).hash()
+    }
+}
+
+impl<T: StaticKey + ?Sized> StaticKey for &T {
+    fn static_hash(&self) -> u64 {
+        (**self).static_hash()
+    }
+}
+
+/// A read-only map built at compile time by
+/// [`static_map!`](macro@crate::static_map).
+///
+/// It iterates in the order the pairs were written.
+pub struct StaticMap<K: 'static, V: 'static> {
+    entries: &'static [(K, V)],
+    // a power-of-two number of arena indices, `EMPTY` where unused
+    slots: &'static [usize],
+}
+
+impl<K, V> StaticMap<K, V> {
+    /// Used by `static_map!`'s expansion, which has already checked the
+    /// table.
+    #[doc(hidden)]
+    pub const fn from_parts(entries: &'static [(K, V)], slots: &'static [usize]) -> Self {
+        StaticMap { entries, slots }
+    }
+
+    pub const fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<K, V> {
+        Iter { inner: self.entries.iter() }
+    }
+
+    fn lookup<Q>(&self, key: &Q) -> Option<&'static (K, V)>
+    where
+        Q: StaticKey + Equivalent<K> + ?Sized,
+    {
+        let mask = self.slots.len() - 1;
+        let mut slot = home(key.static_hash(), self.slots.len());
+        loop {
+            let index = self.slots[slot];
+            if index == EMPTY {
+                return None;
+            }
+            let entry = &self.entries[index];
+            if key.equivalent(&entry.0) {
+                return Some(entry);
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&'static V>
+    where
+        Q: StaticKey + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).map(|(_, value)| value)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&'static K, &'static V)>
+    where
+        Q: StaticKey + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).map(|(key, value)| (key, value))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: StaticKey + Equivalent<K> + ?Sized,
+    {
+        self.lookup(key).is_some()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for StaticMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<K, V, Q> Index<&Q> for StaticMap<K, V>
+where
+    Q: StaticKey + Equivalent<K> + ?Sized,
+{
+    type Output = V;
+
+    /// Panics if `key` is not in the map.
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("StaticMap::index: key not found")
+    }
+}
+
+impl<K, V> IntoIterator for &StaticMap<K, V> {
+    type Item = (&'static K, &'static V);
+    type IntoIter = Iter<K, V>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// The pairs of a [`StaticMap`], in the order they were written.
+pub struct Iter<K: 'static, V: 'static> {
+    inner: slice::Iter<'static, (K, V)>,
+}
+
+impl<K, V> Clone for Iter<K, V> {
+    fn clone(&self) -> Self {
+        Iter { inner: self.inner.clone() }
+    }
+}
+
+impl<K, V> Iterator for Iter<K, V> {
+    type Item = (&'static K, &'static V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(key, value)| (key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<K, V> {}
+
+impl<K, V> FusedIterator for Iter<K, V> {}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    crate::static_map! {
+        static KEYWORDS: &'static str => u32 = {
+            "as" => 0, "break" => 1, "const" => 2, "continue" => 3, "crate" => 4,
+            "else" => 5, "enum" => 6, "extern" => 7, "false" => 8, "fn" => 9,
+            "for" => 10, "if" => 11, "impl" => 12, "in" => 13, "let" => 14,
+            "loop" => 15, "match" => 16, "mod" => 17, "move" => 18, "mut" => 19,
+            "pub" => 20, "ref" => 21, "return" => 22, "self" => 23, "Self" => 24,
+            "static" => 25, "struct" => 26, "super" => 27, "trait" => 28, "true" => 29,
+            "type" => 30, "unsafe" => 31, "use" => 32, "where" => 33, "while" => 34,
+        };
+        static PRIMES: u64 => &'static str = {
+            2 => "two", 3 => "three", 5 => "five", 7 => "seven", 1 << 61 => "big",
+            u64::MAX => "max",
+        };
+        static EMPTY_MAP: char => () = {};
+        static BYTES: &'static [u8] => char = { b"GET" => 'g', b"PUT" => 'p', b"" => 'e' };
+    }
+
+    const KEYWORD_LIST: [&str; 35] = [
+        "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn",
+        "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+        "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
+        "use", "where", "while",
+    ];
+
+    #[test]
+    fn str_keys() {
+        assert_eq!(KEYWORDS.len(), KEYWORD_LIST.len());
+        for (i, &keyword) in KEYWORD_LIST.iter().enumerate() {
+            assert_eq!(KEYWORDS.get(keyword), Some(&(i as u32)));
+            // also through `&&str` and a key that isn't `'static`
+            assert_eq!(KEYWORDS.get(&keyword), Some(&(i as u32)));
+            assert!(KEYWORDS.contains_key(keyword.to_string().as_str()));
+        }
+        for miss in ["", "As", "fnn", "dyn", "async", "selF"] {
+            assert_eq!(KEYWORDS.get(miss), None);
+        }
+        assert_eq!(KEYWORDS.get_key_value("mut"), Some((&"mut", &19)));
+        // iteration keeps the order the pairs were written in
+        assert!(KEYWORDS.iter().map(|(k, _)| *k).eq(KEYWORD_LIST));
+    }
+
+    #[test]
+    fn collisions_were_probed_past() {
+        // 35 keys in 128 slots are all but sure to share a home somewhere
+        let displaced = (0..KEYWORDS.slots.len())
+            .filter(|&slot| {
+                let index = KEYWORDS.slots[slot];
+                index != EMPTY && home(KEYWORDS.entries[index].0.static_hash(), KEYWORDS.slots.len()) != slot
+            })
+            .count();
+        assert!(displaced > 0);
+        assert_eq!(KEYWORDS.slots.iter().filter(|&&index| index != EMPTY).count(), KEYWORDS.len());
+    }
+
+    #[test]
+    fn integer_and_other_keys() {
+        assert_eq!(PRIMES.get(&7), Some(&"seven"));
+        assert_eq!(PRIMES.get(&(1 << 61)), Some(&"big"));
+        assert_eq!(PRIMES[&u64::MAX], "max");
+        assert!(!PRIMES.contains_key(&4));
+        assert!(!PRIMES.contains_key(&0));
+        assert_eq!(PRIMES.len(), 6);
+
+        assert!(EMPTY_MAP.is_empty());
+        assert_eq!(EMPTY_MAP.get(&'x'), None);
+        assert_eq!(EMPTY_MAP.iter().count(), 0);
+
+        assert_eq!(BYTES.get(&b"PUT"[..]), Some(&'p'));
+        assert_eq!(BYTES.get(&b""[..]), Some(&'e'));
+        assert_eq!(BYTES.get(&b"POST"[..]), None);
+        assert_eq!(format!("{:?}", PRIMES.iter().next()), r#"Some((2, "two"))"#);
+    }
+
+    #[test]
+    fn hashes_match_at_compile_and_run_time() {
+        const AT_COMPILE_TIME: u64 = Key("fn").hash();
+        assert_eq!(AT_COMPILE_TIME, "fn".static_hash());
+        assert_eq!(Key(42u16).hash(), 42u16.static_hash());
+        assert_eq!(Key('é').hash(), 'é'.static_hash());
+        // FNV-1a's published test vector
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+    }
+}
@@ -0,0 +1,746 @@
+//! A SwissTable-style [`Table`] for [`HashMap`]: one control byte per
+//! slot, scanned a group at a time.
+//!
+//! A map on a [`SwissTable`] keeps its entries in the same dense arena as
+//! the crate's other maps. Beside the table of slots it keeps a control
+//! byte per slot, holding either a 7-bit fragment of the hash of the entry
+//! there or a marker for an empty or deleted slot. A lookup compares a
+//! whole group of control bytes against the fragment it wants at once,
+//! using SSE2 or NEON where the target has them and a portable loop
+//! otherwise, and only looks at the entries whose fragment matched. Groups
+//! are visited in triangular steps, which on a power-of-two table reach
+//! every group exactly once.
+//!
+//! Removing an entry leaves a deleted marker unless no probe could ever
+//! have passed over the slot, so that lookups for keys placed after it
+//! still find them. Deleted slots are reused by inserts and cleared out
+//! whenever the table is rebuilt.
+//!
+//! The `swiss` feature adds this table as a storage backend for every
+//! `HashMap`, picked by its table parameter, rather than swapping the
+//! default one. Much of `HashMap`'s API is about its chains: bucket counts
+//! and [`bucket_for`](HashMap::bucket_for), two-choice placement,
+//! incremental rehashing, the miss filter, and the arena layout
+//! [`freeze`](HashMap::freeze) packs, and since Cargo unifies features, a
+//! flag that took those away from `HashMap<K, V>` would break any crate
+//! sharing a build with one that turned it on.
+
+use alloc::vec::Vec;
+
+use crate::{bucket_index, sealed, DefaultState, HashMap, Table, TryReserveError, NONE};
+
+use self::group::{Group, GROUP};
+
+/// Control byte of a slot that has never held an entry since the last
+/// rebuild. Full slots hold a hash fragment below 0x80.
+const EMPTY: u8 = 0xff;
+
+/// Control byte of a slot whose entry was removed.
+const DELETED: u8 = 0x80;
+
+/// Entries `nslots` slots hold before the table grows.
+fn capacity_of(nslots: usize) -> usize {
+    nslots / 8 * 7
+}
+
+/// Smallest power-of-two slot count with room for `capacity` entries, or
+/// `None` if that count does not fit in a `usize`. Tables are never
+/// smaller than a group, so a group read never sees a slot twice.
+fn slots_for(capacity: usize) -> Option<usize> {
+    if capacity == 0 {
+        return Some(0);
+    }
+    let mut nslots = GROUP;
+    while capacity_of(nslots) < capacity {
+        nslots = nslots.checked_mul(2)?;
+    }
+    Some(nslots)
+}
+
+/// The 7-bit fragment of `hash` kept in the control bytes. It is taken from
+/// the top of the same mixed hash `bucket_index` uses, where the index does
+/// not look.
+fn fragment(hash: u64) -> u8 {
+    (hash.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 57) as u8
+}
+
+mod group {
+    //! Scans over a group of control bytes.
+    //!
+    //! Every scan answers with a [`BitMask`] whose bit `i` stands for byte
+    //! `i` of the group. The SIMD versions are picked at compile time;
+    //! tests can make a thread use the portable one instead, so both get
+    //! exercised on machines that have SIMD.
+
+    /// Control bytes read at once.
+    pub(super) const GROUP: usize = 16;
+
+    #[derive(Clone, Copy)]
+    pub(super) struct Group([u8; GROUP]);
+
+    impl Group {
+        /// The group starting at the front of `ctrl`.
+        pub(super) fn load(ctrl: &[u8]) -> Group {
+            Group(ctrl[..GROUP].try_into().unwrap())
+        }
+
+        /// Bytes equal to `byte`.
+        pub(super) fn match_byte(self, byte: u8) -> BitMask {
+            #[cfg(all(test, feature = "std"))]
+            if portable::forced() {
+                return BitMask(portable::match_byte(&self.0, byte));
+            }
+            BitMask(native::match_byte(&self.0, byte))
+        }
+
+        /// Bytes with the high bit set, i.e. empty or deleted slots.
+        pub(super) fn match_empty_or_deleted(self) -> BitMask {
+            #[cfg(all(test, feature = "std"))]
+            if portable::forced() {
+                return BitMask(portable::match_high_bit(&self.0));
+            }
+            BitMask(native::match_high_bit(&self.0))
+        }
+
+        pub(super) fn match_empty(self) -> BitMask {
+            self.match_byte(super::EMPTY)
+        }
+    }
+
+    /// Set bits of a group scan, iterated lowest first.
+    #[derive(Clone, Copy)]
+    pub(super) struct BitMask(u16);
+
+    impl BitMask {
+        pub(super) fn any(self) -> bool {
+            self.0 != 0
+        }
+
+        pub(super) fn lowest(self) -> Option<usize> {
+            self.any().then(|| self.0.trailing_zeros() as usize)
+        }
+
+        /// Unset bytes at the start of the group.
+        pub(super) fn leading_unset(self) -> usize {
+            self.0.trailing_zeros() as usize
+        }
+
+        /// Unset bytes at the end of the group.
+        pub(super) fn trailing_unset(self) -> usize {
+            self.0.leading_zeros() as usize
+        }
+    }
+
+    impl Iterator for BitMask {
+        type Item = usize;
+
+        fn next(&mut self) -> Option<usize> {
+            let bit = self.lowest()?;
+            self.0 &= self.0 - 1;
+            Some(bit)
+        }
+    }
+
+    // Only reached from tests when a SIMD version is compiled in.
+    #[cfg_attr(not(all(test, feature = "std")), allow(dead_code))]
+    pub(super) mod portable {
+        use super::GROUP;
+
+        #[cfg(all(test, feature = "std"))]
+        std::thread_local! {
+            static FORCED: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+        }
+
+        /// Makes this thread's scans use the portable code from now on.
+        #[cfg(all(test, feature = "std"))]
+        pub(crate) fn force() {
+            FORCED.with(|forced| forced.set(true));
+        }
+
+        #[cfg(all(test, feature = "std"))]
+        pub(super) fn forced() -> bool {
+            FORCED.with(|forced| forced.get())
+        }
+
+        pub(crate) fn match_byte(bytes: &[u8; GROUP], byte: u8) -> u16 {
+            let mut mask = 0;
+            for (i, &b) in bytes.iter().enumerate() {
+                mask |= u16::from(b == byte) << i;
+            }
+            mask
+        }
+
+        pub(crate) fn match_high_bit(bytes: &[u8; GROUP]) -> u16 {
+            let mut mask = 0;
+            for (i, &b) in bytes.iter().enumerate() {
+                mask |= u16::from(b >> 7) << i;
+            }
+            mask
+        }
+    }
+
+    #[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), target_feature = "sse2"))]
+    pub(super) mod native {
+        #[cfg(target_arch = "x86")]
+        use core::arch::x86::*;
+        #[cfg(target_arch = "x86_64")]
+        use core::arch::x86_64::*;
+
+        use super::GROUP;
+
+        pub(crate) fn match_byte(bytes: &[u8; GROUP], byte: u8) -> u16 {
+            // SAFETY: SSE2 is enabled for the whole build, and the unaligned
+            // load reads exactly the 16 bytes of `bytes`.
+            unsafe {
+                let group = _mm_loadu_si128(bytes.as_ptr().cast());
+                let equal = _mm_cmpeq_epi8(group, _mm_set1_epi8(byte as i8));
+                _mm_movemask_epi8(equal) as u16
+            }
+        }
+
+        pub(crate) fn match_high_bit(bytes: &[u8; GROUP]) -> u16 {
+            // SAFETY: as in `match_byte`.
+            unsafe { _mm_movemask_epi8(_mm_loadu_si128(bytes.as_ptr().cast())) as u16 }
+        }
+    }
+
+    #[cfg(all(target_arch = "aarch64", target_feature = "neon"))]
+    pub(super) mod native {
+        use core::arch::aarch64::*;
+
+        use super::GROUP;
+
+        /// Bit each lane contributes to the mask, per half.
+        const LANE_BITS: [u8; GROUP] = [1, 2, 4, 8, 16, 32, 64, 128, 1, 2, 4, 8, 16, 32, 64, 128];
+
+        pub(crate) fn match_byte(bytes: &[u8; GROUP], byte: u8) -> u16 {
+            // SAFETY: NEON is enabled for the whole build, and the load reads
+            // exactly the 16 bytes of `bytes`.
+            unsafe { movemask(vceqq_u8(vld1q_u8(bytes.as_ptr()), vdupq_n_u8(byte))) }
+        }
+
+        pub(crate) fn match_high_bit(bytes: &[u8; GROUP]) -> u16 {
+            // SAFETY: as in `match_byte`.
+            unsafe { movemask(vcltzq_s8(vreinterpretq_s8_u8(vld1q_u8(bytes.as_ptr())))) }
+        }
+
+        /// Packs lanes that are all ones into one bit each, like SSE2's
+        /// `movemask`, which NEON lacks.
+        unsafe fn movemask(lanes: uint8x16_t) -> u16 {
+            let bits = vandq_u8(lanes, vld1q_u8(LANE_BITS.as_ptr()));
+            let low = u16::from(vaddv_u8(vget_low_u8(bits)));
+            let high = u16::from(vaddv_u8(vget_high_u8(bits)));
+            low | high << 8
+        }
+    }
+
+    #[cfg(not(any(
+        all(any(target_arch = "x86", target_arch = "x86_64"), target_feature = "sse2"),
+        all(target_arch = "aarch64", target_feature = "neon"),
+    )))]
+    pub(super) use self::portable as native;
+}
+
+/// A [`Table`] with SwissTable-style control bytes, scanned a group of
+/// slots at a time.
+///
+/// A map on it has the same API as any [`HashMap`], entry API included;
+/// the chained table's own settings, like the load factor, don't apply:
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use hashmap::{HashMap, SwissTable};
+///
+/// let mut map = HashMap::with_table(SwissTable::new());
+/// map.insert("a", 1);
+/// *map.entry("b").or_insert(0) += 2;
+/// assert_eq!(map.get("a"), Some(&1));
+/// assert_eq!(map.remove("b"), Some(2));
+/// assert_eq!(map.len(), 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SwissTable {
+    /// One control byte per slot, then the first `GROUP` again so a group
+    /// can be read from any slot without wrapping.
+    ctrl: Vec<u8>,
+    /// Arena index of the entry in each full slot.
+    slots: Vec<usize>,
+    /// Inserts into empty slots left before the table must be rebuilt.
+    growth_left: usize,
+}
+
+impl SwissTable {
+    /// An empty table; it allocates its slots on the first insert.
+    pub fn new() -> Self {
+        SwissTable {
+            ctrl: Vec::new(),
+            slots: Vec::new(),
+            growth_left: 0,
+        }
+    }
+}
+
+/// A [`HashMap`] on a [`SwissTable`].
+pub type SwissMap<K, V, S = DefaultState> = HashMap<K, V, S, SwissTable>;
+
+impl sealed::Sealed for SwissTable {}
+
+impl Table for SwissTable {
+    /// Number of entries the map can hold before its table grows. Deleted
+    /// slots count against this until the next rebuild.
+    fn capacity(&self) -> usize {
+        capacity_of(self.slots.len())
+    }
+
+    fn find<K, V, S>(map: &HashMap<K, V, S, Self>, hash: u64, is_match: impl FnMut(&K) -> bool) -> Option<usize> {
+        map.find(hash, is_match).map(|(_, index)| index)
+    }
+
+    // `push` rebuilds the table once it runs out of empty slots, so a
+    // lookup that finds its key never grows it
+    fn reserve_one<K, V, S>(_map: &mut HashMap<K, V, S, Self>) {}
+
+    fn push<K, V, S>(map: &mut HashMap<K, V, S, Self>, hash: u64, key: K, value: V) -> usize {
+        map.push_entry(hash, key, value).1
+    }
+
+    fn remove<K, V, S>(map: &mut HashMap<K, V, S, Self>, index: usize) -> (u64, K, V) {
+        let slot = map.slot_of(index);
+        map.remove_at(slot, index)
+    }
+
+    fn unlink_all<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.reset();
+    }
+
+    fn retained<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.rebuild(map.table.slots.len());
+    }
+
+    fn reserve<K, V, S>(map: &mut HashMap<K, V, S, Self>, additional: usize) {
+        let needed = map.len().checked_add(additional).expect("capacity overflow");
+        map.entries.reserve(additional);
+        if additional > map.table.growth_left {
+            let nslots = slots_for(needed).expect("capacity overflow");
+            map.rebuild(nslots.max(map.table.slots.len()));
+        }
+    }
+
+    fn try_reserve<K, V, S>(map: &mut HashMap<K, V, S, Self>, additional: usize) -> Result<(), TryReserveError> {
+        let needed = map.len().checked_add(additional).ok_or(TryReserveError::CapacityOverflow)?;
+        map.entries.try_reserve(additional).map_err(|_| TryReserveError::AllocError)?;
+        if additional > map.table.growth_left {
+            let nslots = slots_for(needed).ok_or(TryReserveError::CapacityOverflow)?;
+            let nslots = nslots.max(map.table.slots.len());
+            // `rebuild` refills both arrays where they stand
+            let table = &mut map.table;
+            table
+                .ctrl
+                .try_reserve_exact(nslots + GROUP - table.ctrl.len())
+                .and_then(|()| table.slots.try_reserve_exact(nslots - table.slots.len()))
+                .map_err(|_| TryReserveError::AllocError)?;
+            map.rebuild(nslots);
+        }
+        Ok(())
+    }
+
+    /// Shrinks the table as far as the current entries allow.
+    fn shrink_to_fit<K, V, S>(map: &mut HashMap<K, V, S, Self>) {
+        map.entries.shrink_to_fit();
+        map.rebuild(slots_for(map.len()).expect("capacity overflow"));
+        map.table.ctrl.shrink_to_fit();
+        map.table.slots.shrink_to_fit();
+    }
+
+    // only `shrink_to_fit` gives slots back
+    fn maybe_shrink<K, V, S>(_map: &mut HashMap<K, V, S, Self>) {}
+}
+
+impl<K, V, S> HashMap<K, V, S, SwissTable> {
+    fn mask(&self) -> usize {
+        self.table.slots.len() - 1
+    }
+
+    /// Marks every slot empty.
+    fn reset(&mut self) {
+        self.table.ctrl.fill(EMPTY);
+        self.table.slots.fill(NONE);
+        self.table.growth_left = self.capacity();
+    }
+
+    /// Sets the control byte of `slot`, and its copy past the end if it is
+    /// among the first `GROUP`.
+    fn set_ctrl(&mut self, slot: usize, byte: u8) {
+        let mirror = (slot.wrapping_sub(GROUP) & self.mask()) + GROUP;
+        self.table.ctrl[slot] = byte;
+        self.table.ctrl[mirror] = byte;
+    }
+
+    /// Group starts on the probe sequence for `hash`, in triangular steps.
+    fn probe(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let mask = self.mask();
+        let mut pos = bucket_index(hash, self.table.slots.len());
+        let mut stride = 0;
+        core::iter::from_fn(move || {
+            let at = pos;
+            stride += GROUP;
+            pos = (pos + stride) & mask;
+            Some(at)
+        })
+    }
+
+    /// Slot and arena index of the entry with this `hash` whose key
+    /// `is_match` accepts.
+    fn find<F>(&self, hash: u64, mut is_match: F) -> Option<(usize, usize)>
+    where
+        F: FnMut(&K) -> bool,
+    {
+        if self.table.slots.is_empty() {
+            return None;
+        }
+        let fragment = fragment(hash);
+        for pos in self.probe(hash) {
+            let group = Group::load(&self.table.ctrl[pos..]);
+            for bit in group.match_byte(fragment) {
+                let slot = (pos + bit) & self.mask();
+                let index = self.table.slots[slot];
+                let (ehash, ekey, _) = &self.entries[index];
+                if *ehash == hash && is_match(ekey) {
+                    return Some((slot, index));
+                }
+            }
+            if group.match_empty().any() {
+                return None;
+            }
+        }
+        unreachable!("probe sequences never end")
+    }
+
+    /// Slot that points at `entries[index]`.
+    fn slot_of(&self, index: usize) -> usize {
+        let hash = self.entries[index].0;
+        let fragment = fragment(hash);
+        self.probe(hash)
+            .find_map(|pos| {
+                Group::load(&self.table.ctrl[pos..])
+                    .match_byte(fragment)
+                    .map(|bit| (pos + bit) & self.mask())
+                    .find(|&slot| self.table.slots[slot] == index)
+            })
+            .expect("entry missing from its probe sequence")
+    }
+
+    /// First empty or deleted slot on the probe sequence for `hash`.
+    fn insert_slot(&self, hash: u64) -> usize {
+        self.probe(hash)
+            .find_map(|pos| {
+                let free = Group::load(&self.table.ctrl[pos..]).match_empty_or_deleted().lowest()?;
+                Some((pos + free) & self.mask())
+            })
+            .unwrap()
+    }
+
+    /// Puts `entries[index]` in a free slot on its probe sequence. There
+    /// must be one left to take.
+    fn place(&mut self, index: usize) -> usize {
+        let hash = self.entries[index].0;
+        let slot = self.insert_slot(hash);
+        if self.table.ctrl[slot] == EMPTY {
+            self.table.growth_left -= 1;
+        }
+        self.set_ctrl(slot, fragment(hash));
+        self.table.slots[slot] = index;
+        slot
+    }
+
+    /// Adds an entry to the arena and gives it a slot, rebuilding the
+    /// table first if it has no empty slots to spare. Returns its slot and
+    /// arena index.
+    fn push_entry(&mut self, hash: u64, key: K, value: V) -> (usize, usize) {
+        if self.table.growth_left == 0 {
+            self.reserve_one();
+        }
+        let index = self.entries.len();
+        self.entries.push((hash, key, value));
+        (self.place(index), index)
+    }
+
+    /// Makes room for one more entry: deleted slots alone are cleared out
+    /// if that frees enough, otherwise the table doubles.
+    fn reserve_one(&mut self) {
+        let nslots = self.table.slots.len();
+        if self.len() < self.capacity() / 2 {
+            self.rebuild(nslots);
+        } else {
+            self.rebuild((nslots * 2).max(GROUP));
+        }
+    }
+
+    /// Lays the arena out over `nslots` fresh slots.
+    fn rebuild(&mut self, nslots: usize) {
+        self.table.ctrl.clear();
+        self.table.slots.clear();
+        if nslots > 0 {
+            self.table.ctrl.resize(nslots + GROUP, EMPTY);
+            self.table.slots.resize(nslots, NONE);
+        }
+        self.table.growth_left = self.capacity();
+        for index in 0..self.entries.len() {
+            self.place(index);
+        }
+    }
+
+    /// Removes the entry at arena `index`, which `slot` points at. The last
+    /// entry is swapped into its place in the arena, and its slot updated.
+    fn remove_at(&mut self, slot: usize, index: usize) -> (u64, K, V) {
+        // A probe that reads a group holding an empty slot stops there, so
+        // if every group read covering `slot` also covers an empty slot, no
+        // probe has gone past it and it can be made empty again.
+        let before = Group::load(&self.table.ctrl[slot.wrapping_sub(GROUP) & self.mask()..]).match_empty();
+        let after = Group::load(&self.table.ctrl[slot..]).match_empty();
+        if before.trailing_unset() + after.leading_unset() >= GROUP {
+            self.set_ctrl(slot, DELETED);
+        } else {
+            self.set_ctrl(slot, EMPTY);
+            self.table.growth_left += 1;
+        }
+        self.table.slots[slot] = NONE;
+        let last = self.entries.len() - 1;
+        if index != last {
+            let moved = self.slot_of(last);
+            self.table.slots[moved] = index;
+        }
+        self.entries.swap_remove(index)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::hash::BuildHasher;
+    use core::mem;
+
+    use super::group::{native, portable};
+    use super::*;
+    use crate::test_util::{differential, sorted, Colliding, XorShift};
+    use crate::{BuildNoHashHasher, Entry};
+
+    /// Every entry is pointed at by exactly one slot whose control byte is
+    /// its fragment, the mirrored bytes match and `growth_left` is right.
+    fn check_table<K, V, S>(map: &SwissMap<K, V, S>) {
+        let nslots = map.table.slots.len();
+        let mut seen = vec![false; map.len()];
+        let mut deleted = 0;
+        for (slot, &index) in map.table.slots.iter().enumerate() {
+            match map.table.ctrl[slot] {
+                EMPTY | DELETED => {
+                    assert_eq!(index, NONE);
+                    deleted += usize::from(map.table.ctrl[slot] == DELETED);
+                }
+                byte => {
+                    assert_eq!(fragment(map.entries[index].0), byte);
+                    assert!(!mem::replace(&mut seen[index], true));
+                }
+            }
+        }
+        assert!(seen.into_iter().all(|pointed_at| pointed_at));
+        if nslots > 0 {
+            assert_eq!(map.table.ctrl.len(), nslots + GROUP);
+            assert_eq!(map.table.ctrl[..GROUP], map.table.ctrl[nslots..]);
+        }
+        assert_eq!(map.table.growth_left, map.capacity() - map.len() - deleted);
+    }
+
+    fn deleted<K, V, S>(map: &SwissMap<K, V, S>) -> usize {
+        map.table.ctrl[..map.table.slots.len()].iter().filter(|&&byte| byte == DELETED).count()
+    }
+
+    fn differential_suite() {
+        for seed in 0..8 {
+            // few keys, so inserts and removals keep hitting the same ones
+            differential(seed, 64, HashMap::with_table(SwissTable::new()), check_table);
+            differential(seed, 4096, HashMap::with_table(SwissTable::new()), check_table);
+        }
+        for seed in 0..2 {
+            differential(seed, 200, HashMap::with_table_and_hasher(SwissTable::new(), Colliding::default()), check_table);
+        }
+    }
+
+    #[test]
+    fn differential_against_chaining() {
+        differential_suite();
+    }
+
+    #[test]
+    fn differential_against_chaining_portable() {
+        portable::force();
+        differential_suite();
+    }
+
+    #[test]
+    fn native_scans_match_portable() {
+        let mut rng = XorShift(0x1234_5678);
+        for _ in 0..10_000 {
+            let mut bytes = [0; GROUP];
+            for byte in &mut bytes {
+                // mostly control-byte-like values, so matches are common
+                *byte = match rng.next() % 4 {
+                    0 => EMPTY,
+                    1 => DELETED,
+                    _ => rng.next() as u8 & 0x07,
+                };
+            }
+            let byte = rng.next() as u8 & 0x07;
+            assert_eq!(native::match_byte(&bytes, byte), portable::match_byte(&bytes, byte));
+            assert_eq!(native::match_byte(&bytes, EMPTY), portable::match_byte(&bytes, EMPTY));
+            assert_eq!(native::match_high_bit(&bytes), portable::match_high_bit(&bytes));
+        }
+    }
+
+    #[test]
+    fn bitmask_helpers() {
+        let mut bytes = [0; GROUP];
+        bytes[3] = EMPTY;
+        bytes[9] = DELETED;
+        bytes[12] = EMPTY;
+        let empty = Group::load(&bytes).match_empty();
+        assert_eq!(empty.lowest(), Some(3));
+        assert_eq!(empty.leading_unset(), 3);
+        assert_eq!(empty.trailing_unset(), 3);
+        assert_eq!(empty.collect::<Vec<_>>(), [3, 12]);
+        let free = Group::load(&bytes).match_empty_or_deleted();
+        assert_eq!(free.collect::<Vec<_>>(), [3, 9, 12]);
+        assert!(!Group::load(&[0; GROUP]).match_empty().any());
+    }
+
+    #[test]
+    fn removal_leaves_tombstones_only_when_needed() {
+        let mut map = HashMap::with_table_and_hasher(SwissTable::new(), Colliding::default());
+        map.reserve(100);
+        // one key in an otherwise empty table: its slot can go back to empty
+        map.insert(0u32, 0u32);
+        map.remove(&0);
+        assert_eq!(deleted(&map), 0);
+        check_table(&map);
+
+        // seventeen colliding keys fill the first group and spill past it,
+        // so emptying a slot in that group would hide the last key
+        for i in 0..17 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.remove(&3), Some(3));
+        assert_eq!(deleted(&map), 1);
+        assert_eq!(map.get(&16), Some(&16));
+        check_table(&map);
+
+        // the next insert reuses the deleted slot
+        map.insert(100, 100);
+        assert_eq!(deleted(&map), 0);
+        assert!((0..17).filter(|&i| i != 3).all(|i| map.get(&i) == Some(&i)));
+        check_table(&map);
+    }
+
+    #[test]
+    fn tombstones_are_cleared_without_growing() {
+        let mut map: SwissMap<u64, u64, BuildNoHashHasher<u64>> =
+            HashMap::with_table_and_hasher(SwissTable::new(), Default::default());
+        map.reserve(100);
+        let nslots = map.table.slots.len();
+        let hasher = BuildNoHashHasher::<u64>::default();
+        let home = |k: u64| bucket_index(hasher.hash_one(k), nslots);
+        // 96 keys homed at slot 0 fill the groups their probe visits, at
+        // 0, 16, 48, 96, 32 and 112. Removing the first 80 leaves nothing
+        // but tombstones, since none of them is next to an empty slot.
+        let cluster: Vec<u64> = (0..).filter(|&k| home(k) == 0).take(96).collect();
+        // keys with their own homes in the untouched 64..96 then use up
+        // the empty slots
+        let mut spread = (64..96).map(|h| (0..).find(|&k| home(k) == h).unwrap());
+        for &k in &cluster {
+            map.insert(k, k);
+        }
+        for k in &cluster[..80] {
+            map.remove(k);
+        }
+        assert_eq!(deleted(&map), 80);
+        while map.table.growth_left > 0 {
+            let k = spread.next().unwrap();
+            map.insert(k, k);
+        }
+        check_table(&map);
+
+        // with most of the table dead, the next insert rebuilds it in place
+        let k = spread.next().unwrap();
+        map.insert(k, k);
+        assert_eq!(map.table.slots.len(), nslots);
+        assert_eq!(deleted(&map), 0);
+        check_table(&map);
+        assert!(cluster[80..].iter().all(|k| map.get(k) == Some(k)));
+        assert!(cluster[..80].iter().all(|k| map.get(k).is_none()));
+    }
+
+    #[test]
+    fn probes_reach_every_group() {
+        // keys homed in one slot of a big table keep probing outward in
+        // triangular steps, and all of them stay findable
+        let mut map: SwissMap<u64, u64, BuildNoHashHasher<u64>> =
+            HashMap::with_table_and_hasher(SwissTable::new(), Default::default());
+        map.reserve(800);
+        let nslots = map.table.slots.len();
+        let keys: Vec<u64> = (0..)
+            .filter(|&k| bucket_index(map.hasher().hash_one(k), nslots) == nslots - 3)
+            .take(200)
+            .collect();
+        for &k in &keys {
+            map.insert(k, k);
+        }
+        assert_eq!(map.table.slots.len(), nslots);
+        assert!(keys.iter().all(|k| map.get(k) == Some(k)));
+        check_table(&map);
+        for &k in keys.iter().step_by(2) {
+            assert_eq!(map.remove(&k), Some(k));
+        }
+        assert!(keys.iter().skip(1).step_by(2).all(|k| map.get(k) == Some(k)));
+        check_table(&map);
+    }
+
+    #[test]
+    fn entry_api() {
+        let mut map = HashMap::with_table(SwissTable::new());
+        for word in "the quick brown fox jumps over the lazy dog the end".split(' ') {
+            *map.entry(word).or_insert(0) += 1;
+        }
+        assert_eq!(map["the"], 3);
+        match map.entry("fox") {
+            Entry::Occupied(mut e) => {
+                assert_eq!(e.insert(10), 1);
+                assert_eq!(e.remove_entry(), ("fox", 10));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry("fox") {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(e) => assert_eq!(e.insert_entry(5).get(), &5),
+        }
+        map.entry("end").and_modify(|v| *v += 1).or_insert(0);
+        assert_eq!(map["end"], 2);
+        assert_eq!(*map.entry("zero").or_insert_default(), 0);
+        check_table(&map);
+    }
+
+    #[test]
+    fn parity_with_linear_map() {
+        let linear: crate::LinearMap<u32, u32> = (0..1000).map(|i| (i, i * 3)).collect();
+        let mut map: SwissMap<u32, u32> = linear.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(sorted(map.iter()), sorted(linear.iter()));
+        map.retain(|&k, _| k % 2 == 0);
+        map.reserve(5000);
+        check_table(&map);
+        map.shrink_to_fit();
+        check_table(&map);
+        assert_eq!(map.len(), 500);
+        assert_eq!(map.clone().into_keys().count(), 500);
+        assert_eq!(map.drain().map(|(_, v)| v).sum::<u32>(), (0..1000).step_by(2).map(|k| k * 3).sum::<u32>());
+        assert!(map.is_empty());
+        check_table(&map);
+        assert_eq!(format!("{:?}", [(1, 2)].into_iter().collect::<SwissMap<_, _>>()), "{1: 2}");
+    }
+}
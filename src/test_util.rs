@@ -0,0 +1,183 @@
+//! Scaffolding shared by the tests of the map types: hashers that collide
+//! on purpose, a reproducible random source, and one differential suite
+//! that drives any of the maps against a chaining [`HashMap`].
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, BuildHasherDefault, Hasher};
+
+use crate::HashMap;
+
+// Sends every key to the same bucket or home slot.
+#[derive(Default, Clone)]
+pub(crate) struct CollidingHasher;
+
+impl Hasher for CollidingHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {}
+}
+
+pub(crate) type Colliding = BuildHasherDefault<CollidingHasher>;
+
+// Hashes keys in runs of `GROUP` to the same value, so homes pile up in
+// clusters the way badly distributed real-world keys do.
+#[derive(Default)]
+pub(crate) struct ClusteringHasher<const GROUP: u64>(u64);
+
+impl<const GROUP: u64> Hasher for ClusteringHasher<GROUP> {
+    fn finish(&self) -> u64 {
+        self.0 / GROUP
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes.iter().rev() {
+            self.0 = self.0 << 8 | u64::from(byte);
+        }
+    }
+}
+
+pub(crate) type Clustering<const GROUP: u64> = BuildHasherDefault<ClusteringHasher<GROUP>>;
+
+// A tiny xorshift so the sequences are reproducible without extra
+// dev-deps.
+pub(crate) struct XorShift(pub(crate) u64);
+
+impl XorShift {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+pub(crate) fn sorted<'a>(iter: impl Iterator<Item = (&'a u32, &'a u32)>) -> Vec<(u32, u32)> {
+    let mut pairs: Vec<_> = iter.map(|(&k, &v)| (k, v)).collect();
+    pairs.sort_unstable();
+    pairs
+}
+
+/// What [`differential`] needs from a map, with `u32` keys and values.
+pub(crate) trait TestMap {
+    fn insert(&mut self, key: u32, value: u32) -> Option<u32>;
+    fn remove(&mut self, key: u32) -> Option<u32>;
+    fn get(&self, key: u32) -> Option<&u32>;
+    fn contains_key(&self, key: u32) -> bool;
+    fn retain(&mut self, keep: fn(&u32, &mut u32) -> bool);
+    fn clear(&mut self);
+    fn len(&self) -> usize;
+    /// Every entry, sorted.
+    fn pairs(&self) -> Vec<(u32, u32)>;
+    /// Adds one to the value through `entry(key).or_insert(0)`.
+    fn bump(&mut self, key: u32);
+    /// Removes the entry through an occupied `Entry` and returns it, or
+    /// fills a vacant one with `value`.
+    fn toggle(&mut self, key: u32, value: u32) -> Option<(u32, u32)>;
+}
+
+macro_rules! test_maps {
+    ($($(#[$attr:meta])* $map:ident $(<$table:path>)? in $($module:ident)::+;)*) => {$(
+        $(#[$attr])*
+        impl<S: BuildHasher> TestMap for $($module)::+::$map<u32, u32, S $(, $table)?> {
+            fn insert(&mut self, key: u32, value: u32) -> Option<u32> {
+                self.insert(key, value)
+            }
+
+            fn remove(&mut self, key: u32) -> Option<u32> {
+                self.remove(&key)
+            }
+
+            fn get(&self, key: u32) -> Option<&u32> {
+                self.get(&key)
+            }
+
+            fn contains_key(&self, key: u32) -> bool {
+                self.contains_key(&key)
+            }
+
+            fn retain(&mut self, keep: fn(&u32, &mut u32) -> bool) {
+                self.retain(keep)
+            }
+
+            fn clear(&mut self) {
+                self.clear()
+            }
+
+            fn len(&self) -> usize {
+                self.len()
+            }
+
+            fn pairs(&self) -> Vec<(u32, u32)> {
+                sorted(self.iter())
+            }
+
+            fn bump(&mut self, key: u32) {
+                *self.entry(key).or_insert(0) += 1;
+            }
+
+            fn toggle(&mut self, key: u32, value: u32) -> Option<(u32, u32)> {
+                match self.entry(key) {
+                    $($module)::+::Entry::Occupied(e) => Some(e.remove_entry()),
+                    $($module)::+::Entry::Vacant(e) => {
+                        e.insert(value);
+                        None
+                    }
+                }
+            }
+        }
+    )*};
+}
+
+test_maps! {
+    HashMap in crate;
+    HashMap<crate::LinearTable> in crate;
+    CuckooMap in crate::cuckoo;
+    HopscotchMap in crate::hopscotch;
+    RobinHoodMap in crate::robin_hood;
+    SmallMap in crate::small;
+    SoaHashMap in crate::soa;
+    #[cfg(feature = "swiss")]
+    HashMap<crate::SwissTable> in crate;
+}
+
+/// Runs the same random operations against `map` and a chaining
+/// `HashMap`, which must agree after every step. `check` looks at the
+/// map's own invariants every fifty steps and once more at the end.
+pub(crate) fn differential<M: TestMap>(seed: u64, keys: u64, mut map: M, mut check: impl FnMut(&M)) {
+    let mut rng = XorShift(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1);
+    let mut model = HashMap::new();
+    for step in 0..4000 {
+        let key = (rng.next() % keys) as u32;
+        let value = rng.next() as u32;
+        match rng.next() % 10 {
+            0..=2 => assert_eq!(map.insert(key, value), model.insert(key, value)),
+            3..=4 => assert_eq!(map.remove(key), model.remove(&key)),
+            5 => {
+                map.bump(key);
+                model.bump(key);
+            }
+            6 => assert_eq!(map.toggle(key, value), model.toggle(key, value)),
+            7 => assert_eq!(map.get(key), model.get(&key)),
+            8 if step % 97 == 0 => {
+                map.retain(|&k, _| k % 3 != 0);
+                model.retain(|&k, _| k % 3 != 0);
+            }
+            // rare enough that a `SmallMap` gets past its threshold
+            // between clears
+            8 if step % 389 == 0 => {
+                map.clear();
+                model.clear();
+            }
+            _ => assert_eq!(map.contains_key(key), model.contains_key(&key)),
+        }
+        assert_eq!(map.len(), model.len());
+        if step % 50 == 0 {
+            check(&map);
+            assert_eq!(map.pairs(), model.pairs());
+        }
+    }
+    check(&map);
+    assert_eq!(map.pairs(), model.pairs());
+}